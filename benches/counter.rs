@@ -11,9 +11,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, criterion_group, criterion_main, BenchmarkGroup, BenchmarkId, Criterion,
+};
 use fnv::FnvBuildHasher;
-use prometheus::{Counter, CounterVec, IntCounter, Opts};
+use prometheus::core::{Atomic, GenericCounter};
+use prometheus::{Counter, CounterVec, IntCounter, Opts, StripedCounter, StripedIntCounter};
 use std::collections::HashMap;
 use std::sync::{atomic, Arc};
 use std::thread;
@@ -180,6 +184,71 @@ fn bench_int_counter_no_labels_concurrent_write(c: &mut Criterion) {
     }
 }
 
+/// Benchmarks `inc` of plain and striped counters side by side, while 4, 16
+/// and 64 background threads increment the same counter.
+fn bench_striped_counter_no_labels_concurrent_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("striped_counter_no_labels_concurrent_write");
+    for &threads in &[4, 16, 64] {
+        bench_concurrent_inc(
+            &mut group,
+            "int_counter",
+            threads,
+            IntCounter::new("foo", "bar").unwrap(),
+        );
+        bench_concurrent_inc(
+            &mut group,
+            "striped_int_counter",
+            threads,
+            StripedIntCounter::new("foo", "bar").unwrap(),
+        );
+        bench_concurrent_inc(
+            &mut group,
+            "counter",
+            threads,
+            Counter::new("foo", "bar").unwrap(),
+        );
+        bench_concurrent_inc(
+            &mut group,
+            "striped_counter",
+            threads,
+            StripedCounter::new("foo", "bar").unwrap(),
+        );
+    }
+    group.finish();
+}
+
+fn bench_concurrent_inc<P: Atomic + 'static>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    threads: usize,
+    counter: GenericCounter<P>,
+) {
+    let signal_exit = Arc::new(atomic::AtomicBool::new(false));
+
+    let thread_handles: Vec<_> = (0..threads)
+        .map(|_| {
+            let signal_exit2 = signal_exit.clone();
+            let counter2 = counter.clone();
+            thread::spawn(move || {
+                while !signal_exit2.load(atomic::Ordering::Relaxed) {
+                    // Update counter concurrently as the normal group.
+                    counter2.inc();
+                }
+            })
+        })
+        .collect();
+
+    group.bench_function(BenchmarkId::new(name, threads), |b| {
+        b.iter(|| counter.inc());
+    });
+
+    // Wait for accompanying threads to exit.
+    signal_exit.store(true, atomic::Ordering::Relaxed);
+    for h in thread_handles {
+        h.join().unwrap();
+    }
+}
+
 fn bench_counter_with_label_values_concurrent_write(c: &mut Criterion) {
     let signal_exit = Arc::new(atomic::AtomicBool::new(false));
     let counter = CounterVec::new(Opts::new("foo", "bar"), &["one", "two", "three"]).unwrap();
@@ -219,5 +288,6 @@ criterion_group!(
     bench_counter_with_prepared_mapped_labels,
     bench_int_counter_no_labels,
     bench_int_counter_no_labels_concurrent_write,
+    bench_striped_counter_no_labels_concurrent_write,
 );
 criterion_main!(benches);
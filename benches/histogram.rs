@@ -11,8 +11,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use criterion::{black_box, BenchmarkId};
 use criterion::{criterion_group, criterion_main, Criterion};
-use prometheus::{core::Collector, Histogram, HistogramOpts, HistogramVec};
+use prometheus::{
+    core::Collector, exponential_buckets, linear_buckets, Histogram, HistogramOpts, HistogramVec,
+};
 use std::sync::{atomic, Arc};
 use std::thread;
 
@@ -78,6 +81,65 @@ fn bench_local_histogram_timer(c: &mut Criterion) {
     local.flush();
 }
 
+/// Benchmarks `observe` with many evenly spaced, exponential and irregular
+/// buckets. The `emulated_linear_scan` cases are not the previous lookup but
+/// an approximation of it, which scans the same upper bounds before observing
+/// into a single bucket histogram, as a rough point of comparison.
+fn bench_histogram_many_buckets(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bench_histogram_many_buckets");
+    let single_bucket = Histogram::with_opts(
+        HistogramOpts::new("benchmark_histogram", "A histogram to benchmark it.")
+            .buckets(vec![1.0]),
+    )
+    .unwrap();
+    for &count in &[16, 50, 200] {
+        let cases = vec![
+            ("linear", linear_buckets(0.0, 0.005, count).unwrap()),
+            (
+                "exponential",
+                exponential_buckets(0.0001, 1.1, count).unwrap(),
+            ),
+            (
+                "irregular",
+                (0..count).map(|i| ((i * i) as f64 + 0.5) / 1e4).collect(),
+            ),
+        ];
+        for (kind, buckets) in cases {
+            // Observations spread over all buckets.
+            let max = buckets[count - 1] * 1.1;
+            let values: Vec<f64> = (0..1024).map(|i| max * i as f64 / 1024.0).collect();
+
+            let histogram = Histogram::with_opts(
+                HistogramOpts::new("benchmark_histogram", "A histogram to benchmark it.")
+                    .buckets(buckets.clone()),
+            )
+            .unwrap();
+            group.bench_with_input(BenchmarkId::new(kind, count), &values, |b, values| {
+                let mut i = 0;
+                b.iter(|| {
+                    histogram.observe(values[i & 1023]);
+                    i += 1;
+                })
+            });
+
+            group.bench_with_input(
+                BenchmarkId::new(format!("{}_emulated_linear_scan", kind), count),
+                &values,
+                |b, values| {
+                    let mut i = 0;
+                    b.iter(|| {
+                        let v = values[i & 1023];
+                        i += 1;
+                        black_box(buckets.iter().position(|&b| v <= b));
+                        single_bucket.observe(v);
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
 fn concurrent_observe_and_collect(c: &mut Criterion) {
     let signal_exit = Arc::new(atomic::AtomicBool::new(false));
     let opts = HistogramOpts::new("test_name", "test help").buckets(vec![1.0]);
@@ -116,6 +178,7 @@ criterion_group!(
     bench_histogram_timer,
     bench_histogram_local,
     bench_local_histogram_timer,
+    bench_histogram_many_buckets,
     concurrent_observe_and_collect,
 );
 criterion_main!(benches);
@@ -383,6 +383,8 @@ pub struct Counter {
     // message fields
     // @@protoc_insertion_point(field:io.prometheus.client.Counter.value)
     pub value: ::std::option::Option<f64>,
+    // @@protoc_insertion_point(field:io.prometheus.client.Counter.created_timestamp)
+    pub created_timestamp: ::protobuf::MessageField<::protobuf::well_known_types::timestamp::Timestamp>,
     // special fields
     // @@protoc_insertion_point(special_field:io.prometheus.client.Counter.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -419,13 +421,18 @@ impl Counter {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(1);
+        let mut fields = ::std::vec::Vec::with_capacity(2);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
             "value",
             |m: &Counter| { &m.value },
             |m: &mut Counter| { &mut m.value },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_message_field_accessor::<_, ::protobuf::well_known_types::timestamp::Timestamp>(
+            "created_timestamp",
+            |m: &Counter| { &m.created_timestamp },
+            |m: &mut Counter| { &mut m.created_timestamp },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<Counter>(
             "Counter",
             fields,
@@ -447,6 +454,9 @@ impl ::protobuf::Message for Counter {
                 9 => {
                     self.value = ::std::option::Option::Some(is.read_double()?);
                 },
+                26 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.created_timestamp)?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -462,6 +472,10 @@ impl ::protobuf::Message for Counter {
         if let Some(v) = self.value {
             my_size += 1 + 8;
         }
+        if let Some(v) = self.created_timestamp.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -471,6 +485,9 @@ impl ::protobuf::Message for Counter {
         if let Some(v) = self.value {
             os.write_double(1, v)?;
         }
+        if let Some(v) = self.created_timestamp.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(3, v, os)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -489,12 +506,14 @@ impl ::protobuf::Message for Counter {
 
     fn clear(&mut self) {
         self.value = ::std::option::Option::None;
+        self.created_timestamp.clear();
         self.special_fields.clear();
     }
 
     fn default_instance() -> &'static Counter {
         static instance: Counter = Counter {
             value: ::std::option::Option::None,
+            created_timestamp: ::protobuf::MessageField::none(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -706,6 +725,8 @@ pub struct Summary {
     pub sample_sum: ::std::option::Option<f64>,
     // @@protoc_insertion_point(field:io.prometheus.client.Summary.quantile)
     pub quantile: ::std::vec::Vec<Quantile>,
+    // @@protoc_insertion_point(field:io.prometheus.client.Summary.created_timestamp)
+    pub created_timestamp: ::protobuf::MessageField<::protobuf::well_known_types::timestamp::Timestamp>,
     // special fields
     // @@protoc_insertion_point(special_field:io.prometheus.client.Summary.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -761,7 +782,7 @@ impl Summary {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut fields = ::std::vec::Vec::with_capacity(4);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
             "sample_count",
@@ -778,6 +799,11 @@ impl Summary {
             |m: &Summary| { &m.quantile },
             |m: &mut Summary| { &mut m.quantile },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_message_field_accessor::<_, ::protobuf::well_known_types::timestamp::Timestamp>(
+            "created_timestamp",
+            |m: &Summary| { &m.created_timestamp },
+            |m: &mut Summary| { &mut m.created_timestamp },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<Summary>(
             "Summary",
             fields,
@@ -805,6 +831,9 @@ impl ::protobuf::Message for Summary {
                 26 => {
                     self.quantile.push(is.read_message()?);
                 },
+                34 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.created_timestamp)?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -827,6 +856,10 @@ impl ::protobuf::Message for Summary {
             let len = value.compute_size();
             my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
         };
+        if let Some(v) = self.created_timestamp.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -842,6 +875,9 @@ impl ::protobuf::Message for Summary {
         for v in &self.quantile {
             ::protobuf::rt::write_message_field_with_cached_size(3, v, os)?;
         };
+        if let Some(v) = self.created_timestamp.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(4, v, os)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -862,6 +898,7 @@ impl ::protobuf::Message for Summary {
         self.sample_count = ::std::option::Option::None;
         self.sample_sum = ::std::option::Option::None;
         self.quantile.clear();
+        self.created_timestamp.clear();
         self.special_fields.clear();
     }
 
@@ -870,6 +907,7 @@ impl ::protobuf::Message for Summary {
             sample_count: ::std::option::Option::None,
             sample_sum: ::std::option::Option::None,
             quantile: ::std::vec::Vec::new(),
+            created_timestamp: ::protobuf::MessageField::none(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -1044,6 +1082,8 @@ pub struct Histogram {
     pub sample_sum: ::std::option::Option<f64>,
     // @@protoc_insertion_point(field:io.prometheus.client.Histogram.bucket)
     pub bucket: ::std::vec::Vec<Bucket>,
+    // @@protoc_insertion_point(field:io.prometheus.client.Histogram.created_timestamp)
+    pub created_timestamp: ::protobuf::MessageField<::protobuf::well_known_types::timestamp::Timestamp>,
     // special fields
     // @@protoc_insertion_point(special_field:io.prometheus.client.Histogram.special_fields)
     pub special_fields: ::protobuf::SpecialFields,
@@ -1099,7 +1139,7 @@ impl Histogram {
     }
 
     fn generated_message_descriptor_data() -> ::protobuf::reflect::GeneratedMessageDescriptorData {
-        let mut fields = ::std::vec::Vec::with_capacity(3);
+        let mut fields = ::std::vec::Vec::with_capacity(4);
         let mut oneofs = ::std::vec::Vec::with_capacity(0);
         fields.push(::protobuf::reflect::rt::v2::make_option_accessor::<_, _>(
             "sample_count",
@@ -1116,6 +1156,11 @@ impl Histogram {
             |m: &Histogram| { &m.bucket },
             |m: &mut Histogram| { &mut m.bucket },
         ));
+        fields.push(::protobuf::reflect::rt::v2::make_message_field_accessor::<_, ::protobuf::well_known_types::timestamp::Timestamp>(
+            "created_timestamp",
+            |m: &Histogram| { &m.created_timestamp },
+            |m: &mut Histogram| { &mut m.created_timestamp },
+        ));
         ::protobuf::reflect::GeneratedMessageDescriptorData::new_2::<Histogram>(
             "Histogram",
             fields,
@@ -1143,6 +1188,9 @@ impl ::protobuf::Message for Histogram {
                 26 => {
                     self.bucket.push(is.read_message()?);
                 },
+                122 => {
+                    ::protobuf::rt::read_singular_message_into_field(is, &mut self.created_timestamp)?;
+                },
                 tag => {
                     ::protobuf::rt::read_unknown_or_skip_group(tag, is, self.special_fields.mut_unknown_fields())?;
                 },
@@ -1165,6 +1213,10 @@ impl ::protobuf::Message for Histogram {
             let len = value.compute_size();
             my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
         };
+        if let Some(v) = self.created_timestamp.as_ref() {
+            let len = v.compute_size();
+            my_size += 1 + ::protobuf::rt::compute_raw_varint64_size(len) + len;
+        }
         my_size += ::protobuf::rt::unknown_fields_size(self.special_fields.unknown_fields());
         self.special_fields.cached_size().set(my_size as u32);
         my_size
@@ -1180,6 +1232,9 @@ impl ::protobuf::Message for Histogram {
         for v in &self.bucket {
             ::protobuf::rt::write_message_field_with_cached_size(3, v, os)?;
         };
+        if let Some(v) = self.created_timestamp.as_ref() {
+            ::protobuf::rt::write_message_field_with_cached_size(15, v, os)?;
+        }
         os.write_unknown_fields(self.special_fields.unknown_fields())?;
         ::std::result::Result::Ok(())
     }
@@ -1200,6 +1255,7 @@ impl ::protobuf::Message for Histogram {
         self.sample_count = ::std::option::Option::None;
         self.sample_sum = ::std::option::Option::None;
         self.bucket.clear();
+        self.created_timestamp.clear();
         self.special_fields.clear();
     }
 
@@ -1208,6 +1264,7 @@ impl ::protobuf::Message for Histogram {
             sample_count: ::std::option::Option::None,
             sample_sum: ::std::option::Option::None,
             bucket: ::std::vec::Vec::new(),
+            created_timestamp: ::protobuf::MessageField::none(),
             special_fields: ::protobuf::SpecialFields::new(),
         };
         &instance
@@ -2013,36 +2070,42 @@ impl MetricType {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x11proto_model.proto\x12\x14io.prometheus.client\"5\n\tLabelPair\x12\
-    \x12\n\x04name\x18\x01\x20\x01(\tR\x04name\x12\x14\n\x05value\x18\x02\
-    \x20\x01(\tR\x05value\"\x1d\n\x05Gauge\x12\x14\n\x05value\x18\x01\x20\
-    \x01(\x01R\x05value\"\x1f\n\x07Counter\x12\x14\n\x05value\x18\x01\x20\
-    \x01(\x01R\x05value\"<\n\x08Quantile\x12\x1a\n\x08quantile\x18\x01\x20\
-    \x01(\x01R\x08quantile\x12\x14\n\x05value\x18\x02\x20\x01(\x01R\x05value\
-    \"\x87\x01\n\x07Summary\x12!\n\x0csample_count\x18\x01\x20\x01(\x04R\x0b\
-    sampleCount\x12\x1d\n\nsample_sum\x18\x02\x20\x01(\x01R\tsampleSum\x12:\
-    \n\x08quantile\x18\x03\x20\x03(\x0b2\x1e.io.prometheus.client.QuantileR\
-    \x08quantile\"\x1f\n\x07Untyped\x12\x14\n\x05value\x18\x01\x20\x01(\x01R\
-    \x05value\"\x83\x01\n\tHistogram\x12!\n\x0csample_count\x18\x01\x20\x01(\
-    \x04R\x0bsampleCount\x12\x1d\n\nsample_sum\x18\x02\x20\x01(\x01R\tsample\
-    Sum\x124\n\x06bucket\x18\x03\x20\x03(\x0b2\x1c.io.prometheus.client.Buck\
-    etR\x06bucket\"T\n\x06Bucket\x12)\n\x10cumulative_count\x18\x01\x20\x01(\
-    \x04R\x0fcumulativeCount\x12\x1f\n\x0bupper_bound\x18\x02\x20\x01(\x01R\
-    \nupperBound\"\xff\x02\n\x06Metric\x125\n\x05label\x18\x01\x20\x03(\x0b2\
-    \x1f.io.prometheus.client.LabelPairR\x05label\x121\n\x05gauge\x18\x02\
-    \x20\x01(\x0b2\x1b.io.prometheus.client.GaugeR\x05gauge\x127\n\x07counte\
-    r\x18\x03\x20\x01(\x0b2\x1d.io.prometheus.client.CounterR\x07counter\x12\
+    \n\x11proto_model.proto\x12\x14io.prometheus.client\x1a\x1fgoogle/proto\
+    buf/timestamp.proto\"5\n\tLabelPair\x12\x12\n\x04name\x18\x01\x20\x01(\
+    \tR\x04name\x12\x14\n\x05value\x18\x02\x20\x01(\tR\x05value\"\x1d\n\x05\
+    Gauge\x12\x14\n\x05value\x18\x01\x20\x01(\x01R\x05value\"h\n\x07Counter\
+    \x12\x14\n\x05value\x18\x01\x20\x01(\x01R\x05value\x12G\n\x11created_ti\
+    mestamp\x18\x03\x20\x01(\x0b2\x1a.google.protobuf.TimestampR\x10created\
+    Timestamp\"<\n\x08Quantile\x12\x1a\n\x08quantile\x18\x01\x20\x01(\x01R\
+    \x08quantile\x12\x14\n\x05value\x18\x02\x20\x01(\x01R\x05value\"\xd0\
+    \x01\n\x07Summary\x12!\n\x0csample_count\x18\x01\x20\x01(\x04R\x0bsampl\
+    eCount\x12\x1d\n\nsample_sum\x18\x02\x20\x01(\x01R\tsampleSum\x12:\n\
+    \x08quantile\x18\x03\x20\x03(\x0b2\x1e.io.prometheus.client.QuantileR\
+    \x08quantile\x12G\n\x11created_timestamp\x18\x04\x20\x01(\x0b2\x1a.goog\
+    le.protobuf.TimestampR\x10createdTimestamp\"\x1f\n\x07Untyped\x12\x14\n\
+    \x05value\x18\x01\x20\x01(\x01R\x05value\"\xcc\x01\n\tHistogram\x12!\n\
+    \x0csample_count\x18\x01\x20\x01(\x04R\x0bsampleCount\x12\x1d\n\nsample\
+    _sum\x18\x02\x20\x01(\x01R\tsampleSum\x124\n\x06bucket\x18\x03\x20\x03(\
+    \x0b2\x1c.io.prometheus.client.BucketR\x06bucket\x12G\n\x11created_time\
+    stamp\x18\x0f\x20\x01(\x0b2\x1a.google.protobuf.TimestampR\x10createdTi\
+    mestamp\"T\n\x06Bucket\x12)\n\x10cumulative_count\x18\x01\x20\x01(\x04R\
+    \x0fcumulativeCount\x12\x1f\n\x0bupper_bound\x18\x02\x20\x01(\x01R\nupp\
+    erBound\"\xff\x02\n\x06Metric\x125\n\x05label\x18\x01\x20\x03(\x0b2\x1f\
+    .io.prometheus.client.LabelPairR\x05label\x121\n\x05gauge\x18\x02\x20\
+    \x01(\x0b2\x1b.io.prometheus.client.GaugeR\x05gauge\x127\n\x07counter\
+    \x18\x03\x20\x01(\x0b2\x1d.io.prometheus.client.CounterR\x07counter\x12\
     7\n\x07summary\x18\x04\x20\x01(\x0b2\x1d.io.prometheus.client.SummaryR\
-    \x07summary\x127\n\x07untyped\x18\x05\x20\x01(\x0b2\x1d.io.prometheus.cl\
-    ient.UntypedR\x07untyped\x12=\n\thistogram\x18\x07\x20\x01(\x0b2\x1f.io.\
-    prometheus.client.HistogramR\thistogram\x12!\n\x0ctimestamp_ms\x18\x06\
-    \x20\x01(\x03R\x0btimestampMs\"\xa2\x01\n\x0cMetricFamily\x12\x12\n\x04n\
-    ame\x18\x01\x20\x01(\tR\x04name\x12\x12\n\x04help\x18\x02\x20\x01(\tR\
-    \x04help\x124\n\x04type\x18\x03\x20\x01(\x0e2\x20.io.prometheus.client.M\
-    etricTypeR\x04type\x124\n\x06metric\x18\x04\x20\x03(\x0b2\x1c.io.prometh\
-    eus.client.MetricR\x06metric*M\n\nMetricType\x12\x0b\n\x07COUNTER\x10\0\
-    \x12\t\n\x05GAUGE\x10\x01\x12\x0b\n\x07SUMMARY\x10\x02\x12\x0b\n\x07UNTY\
-    PED\x10\x03\x12\r\n\tHISTOGRAM\x10\x04B\x16\n\x14io.prometheus.client\
+    \x07summary\x127\n\x07untyped\x18\x05\x20\x01(\x0b2\x1d.io.prometheus.c\
+    lient.UntypedR\x07untyped\x12=\n\thistogram\x18\x07\x20\x01(\x0b2\x1f.i\
+    o.prometheus.client.HistogramR\thistogram\x12!\n\x0ctimestamp_ms\x18\
+    \x06\x20\x01(\x03R\x0btimestampMs\"\xa2\x01\n\x0cMetricFamily\x12\x12\n\
+    \x04name\x18\x01\x20\x01(\tR\x04name\x12\x12\n\x04help\x18\x02\x20\x01(\
+    \tR\x04help\x124\n\x04type\x18\x03\x20\x01(\x0e2\x20.io.prometheus.clie\
+    nt.MetricTypeR\x04type\x124\n\x06metric\x18\x04\x20\x03(\x0b2\x1c.io.pr\
+    ometheus.client.MetricR\x06metric*M\n\nMetricType\x12\x0b\n\x07COUNTER\
+    \x10\0\x12\t\n\x05GAUGE\x10\x01\x12\x0b\n\x07SUMMARY\x10\x02\x12\x0b\n\
+    \x07UNTYPED\x10\x03\x12\r\n\tHISTOGRAM\x10\x04B\x16\n\x14io.prometheus.\
+    client\
 ";
 
 /// `FileDescriptorProto` object which was a source for this generated file
@@ -2059,7 +2122,8 @@ pub fn file_descriptor() -> &'static ::protobuf::reflect::FileDescriptor {
     static file_descriptor: ::protobuf::rt::Lazy<::protobuf::reflect::FileDescriptor> = ::protobuf::rt::Lazy::new();
     file_descriptor.get(|| {
         let generated_file_descriptor = generated_file_descriptor_lazy.get(|| {
-            let mut deps = ::std::vec::Vec::with_capacity(0);
+            let mut deps = ::std::vec::Vec::with_capacity(1);
+            deps.push(::protobuf::well_known_types::timestamp::file_descriptor().clone());
             let mut messages = ::std::vec::Vec::with_capacity(10);
             messages.push(LabelPair::generated_message_descriptor_data());
             messages.push(Gauge::generated_message_descriptor_data());
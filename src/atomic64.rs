@@ -4,7 +4,11 @@
 use std::cmp::*;
 use std::f64;
 use std::ops::*;
-use std::sync::atomic::{AtomicI64 as StdAtomicI64, AtomicU64 as StdAtomicU64, Ordering};
+use std::sync::atomic::{
+    AtomicI64 as StdAtomicI64, AtomicU64 as StdAtomicU64, AtomicUsize, Ordering,
+};
+use std::sync::OnceLock;
+use std::thread;
 
 /// An interface for numbers. Used to generically model float metrics and integer metrics, i.e.
 /// [`Counter`](crate::Counter) and [`IntCounter`](crate::Counter).
@@ -15,6 +19,10 @@ pub trait Number:
     fn from_i64(v: i64) -> Self;
     /// Convert to a f64.
     fn into_f64(self) -> f64;
+    /// Convert from a f64, truncating it for integers.
+    fn from_f64(v: f64) -> Self {
+        Self::from_i64(v as i64)
+    }
 }
 
 impl Number for i64 {
@@ -51,6 +59,11 @@ impl Number for f64 {
     fn into_f64(self) -> f64 {
         self
     }
+
+    #[inline]
+    fn from_f64(v: f64) -> Self {
+        v
+    }
 }
 
 /// An interface for atomics. Used to generically model float metrics and integer metrics, i.e.
@@ -70,6 +83,15 @@ pub trait Atomic: Send + Sync {
     fn dec_by(&self, delta: Self::T);
 }
 
+/// An [`Atomic`] that can atomically keep the maximum or the minimum of its
+/// value and another, e.g. for watermarks.
+pub trait AtomicMinMax: Atomic {
+    /// Set the value to `val` if it is greater.
+    fn set_max(&self, val: Self::T);
+    /// Set the value to `val` if it is less.
+    fn set_min(&self, val: Self::T);
+}
+
 /// A atomic float.
 #[derive(Debug)]
 pub struct AtomicF64 {
@@ -128,7 +150,36 @@ impl Atomic for AtomicF64 {
     }
 }
 
+impl AtomicMinMax for AtomicF64 {
+    #[inline]
+    fn set_max(&self, val: Self::T) {
+        self.store_if(val, |current| val > current);
+    }
+
+    #[inline]
+    fn set_min(&self, val: Self::T) {
+        self.store_if(val, |current| val < current);
+    }
+}
+
 impl AtomicF64 {
+    /// Store `val` unless `replace` of the current value is false.
+    #[inline]
+    fn store_if<F: Fn(f64) -> bool>(&self, val: f64, replace: F) {
+        let mut current = self.inner.load(Ordering::Relaxed);
+        while replace(u64_to_f64(current)) {
+            match self.inner.compare_exchange_weak(
+                current,
+                f64_to_u64(val),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+
     /// Store the value, returning the previous value.
     pub fn swap(&self, val: f64, ordering: Ordering) -> f64 {
         u64_to_f64(self.inner.swap(f64_to_u64(val), ordering))
@@ -171,6 +222,18 @@ impl Atomic for AtomicI64 {
     }
 }
 
+impl AtomicMinMax for AtomicI64 {
+    #[inline]
+    fn set_max(&self, val: Self::T) {
+        self.inner.fetch_max(val, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn set_min(&self, val: Self::T) {
+        self.inner.fetch_min(val, Ordering::Relaxed);
+    }
+}
+
 /// A atomic unsigned integer.
 #[derive(Debug)]
 pub struct AtomicU64 {
@@ -207,6 +270,18 @@ impl Atomic for AtomicU64 {
     }
 }
 
+impl AtomicMinMax for AtomicU64 {
+    #[inline]
+    fn set_max(&self, val: Self::T) {
+        self.inner.fetch_max(val, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn set_min(&self, val: Self::T) {
+        self.inner.fetch_min(val, Ordering::Relaxed);
+    }
+}
+
 impl AtomicU64 {
     /// Stores a value into the atomic integer if the current value is the same
     /// as the current value.
@@ -239,10 +314,102 @@ impl AtomicU64 {
     }
 }
 
+/// The maximum number of slots of a [`ShardedAtomic`].
+const MAX_SHARDS: usize = 64;
+
+/// Pads and aligns a value to 128 bytes, so that neighbouring slots never share
+/// a cache line, even with adjacent-line prefetching.
+#[derive(Debug)]
+#[repr(align(128))]
+struct CachePadded<T>(T);
+
+/// The number of slots of every [`ShardedAtomic`], the available parallelism
+/// rounded up to a power of two.
+fn shard_count() -> usize {
+    static SHARDS: OnceLock<usize> = OnceLock::new();
+    *SHARDS.get_or_init(|| {
+        thread::available_parallelism()
+            .map_or(1, |n| n.get())
+            .next_power_of_two()
+            .min(MAX_SHARDS)
+    })
+}
+
+/// The slot index of the current thread. Threads are assigned indexes round
+/// robin as they first touch a [`ShardedAtomic`].
+fn shard_index() -> usize {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static INDEX: usize = NEXT.fetch_add(1, Ordering::Relaxed);
+    }
+    INDEX.with(|i| *i)
+}
+
+/// A striped atomic, for values updated by many threads at once.
+///
+/// Updates go to a cache-padded slot of the current thread, so concurrent
+/// increments don't contend on one cache line, while [`get`](Atomic::get)
+/// sums all slots. It trades memory and slower reads for faster writes, and
+/// plugs into [`GenericCounter`](crate::core::GenericCounter) and
+/// [`GenericGauge`](crate::core::GenericGauge) like any other [`Atomic`].
+///
+/// [`set`](Atomic::set) isn't atomic with concurrent updates, which may be
+/// lost. Slots of an unsigned atomic can't go below zero on their own, so use
+/// a signed or float atomic for gauges.
+#[derive(Debug)]
+pub struct ShardedAtomic<A: Atomic> {
+    slots: Box<[CachePadded<A>]>,
+}
+
+impl<A: Atomic> ShardedAtomic<A> {
+    #[inline]
+    fn slot(&self) -> &A {
+        &self.slots[shard_index() & (self.slots.len() - 1)].0
+    }
+}
+
+impl<A: Atomic> Atomic for ShardedAtomic<A> {
+    type T = A::T;
+
+    fn new(val: Self::T) -> ShardedAtomic<A> {
+        let slots = (0..shard_count())
+            .map(|i| CachePadded(A::new(if i == 0 { val } else { A::T::from_i64(0) })))
+            .collect();
+        ShardedAtomic { slots }
+    }
+
+    fn set(&self, val: Self::T) {
+        let (first, rest) = self.slots.split_first().unwrap();
+        for slot in rest {
+            slot.0.set(A::T::from_i64(0));
+        }
+        first.0.set(val);
+    }
+
+    fn get(&self) -> Self::T {
+        let mut sum = A::T::from_i64(0);
+        for slot in self.slots.iter() {
+            sum += slot.0.get();
+        }
+        sum
+    }
+
+    #[inline]
+    fn inc_by(&self, delta: Self::T) {
+        self.slot().inc_by(delta);
+    }
+
+    #[inline]
+    fn dec_by(&self, delta: Self::T) {
+        self.slot().dec_by(delta);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::f64;
     use std::f64::consts::PI;
+    use std::sync::Arc;
 
     use super::*;
 
@@ -275,4 +442,32 @@ mod test {
         au64.inc_by(123);
         assert_eq!(au64.get(), 123);
     }
+
+    #[test]
+    fn test_sharded_atomic() {
+        let sharded = Arc::new(ShardedAtomic::<AtomicF64>::new(1.0));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let sharded = Arc::clone(&sharded);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        sharded.inc_by(1.0);
+                    }
+                    sharded.dec_by(0.5);
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(sharded.get(), 8001.0 - 4.0);
+
+        sharded.set(3.0);
+        assert_eq!(sharded.get(), 3.0);
+
+        let sharded = ShardedAtomic::<AtomicI64>::new(0);
+        sharded.dec_by(5);
+        sharded.inc_by(2);
+        assert_eq!(sharded.get(), -3);
+    }
 }
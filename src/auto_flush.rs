@@ -1,5 +1,6 @@
 use crate::core::Atomic;
 use crate::counter::{CounterWithValueType, GenericLocalCounter};
+use crate::gauge::{GaugeWithValueType, GenericLocalGauge};
 use crate::histogram::{Instant, LocalHistogram};
 use crate::metrics::MayFlush;
 use crate::timer;
@@ -15,6 +16,15 @@ pub trait CounterDelegator<T: 'static + MayFlush, V: CounterWithValueType> {
     fn get_local<'a>(&self, root_metric: &'a T) -> &'a GenericLocalCounter<V::ValueType>;
 }
 
+/// Delegator for auto flush-able local gauge
+pub trait GaugeDelegator<T: 'static + MayFlush, V: GaugeWithValueType> {
+    /// Get the root local metric for delegate
+    fn get_root_metric(&self) -> &'static LocalKey<T>;
+
+    /// Get the final gauge for delegate
+    fn get_local<'a>(&self, root_metric: &'a T) -> &'a GenericLocalGauge<V::ValueType>;
+}
+
 /// Delegator for auto flush-able local counter
 pub trait HistogramDelegator<T: 'static + MayFlush> {
     /// Get the root local metric for delegate
@@ -113,6 +123,85 @@ impl<T: 'static + MayFlush, V: CounterWithValueType, D: CounterDelegator<T, V>>
     }
 }
 
+/// Auto flush-able local gauge
+#[derive(Debug)]
+pub struct AFLocalGauge<T: 'static + MayFlush, V: GaugeWithValueType, D: GaugeDelegator<T, V>> {
+    /// Delegator to get thread local metric
+    delegator: D,
+    /// Phantomdata marker
+    _p: std::marker::PhantomData<(Mutex<T>, Mutex<V>)>,
+}
+
+impl<T: 'static + MayFlush, V: GaugeWithValueType, D: GaugeDelegator<T, V>> AFLocalGauge<T, V, D> {
+    /// Construct a new AFLocalGauge from delegator.
+    pub fn new(delegator: D) -> AFLocalGauge<T, V, D> {
+        timer::ensure_updater();
+        AFLocalGauge {
+            delegator,
+            _p: std::marker::PhantomData,
+        }
+    }
+
+    #[inline]
+    /// Apply `f` to the local gauge and try to flush to global.
+    fn update<F: FnOnce(&GenericLocalGauge<V::ValueType>)>(&self, f: F) {
+        self.delegator.get_root_metric().with(|m| {
+            f(self.delegator.get_local(m));
+            m.may_flush();
+        })
+    }
+
+    /// Increase the local gauge by 1,
+    /// and try to flush to global.
+    #[inline]
+    pub fn inc(&self) {
+        self.update(|gauge| gauge.inc())
+    }
+
+    /// Decrease the local gauge by 1,
+    /// and try to flush to global.
+    #[inline]
+    pub fn dec(&self) {
+        self.update(|gauge| gauge.dec())
+    }
+
+    /// Add the given value to the local gauge,
+    /// and try to flush to global.
+    #[inline]
+    pub fn add(&self, v: <V::ValueType as Atomic>::T) {
+        self.update(|gauge| gauge.add(v))
+    }
+
+    /// Subtract the given value from the local gauge,
+    /// and try to flush to global.
+    #[inline]
+    pub fn sub(&self, v: <V::ValueType as Atomic>::T) {
+        self.update(|gauge| gauge.sub(v))
+    }
+
+    /// Return the change of the gauge not yet flushed.
+    #[inline]
+    pub fn get(&self) -> <V::ValueType as Atomic>::T {
+        self.delegator
+            .get_root_metric()
+            .with(|m| self.delegator.get_local(m).get())
+    }
+
+    /// Drop the change of the gauge not yet flushed.
+    #[inline]
+    pub fn clear(&self) {
+        self.delegator
+            .get_root_metric()
+            .with(|m| self.delegator.get_local(m).clear())
+    }
+
+    /// trigger flush of LocalKey<T>
+    #[inline]
+    pub fn flush(&self) {
+        self.delegator.get_root_metric().with(|m| m.flush())
+    }
+}
+
 /// Auto flush-able local counter
 #[derive(Debug)]
 pub struct AFLocalHistogram<T: 'static + MayFlush, D: HistogramDelegator<T>> {
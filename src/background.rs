@@ -0,0 +1,98 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use crate::errors::{Error, Result};
+
+/// `BackgroundThread` is a named thread of an exporter which runs until it is
+/// stopped, e.g. by a [`PeriodicTask`](crate::PeriodicTask) or a
+/// [`PushScheduler`](crate::PushScheduler).
+///
+/// Stopping the thread, explicitly or by dropping it, wakes it up and waits
+/// for it to finish.
+#[derive(Debug)]
+pub(crate) struct BackgroundThread {
+    name: String,
+    shutdown: Option<Sender<()>>,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl BackgroundThread {
+    /// `spawn` starts a thread named `name` running `f`, which should return
+    /// once its [`Shutdown`] signals.
+    pub(crate) fn spawn<F>(name: &str, f: F) -> BackgroundThread
+    where
+        F: FnOnce(Shutdown) -> Result<()> + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+        let handle = thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || f(Shutdown(rx)))
+            .unwrap_or_else(|e| panic!("failed to spawn thread {}: {}", name, e));
+
+        BackgroundThread {
+            name: name.to_owned(),
+            shutdown: Some(tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// `stop` signals the thread to shut down, waits for it, and returns its
+    /// result. Later calls return `Ok(())`.
+    pub(crate) fn stop(&mut self) -> Result<()> {
+        // Dropping the sender wakes up the thread.
+        self.shutdown.take();
+        match self.handle.take() {
+            Some(handle) => handle.join().unwrap_or_else(|_| {
+                Err(Error::ThreadPanicked {
+                    name: self.name.clone(),
+                })
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for BackgroundThread {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+/// The receiving end of the shutdown signal of a [`BackgroundThread`].
+#[derive(Debug)]
+pub(crate) struct Shutdown(Receiver<()>);
+
+impl Shutdown {
+    /// `wait_until` blocks until `deadline`, and returns whether the thread
+    /// was stopped before.
+    pub(crate) fn wait_until(&self, deadline: Instant) -> bool {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        self.0.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_background_thread() {
+        let mut thread = BackgroundThread::spawn("test-background", |shutdown| {
+            while !shutdown.wait_until(Instant::now() + Duration::from_secs(3600)) {}
+            Ok(())
+        });
+        thread.stop().unwrap();
+        thread.stop().unwrap();
+
+        let mut thread = BackgroundThread::spawn("test-panic", |_| panic!("test panic"));
+        match thread.stop() {
+            Err(Error::ThreadPanicked { name }) => assert_eq!(name, "test-panic"),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
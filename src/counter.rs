@@ -6,7 +6,7 @@ use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use crate::atomic64::{Atomic, AtomicF64, AtomicU64, Number};
+use crate::atomic64::{Atomic, AtomicF64, AtomicU64, Number, ShardedAtomic};
 use crate::desc::Desc;
 use crate::errors::Result;
 use crate::metrics::{Collector, LocalMetric, Metric, Opts};
@@ -27,6 +27,13 @@ pub type Counter = GenericCounter<AtomicF64>;
 /// are all positive integers (natural numbers).
 pub type IntCounter = GenericCounter<AtomicU64>;
 
+/// A [`Counter`] backed by a [`ShardedAtomic`], for counters incremented by
+/// many threads at once.
+pub type StripedCounter = GenericCounter<ShardedAtomic<AtomicF64>>;
+
+/// The integer version of [`StripedCounter`].
+pub type StripedIntCounter = GenericCounter<ShardedAtomic<AtomicU64>>;
+
 impl<P: Atomic> Clone for GenericCounter<P> {
     fn clone(&self) -> Self {
         Self {
@@ -75,10 +82,11 @@ impl<P: Atomic> GenericCounter<P> {
         self.v.get()
     }
 
-    /// Restart the counter, resetting its value back to 0.
+    /// Restart the counter, resetting its value back to 0 and its created
+    /// timestamp to now.
     #[inline]
     pub fn reset(&self) {
-        self.v.set(P::T::from_i64(0))
+        self.v.reset()
     }
 
     /// Return a [`GenericLocalCounter`] for single thread usage.
@@ -373,6 +381,31 @@ mod tests {
         assert_eq!(counter.get(), 0);
     }
 
+    #[test]
+    fn test_striped_counter() {
+        let counter = StripedIntCounter::new("foo", "bar").unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..100 {
+                        counter.inc();
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(counter.get(), 400);
+
+        let mf = counter.collect().pop().unwrap();
+        assert_eq!(mf.get_metric()[0].get_counter().get_value() as u64, 400);
+
+        counter.reset();
+        assert_eq!(counter.get(), 0);
+    }
+
     #[test]
     fn test_local_counter() {
         let counter = Counter::new("counter", "counter helper").unwrap();
@@ -630,6 +663,31 @@ mod tests {
         assert_eq!(vec.with_label_values(&["v1", "v2"]).get(), 34);
     }
 
+    #[test]
+    fn test_counter_created_timestamp() {
+        let created = |m: proto::Metric| {
+            let t = m.get_counter().get_created_timestamp().unwrap().clone();
+            (t.seconds, t.nanos)
+        };
+
+        let counter = Counter::new("test_created", "test help").unwrap();
+        let first = created(counter.metric());
+        assert!(first.0 > 0);
+        counter.inc();
+        assert_eq!(created(counter.metric()), first);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        counter.reset();
+        assert!(created(counter.metric()) > first);
+
+        // Each child has its own created timestamp.
+        let vec = CounterVec::new(Opts::new("test_created", "test help"), &["l"]).unwrap();
+        let a = created(vec.with_label_values(&["a"]).metric());
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let b = created(vec.with_label_values(&["b"]).metric());
+        assert!(b > a);
+        assert_eq!(created(vec.with_label_values(&["a"]).metric()), a);
+    }
+
     #[cfg(debug_assertions)]
     #[test]
     #[should_panic(expected = "assertion failed")]
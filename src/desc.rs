@@ -99,14 +99,11 @@ impl Desc {
         };
 
         if desc.help.is_empty() {
-            return Err(Error::Msg("empty help string".into()));
+            return Err(Error::EmptyHelp { name: desc.fq_name });
         }
 
         if !is_valid_metric_name(&desc.fq_name) {
-            return Err(Error::Msg(format!(
-                "'{}' is not a valid metric name",
-                desc.fq_name
-            )));
+            return Err(Error::InvalidMetricName { name: desc.fq_name });
         }
 
         let mut label_values = Vec::with_capacity(const_labels.len() + 1);
@@ -116,17 +113,15 @@ impl Desc {
 
         for label_name in const_labels.keys() {
             if !is_valid_label_name(label_name) {
-                return Err(Error::Msg(format!(
-                    "'{}' is not a valid label name",
-                    &label_name
-                )));
+                return Err(Error::InvalidLabelName {
+                    name: label_name.clone(),
+                });
             }
 
             if !label_names.insert(label_name.clone()) {
-                return Err(Error::Msg(format!(
-                    "duplicate const label name {}",
-                    label_name
-                )));
+                return Err(Error::DuplicateLabel {
+                    name: label_name.clone(),
+                });
             }
         }
 
@@ -140,17 +135,15 @@ impl Desc {
         // dimension with a different mix between preset and variable labels.
         for label_name in &desc.variable_labels {
             if !is_valid_label_name(label_name) {
-                return Err(Error::Msg(format!(
-                    "'{}' is not a valid label name",
-                    &label_name
-                )));
+                return Err(Error::InvalidLabelName {
+                    name: label_name.clone(),
+                });
             }
 
             if !label_names.insert(format!("${}", label_name)) {
-                return Err(Error::Msg(format!(
-                    "duplicate variable label name {}",
-                    label_name
-                )));
+                return Err(Error::DuplicateLabel {
+                    name: label_name.clone(),
+                });
             }
         }
 
@@ -255,7 +248,7 @@ mod tests {
             )
             .expect_err(format!("expected error for {}", name).as_ref());
             match res {
-                Error::Msg(msg) => assert_eq!(msg, format!("'{}' is not a valid label name", name)),
+                Error::InvalidLabelName { name: got } => assert_eq!(got, name),
                 other => panic!("{}", other),
             };
         }
@@ -269,21 +262,39 @@ mod tests {
             let res = Desc::new("name".into(), "help".into(), vec![], labels)
                 .expect_err(format!("expected error for {}", name).as_ref());
             match res {
-                Error::Msg(msg) => assert_eq!(msg, format!("'{}' is not a valid label name", name)),
+                Error::InvalidLabelName { name: got } => assert_eq!(got, name),
                 other => panic!("{}", other),
             };
         }
     }
 
+    #[test]
+    fn test_empty_help_and_duplicate_label() {
+        let res = Desc::new("name".into(), "".into(), vec![], HashMap::new());
+        match res.unwrap_err() {
+            Error::EmptyHelp { name } => assert_eq!(name, "name"),
+            other => panic!("{}", other),
+        };
+
+        let res = Desc::new(
+            "name".into(),
+            "help".into(),
+            vec!["a".into(), "a".into()],
+            HashMap::new(),
+        );
+        match res.unwrap_err() {
+            Error::DuplicateLabel { name } => assert_eq!(name, "a"),
+            other => panic!("{}", other),
+        };
+    }
+
     #[test]
     fn test_invalid_metric_name() {
         for &name in &["-dash", "9gag", "has space"] {
             let res = Desc::new(name.into(), "help".into(), vec![], HashMap::new())
                 .expect_err(format!("expected error for {}", name).as_ref());
             match res {
-                Error::Msg(msg) => {
-                    assert_eq!(msg, format!("'{}' is not a valid metric name", name))
-                }
+                Error::InvalidMetricName { name: got } => assert_eq!(got, name),
                 other => panic!("{}", other),
             };
         }
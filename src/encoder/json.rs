@@ -0,0 +1,511 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::io::{Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Map, Number, Value};
+
+use crate::errors::{Error, Result};
+use crate::proto::{
+    Bucket, Counter, Gauge, Histogram, LabelPair, Metric, MetricFamily, MetricType, Quantile,
+    Summary, Untyped,
+};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+
+use super::{check_metric_family, Encoder};
+
+/// The JSON format of metric family.
+pub const JSON_FORMAT: &str = "application/json";
+
+/// An implementation of an [`Encoder`] that converts a [`MetricFamily`] proto
+/// message into JSON.
+///
+/// The metric families are written as an array of objects of the form
+///
+/// ```json
+/// {
+///   "name": "http_requests_total",
+///   "help": "Total number of HTTP requests.",
+///   "type": "counter",
+///   "metrics": [
+///     { "labels": { "code": "200" }, "timestamp_ms": 1700000000000, "value": 3 }
+///   ]
+/// }
+/// ```
+///
+/// where `type` is one of `counter`, `gauge`, `histogram`, `summary` and
+/// `untyped`. Counters and gauges have a `value`, histograms have a
+/// `histogram` object with `sample_count`, `sample_sum` and `buckets` of
+/// `upper_bound` and `cumulative_count`, and summaries have a `summary`
+/// object with `sample_count`, `sample_sum` and `quantiles` of `quantile` and
+/// `value`. Untyped metrics have an `untyped` object with a `value`.
+/// `timestamp_ms` is omitted when unset, as is the `created_timestamp` of
+/// counters, histograms and summaries, an object of `seconds` and `nanos`
+/// since the Unix epoch.
+/// Infinite and NaN values are written as the strings `"+Inf"`, `"-Inf"` and
+/// `"NaN"`.
+///
+/// This format is independent of the `serde` implementations of the model
+/// types behind the `serde` feature. Those mirror the protobuf messages field
+/// by field, e.g. with `label` as a list of name and value pairs and a
+/// `quantile` list, to round-trip any message losslessly, while this format
+/// is shaped for consumers of JSON, with `labels` as a map and plural names
+/// for lists.
+#[derive(Debug, Default)]
+pub struct JsonEncoder;
+
+impl JsonEncoder {
+    /// Create a new JSON encoder.
+    pub fn new() -> JsonEncoder {
+        JsonEncoder
+    }
+}
+
+impl Encoder for JsonEncoder {
+    fn encode<W: Write>(&self, metric_families: &[MetricFamily], writer: &mut W) -> Result<()> {
+        let mut values = Vec::with_capacity(metric_families.len());
+        for mf in metric_families {
+            // Fail-fast checks.
+            check_metric_family(mf)?;
+            values.push(metric_family_to_json(mf));
+        }
+        serde_json::to_writer(writer, &values)?;
+        Ok(())
+    }
+
+    fn format_type(&self) -> &str {
+        JSON_FORMAT
+    }
+}
+
+/// A decoder reading the JSON written by a [`JsonEncoder`] back into
+/// [`MetricFamily`] proto messages.
+#[derive(Debug, Default)]
+pub struct JsonDecoder;
+
+impl JsonDecoder {
+    /// Create a new JSON decoder.
+    pub fn new() -> JsonDecoder {
+        JsonDecoder
+    }
+
+    /// `decode` reads an array of metric families from `reader`.
+    pub fn decode<R: Read>(&self, reader: R) -> Result<Vec<MetricFamily>> {
+        let value: Value = serde_json::from_reader(reader)?;
+        let values = match value {
+            Value::Array(values) => values,
+            _ => return Err(invalid("metric families", "an array")),
+        };
+        values.iter().map(metric_family_from_json).collect()
+    }
+}
+
+fn metric_family_to_json(mf: &MetricFamily) -> Value {
+    let metric_type = mf.get_field_type();
+    let metrics: Vec<Value> = mf
+        .get_metric()
+        .iter()
+        .map(|m| {
+            let labels: Map<String, Value> = m
+                .get_label()
+                .iter()
+                .map(|lp| (lp.name().to_owned(), Value::from(lp.value())))
+                .collect();
+            let mut obj = Map::new();
+            obj.insert("labels".to_owned(), Value::Object(labels));
+            if m.timestamp_ms() != 0 {
+                obj.insert("timestamp_ms".to_owned(), Value::from(m.timestamp_ms()));
+            }
+            match metric_type {
+                MetricType::COUNTER => {
+                    let c = m.get_counter();
+                    obj.insert("value".to_owned(), float(c.get_value()));
+                    if let Some(t) = c.get_created_timestamp() {
+                        obj.insert("created_timestamp".to_owned(), timestamp(t.seconds, t.nanos));
+                    }
+                }
+                MetricType::GAUGE => {
+                    obj.insert("value".to_owned(), float(m.get_gauge().get_value()));
+                }
+                MetricType::HISTOGRAM => {
+                    let h = m.get_histogram();
+                    let buckets: Vec<Value> = h
+                        .get_bucket()
+                        .iter()
+                        .map(|b| {
+                            json!({
+                                "upper_bound": float(b.upper_bound()),
+                                "cumulative_count": b.cumulative_count(),
+                            })
+                        })
+                        .collect();
+                    let histogram = json!({
+                        "sample_count": h.get_sample_count(),
+                        "sample_sum": float(h.get_sample_sum()),
+                        "buckets": buckets,
+                    });
+                    obj.insert("histogram".to_owned(), histogram);
+                    if let Some(t) = h.get_created_timestamp() {
+                        obj.insert("created_timestamp".to_owned(), timestamp(t.seconds, t.nanos));
+                    }
+                }
+                MetricType::SUMMARY => {
+                    let s = m.get_summary();
+                    let quantiles: Vec<Value> = s
+                        .get_quantile()
+                        .iter()
+                        .map(|q| json!({ "quantile": float(q.quantile()), "value": float(q.value()) }))
+                        .collect();
+                    let summary = json!({
+                        "sample_count": s.sample_count(),
+                        "sample_sum": float(s.sample_sum()),
+                        "quantiles": quantiles,
+                    });
+                    obj.insert("summary".to_owned(), summary);
+                    if let Some(t) = s.get_created_timestamp() {
+                        obj.insert("created_timestamp".to_owned(), timestamp(t.seconds, t.nanos));
+                    }
+                }
+                MetricType::UNTYPED => {
+                    #[allow(deprecated)]
+                    let value = m.get_untyped().get_value();
+                    obj.insert("untyped".to_owned(), json!({ "value": float(value) }));
+                }
+            }
+            Value::Object(obj)
+        })
+        .collect();
+
+    json!({
+        "name": mf.name(),
+        "help": mf.help(),
+        "type": type_name(metric_type),
+        "metrics": metrics,
+    })
+}
+
+fn metric_family_from_json(value: &Value) -> Result<MetricFamily> {
+    let obj = object(value, "metric family")?;
+    let metric_type = match string(obj, "type")? {
+        "counter" => MetricType::COUNTER,
+        "gauge" => MetricType::GAUGE,
+        "histogram" => MetricType::HISTOGRAM,
+        "summary" => MetricType::SUMMARY,
+        "untyped" => MetricType::UNTYPED,
+        _ => return Err(invalid("type", "a metric type")),
+    };
+
+    let mut metrics = vec![];
+    for value in array(obj, "metrics")? {
+        let obj = object(value, "metric")?;
+        let mut m = Metric::default();
+
+        let mut labels = vec![];
+        for (name, value) in object(field(obj, "labels")?, "labels")? {
+            let mut lp = LabelPair::default();
+            lp.set_name(name.clone());
+            lp.set_value(
+                value
+                    .as_str()
+                    .ok_or_else(|| invalid("label value", "a string"))?
+                    .to_owned(),
+            );
+            labels.push(lp);
+        }
+        m.set_label(labels);
+
+        if let Some(timestamp) = obj.get("timestamp_ms") {
+            m.set_timestamp_ms(
+                timestamp
+                    .as_i64()
+                    .ok_or_else(|| invalid("timestamp_ms", "an integer"))?,
+            );
+        }
+
+        let created = match obj.get("created_timestamp") {
+            Some(value) => Some(system_time(value)?),
+            None => None,
+        };
+
+        match metric_type {
+            MetricType::COUNTER => {
+                let mut counter = Counter::default();
+                counter.set_value(number(obj, "value")?);
+                if let Some(created) = created {
+                    counter.set_created_timestamp(created.into());
+                }
+                m.set_counter(counter);
+            }
+            MetricType::GAUGE => {
+                let mut gauge = Gauge::default();
+                gauge.set_value(number(obj, "value")?);
+                m.set_gauge(gauge);
+            }
+            MetricType::HISTOGRAM => {
+                let obj = object(field(obj, "histogram")?, "histogram")?;
+                let mut h = Histogram::default();
+                h.set_sample_count(unsigned(obj, "sample_count")?);
+                h.set_sample_sum(number(obj, "sample_sum")?);
+                let mut buckets = vec![];
+                for value in array(obj, "buckets")? {
+                    let obj = object(value, "bucket")?;
+                    let mut b = Bucket::default();
+                    b.set_upper_bound(number(obj, "upper_bound")?);
+                    b.set_cumulative_count(unsigned(obj, "cumulative_count")?);
+                    buckets.push(b);
+                }
+                h.set_bucket(buckets);
+                if let Some(created) = created {
+                    h.set_created_timestamp(created.into());
+                }
+                m.set_histogram(h);
+            }
+            MetricType::SUMMARY => {
+                let obj = object(field(obj, "summary")?, "summary")?;
+                let mut s = Summary::default();
+                s.set_sample_count(unsigned(obj, "sample_count")?);
+                s.set_sample_sum(number(obj, "sample_sum")?);
+                let mut quantiles = vec![];
+                for value in array(obj, "quantiles")? {
+                    let obj = object(value, "quantile")?;
+                    let mut q = Quantile::default();
+                    q.set_quantile(number(obj, "quantile")?);
+                    q.set_value(number(obj, "value")?);
+                    quantiles.push(q);
+                }
+                s.set_quantile(quantiles);
+                if let Some(created) = created {
+                    s.set_created_timestamp(created.into());
+                }
+                m.set_summary(s);
+            }
+            MetricType::UNTYPED => {
+                let obj = object(field(obj, "untyped")?, "untyped")?;
+                let mut untyped = Untyped::default();
+                #[allow(deprecated)]
+                {
+                    untyped.set_value(number(obj, "value")?);
+                    m.set_untyped(untyped);
+                }
+            }
+        }
+        metrics.push(m);
+    }
+
+    let mut mf = MetricFamily::default();
+    mf.set_name(string(obj, "name")?.to_owned());
+    mf.set_help(string(obj, "help")?.to_owned());
+    mf.set_field_type(metric_type);
+    mf.set_metric(metrics);
+    Ok(mf)
+}
+
+fn type_name(metric_type: MetricType) -> &'static str {
+    match metric_type {
+        MetricType::COUNTER => "counter",
+        MetricType::GAUGE => "gauge",
+        MetricType::HISTOGRAM => "histogram",
+        MetricType::SUMMARY => "summary",
+        MetricType::UNTYPED => "untyped",
+    }
+}
+
+fn float(f: f64) -> Value {
+    match Number::from_f64(f) {
+        Some(n) => Value::Number(n),
+        None if f.is_nan() => Value::from("NaN"),
+        None if f > 0.0 => Value::from("+Inf"),
+        None => Value::from("-Inf"),
+    }
+}
+
+fn timestamp(seconds: i64, nanos: i32) -> Value {
+    json!({ "seconds": seconds, "nanos": nanos })
+}
+
+/// The range of seconds of a protobuf `Timestamp`, 0001-01-01T00:00:00Z to
+/// 9999-12-31T23:59:59Z.
+const MIN_TIMESTAMP_SECONDS: i64 = -62_135_596_800;
+const MAX_TIMESTAMP_SECONDS: i64 = 253_402_300_799;
+
+fn system_time(value: &Value) -> Result<SystemTime> {
+    let obj = object(value, "created_timestamp")?;
+    let seconds = field(obj, "seconds")?
+        .as_i64()
+        .ok_or_else(|| invalid("seconds", "an integer"))?;
+    if !(MIN_TIMESTAMP_SECONDS..=MAX_TIMESTAMP_SECONDS).contains(&seconds) {
+        return Err(invalid("seconds", "between years 0001 and 9999"));
+    }
+    let nanos = unsigned(obj, "nanos")?;
+    if nanos >= 1_000_000_000 {
+        return Err(invalid("nanos", "less than 1000000000"));
+    }
+    let since_epoch = Duration::from_secs(seconds.unsigned_abs());
+    let time = if seconds < 0 {
+        UNIX_EPOCH.checked_sub(since_epoch)
+    } else {
+        UNIX_EPOCH.checked_add(since_epoch)
+    };
+    time.and_then(|t| t.checked_add(Duration::from_nanos(nanos)))
+        .ok_or_else(|| invalid("created_timestamp", "a representable time"))
+}
+
+fn invalid(field: &'static str, expect: &'static str) -> Error {
+    Error::InvalidJson { field, expect }
+}
+
+fn field<'a>(obj: &'a Map<String, Value>, name: &'static str) -> Result<&'a Value> {
+    obj.get(name).ok_or_else(|| invalid(name, "present"))
+}
+
+fn object<'a>(value: &'a Value, what: &'static str) -> Result<&'a Map<String, Value>> {
+    value.as_object().ok_or_else(|| invalid(what, "an object"))
+}
+
+fn array<'a>(obj: &'a Map<String, Value>, name: &'static str) -> Result<&'a Vec<Value>> {
+    field(obj, name)?
+        .as_array()
+        .ok_or_else(|| invalid(name, "an array"))
+}
+
+fn string<'a>(obj: &'a Map<String, Value>, name: &'static str) -> Result<&'a str> {
+    field(obj, name)?
+        .as_str()
+        .ok_or_else(|| invalid(name, "a string"))
+}
+
+fn unsigned(obj: &Map<String, Value>, name: &'static str) -> Result<u64> {
+    field(obj, name)?
+        .as_u64()
+        .ok_or_else(|| invalid(name, "an unsigned integer"))
+}
+
+fn number(obj: &Map<String, Value>, name: &'static str) -> Result<f64> {
+    let value = field(obj, name)?;
+    match value.as_str() {
+        Some("+Inf") => Ok(f64::INFINITY),
+        Some("-Inf") => Ok(f64::NEG_INFINITY),
+        Some("NaN") => Ok(f64::NAN),
+        _ => value.as_f64().ok_or_else(|| invalid(name, "a number")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::CounterVec;
+    use crate::histogram::{Histogram as HistogramMetric, HistogramOpts};
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_json_encoder() {
+        let cv = CounterVec::new(Opts::new("test_counter", "test help"), &["a"]).unwrap();
+        cv.with_label_values(&["1"]).inc();
+        let histogram = HistogramMetric::with_opts(
+            HistogramOpts::new("test_histogram", "test help").buckets(vec![0.5]),
+        )
+        .unwrap();
+        histogram.observe(0.25);
+
+        let mut mfs = cv.collect();
+        mfs.extend(histogram.collect());
+        let mut untyped = Untyped::default();
+        #[allow(deprecated)]
+        untyped.set_value(f64::INFINITY);
+        let mut m = Metric::default();
+        #[allow(deprecated)]
+        m.set_untyped(untyped);
+        let mut mf = MetricFamily::default();
+        mf.set_name("test_untyped".to_owned());
+        mf.set_help("test help".to_owned());
+        mf.set_field_type(MetricType::UNTYPED);
+        mf.set_metric(vec![m]);
+        mfs.push(mf);
+
+        let mut writer = Vec::<u8>::new();
+        let encoder = JsonEncoder::new();
+        encoder.encode(&mfs, &mut writer).unwrap();
+        assert_eq!(encoder.format_type(), JSON_FORMAT);
+
+        let counter_created = mfs[0].get_metric()[0]
+            .get_counter()
+            .get_created_timestamp()
+            .unwrap();
+        let histogram_created = mfs[1].get_metric()[0]
+            .get_histogram()
+            .get_created_timestamp()
+            .unwrap();
+        let expected = format!(
+            r#"[{{"help":"test help","metrics":[{{"created_timestamp":{{"nanos":{},"seconds":{}}},"labels":{{"a":"1"}},"value":1.0}}],"name":"test_counter","type":"counter"}},{{"help":"test help","metrics":[{{"created_timestamp":{{"nanos":{},"seconds":{}}},"histogram":{{"buckets":[{{"cumulative_count":1,"upper_bound":0.5}}],"sample_count":1,"sample_sum":0.25}},"labels":{{}}}}],"name":"test_histogram","type":"histogram"}},{{"help":"test help","metrics":[{{"labels":{{}},"untyped":{{"value":"+Inf"}}}}],"name":"test_untyped","type":"untyped"}}]"#,
+            counter_created.nanos,
+            counter_created.seconds,
+            histogram_created.nanos,
+            histogram_created.seconds,
+        );
+        assert_eq!(std::str::from_utf8(&writer).unwrap(), expected);
+
+        let decoded = JsonDecoder::new().decode(writer.as_slice()).unwrap();
+        assert_eq!(decoded, mfs);
+    }
+
+    #[test]
+    fn test_json_decoder() {
+        let input = r#"[{"name":"test_summary","help":"","type":"summary","metrics":[
+            {"labels":{},"timestamp_ms":1000,"summary":{"sample_count":2,"sample_sum":"+Inf",
+             "quantiles":[{"quantile":0.5,"value":"NaN"}]}}]}]"#;
+        let mfs = JsonDecoder::new().decode(input.as_bytes()).unwrap();
+        let m = &mfs[0].get_metric()[0];
+        assert_eq!(m.timestamp_ms(), 1000);
+        let s = m.get_summary();
+        assert_eq!(s.sample_count(), 2);
+        assert_eq!(s.sample_sum(), f64::INFINITY);
+        assert!(s.get_quantile()[0].value().is_nan());
+
+        let mut writer = Vec::<u8>::new();
+        JsonEncoder::new().encode(&mfs, &mut writer).unwrap();
+        assert!(std::str::from_utf8(&writer).unwrap().contains(
+            r#""quantiles":[{"quantile":0.5,"value":"NaN"}],"sample_count":2,"sample_sum":"+Inf""#
+        ));
+
+        for (input, field, expect) in &[
+            ("{}", "metric families", "an array"),
+            (
+                r#"[{"name":"a","help":"","type":"foo","metrics":[]}]"#,
+                "type",
+                "a metric type",
+            ),
+            (
+                r#"[{"name":"a","help":"","type":"gauge","metrics":[{"labels":{}}]}]"#,
+                "value",
+                "present",
+            ),
+            (
+                r#"[{"name":"a","help":"","type":"counter","metrics":[{"labels":{},"value":1,
+                    "created_timestamp":{"seconds":-9223372036854775808,"nanos":0}}]}]"#,
+                "seconds",
+                "between years 0001 and 9999",
+            ),
+            (
+                r#"[{"name":"a","help":"","type":"counter","metrics":[{"labels":{},"value":1,
+                    "created_timestamp":{"seconds":9223372036854775807,"nanos":0}}]}]"#,
+                "seconds",
+                "between years 0001 and 9999",
+            ),
+            (
+                r#"[{"name":"a","help":"","type":"counter","metrics":[{"labels":{},"value":1,
+                    "created_timestamp":{"seconds":0,"nanos":1000000000}}]}]"#,
+                "nanos",
+                "less than 1000000000",
+            ),
+        ] {
+            match JsonDecoder::new().decode(input.as_bytes()) {
+                Err(Error::InvalidJson {
+                    field: f,
+                    expect: e,
+                }) => assert_eq!((f, e), (*field, *expect)),
+                res => panic!("unexpected result {:?} for {}", res, input),
+            }
+        }
+    }
+}
@@ -1,9 +1,15 @@
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+#[cfg(feature = "json")]
+mod json;
+mod openmetrics;
 #[cfg(feature = "protobuf")]
 mod pb;
 mod text;
 
+#[cfg(feature = "json")]
+pub use self::json::{JsonDecoder, JsonEncoder, JSON_FORMAT};
+pub use self::openmetrics::{OpenMetricsEncoder, OPENMETRICS_FORMAT};
 #[cfg(feature = "protobuf")]
 pub use self::pb::{ProtobufEncoder, PROTOBUF_FORMAT};
 pub use self::text::{TextEncoder, TEXT_FORMAT};
@@ -28,10 +34,14 @@ pub trait Encoder {
 
 fn check_metric_family(mf: &MetricFamily) -> Result<()> {
     if mf.get_metric().is_empty() {
-        return Err(Error::Msg(format!("MetricFamily has no metrics: {:?}", mf)));
+        return Err(Error::EmptyMetricFamily {
+            name: mf.name().to_owned(),
+        });
     }
     if mf.name().is_empty() {
-        return Err(Error::Msg(format!("MetricFamily has no name: {:?}", mf)));
+        return Err(Error::InvalidMetricName {
+            name: mf.name().to_owned(),
+        });
     }
     Ok(())
 }
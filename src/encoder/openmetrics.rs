@@ -0,0 +1,357 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::io::Write;
+
+use crate::errors::Result;
+use crate::histogram::BUCKET_LABEL;
+use crate::proto::{self, MetricFamily, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+
+use super::text::{
+    escape_string, label_pairs_to_text, StringBuf, WriteUtf8, POSITIVE_INF, QUANTILE,
+};
+use super::{check_metric_family, Encoder};
+
+/// The OpenMetrics text format of metric family.
+pub const OPENMETRICS_FORMAT: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+const TOTAL_SUFFIX: &str = "_total";
+
+/// An implementation of an [`Encoder`] that converts a [`MetricFamily`] proto
+/// message into the OpenMetrics text format.
+///
+/// Unlike the [`TextEncoder`](super::TextEncoder), it exposes the created
+/// timestamps of counters, summaries and histograms as `_created` samples.
+/// Counter families are named without their `_total` suffix, which their
+/// samples always carry.
+#[derive(Debug, Default)]
+pub struct OpenMetricsEncoder;
+
+impl OpenMetricsEncoder {
+    /// Create a new OpenMetrics encoder.
+    pub fn new() -> OpenMetricsEncoder {
+        OpenMetricsEncoder
+    }
+
+    /// Appends metrics to a given `String` buffer.
+    ///
+    /// This is a convenience wrapper around `<OpenMetricsEncoder as Encoder>::encode`.
+    pub fn encode_utf8(&self, metric_families: &[MetricFamily], buf: &mut String) -> Result<()> {
+        self.encode_impl(metric_families, &mut StringBuf(buf))
+    }
+
+    /// Converts metrics to `String`.
+    ///
+    /// This is a convenience wrapper around `<OpenMetricsEncoder as Encoder>::encode`.
+    pub fn encode_to_string(&self, metric_families: &[MetricFamily]) -> Result<String> {
+        let mut buf = String::new();
+        self.encode_utf8(metric_families, &mut buf)?;
+        Ok(buf)
+    }
+
+    fn encode_impl(
+        &self,
+        metric_families: &[MetricFamily],
+        writer: &mut dyn WriteUtf8,
+    ) -> Result<()> {
+        for mf in metric_families {
+            // Fail-fast checks.
+            check_metric_family(mf)?;
+
+            let metric_type = mf.get_field_type();
+            let name = match metric_type {
+                MetricType::COUNTER => mf.name().strip_suffix(TOTAL_SUFFIX).unwrap_or(mf.name()),
+                _ => mf.name(),
+            };
+
+            // Write `# HELP` header.
+            let help = mf.help();
+            if !help.is_empty() {
+                writer.write_all("# HELP ")?;
+                writer.write_all(name)?;
+                writer.write_all(" ")?;
+                writer.write_all(&escape_string(help, true))?;
+                writer.write_all("\n")?;
+            }
+
+            // Write `# TYPE` header.
+            let type_name = match metric_type {
+                MetricType::COUNTER => "counter",
+                MetricType::GAUGE => "gauge",
+                MetricType::SUMMARY => "summary",
+                MetricType::UNTYPED => "unknown",
+                MetricType::HISTOGRAM => "histogram",
+            };
+            writer.write_all("# TYPE ")?;
+            writer.write_all(name)?;
+            writer.write_all(" ")?;
+            writer.write_all(type_name)?;
+            writer.write_all("\n")?;
+
+            for m in mf.get_metric() {
+                match metric_type {
+                    MetricType::COUNTER => {
+                        let c = m.get_counter();
+                        write_sample(writer, name, TOTAL_SUFFIX, m, None, c.get_value())?;
+                        write_created(writer, name, m, c.get_created_timestamp())?;
+                    }
+                    MetricType::GAUGE => {
+                        write_sample(writer, name, "", m, None, m.get_gauge().get_value())?;
+                    }
+                    MetricType::HISTOGRAM => {
+                        let h = m.get_histogram();
+
+                        let mut inf_seen = false;
+                        for b in h.get_bucket() {
+                            let upper_bound = b.upper_bound();
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                m,
+                                Some((BUCKET_LABEL, &format_float(upper_bound))),
+                                b.cumulative_count() as f64,
+                            )?;
+                            if upper_bound.is_sign_positive() && upper_bound.is_infinite() {
+                                inf_seen = true;
+                            }
+                        }
+                        if !inf_seen {
+                            write_sample(
+                                writer,
+                                name,
+                                "_bucket",
+                                m,
+                                Some((BUCKET_LABEL, POSITIVE_INF)),
+                                h.get_sample_count() as f64,
+                            )?;
+                        }
+
+                        write_sample(writer, name, "_sum", m, None, h.get_sample_sum())?;
+                        write_sample(writer, name, "_count", m, None, h.get_sample_count() as f64)?;
+                        write_created(writer, name, m, h.get_created_timestamp())?;
+                    }
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+
+                        for q in s.get_quantile() {
+                            write_sample(
+                                writer,
+                                name,
+                                "",
+                                m,
+                                Some((QUANTILE, &format_float(q.quantile()))),
+                                q.value(),
+                            )?;
+                        }
+
+                        write_sample(writer, name, "_sum", m, None, s.sample_sum())?;
+                        write_sample(writer, name, "_count", m, None, s.sample_count() as f64)?;
+                        write_created(writer, name, m, s.get_created_timestamp())?;
+                    }
+                    MetricType::UNTYPED => {
+                        #[allow(deprecated)]
+                        let value = m.get_untyped().get_value();
+                        write_sample(writer, name, "", m, None, value)?;
+                    }
+                }
+            }
+        }
+
+        writer.write_all("# EOF\n")?;
+
+        Ok(())
+    }
+}
+
+impl Encoder for OpenMetricsEncoder {
+    fn encode<W: Write>(&self, metric_families: &[MetricFamily], writer: &mut W) -> Result<()> {
+        self.encode_impl(metric_families, &mut *writer)
+    }
+
+    fn format_type(&self) -> &str {
+        OPENMETRICS_FORMAT
+    }
+}
+
+/// `write_sample` writes a single sample named `name` plus `name_postfix`, with
+/// the labels of `mc` and the optional `additional_label`. Its timestamp, if
+/// any, is written in seconds.
+fn write_sample(
+    writer: &mut dyn WriteUtf8,
+    name: &str,
+    name_postfix: &str,
+    mc: &proto::Metric,
+    additional_label: Option<(&str, &str)>,
+    value: f64,
+) -> Result<()> {
+    writer.write_all(name)?;
+    writer.write_all(name_postfix)?;
+
+    label_pairs_to_text(mc.get_label(), additional_label, writer)?;
+
+    writer.write_all(" ")?;
+    writer.write_all(&format_float(value))?;
+
+    let timestamp = mc.timestamp_ms();
+    if timestamp != 0 {
+        writer.write_all(" ")?;
+        writer.write_all(&format_float(timestamp as f64 / 1000.0))?;
+    }
+
+    writer.write_all("\n")?;
+
+    Ok(())
+}
+
+/// `write_created` writes the `_created` sample of `name` if the created
+/// timestamp is known.
+fn write_created<T: CreatedTimestamp>(
+    writer: &mut dyn WriteUtf8,
+    name: &str,
+    mc: &proto::Metric,
+    created: Option<&T>,
+) -> Result<()> {
+    match created {
+        Some(created) => write_sample(writer, name, "_created", mc, None, created.as_secs_f64()),
+        None => Ok(()),
+    }
+}
+
+/// The created timestamp of either model, in seconds since the Unix epoch.
+trait CreatedTimestamp {
+    fn as_secs_f64(&self) -> f64;
+}
+
+#[cfg(feature = "protobuf")]
+impl CreatedTimestamp for protobuf::well_known_types::timestamp::Timestamp {
+    fn as_secs_f64(&self) -> f64 {
+        self.seconds as f64 + f64::from(self.nanos) / 1e9
+    }
+}
+
+#[cfg(not(feature = "protobuf"))]
+impl CreatedTimestamp for proto::Timestamp {
+    fn as_secs_f64(&self) -> f64 {
+        self.seconds as f64 + f64::from(self.nanos) / 1e9
+    }
+}
+
+/// `format_float` formats `v` as OpenMetrics requires, always with a decimal
+/// point or exponent, and with `NaN`, `+Inf` and `-Inf` for the special
+/// values.
+fn format_float(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_owned()
+    } else if v.is_infinite() {
+        if v.is_sign_positive() {
+            POSITIVE_INF
+        } else {
+            "-Inf"
+        }
+        .to_owned()
+    } else {
+        let mut s = v.to_string();
+        if !s.contains(['.', 'e']) {
+            s.push_str(".0");
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::CounterVec;
+    use crate::gauge::Gauge;
+    use crate::histogram::{Histogram, HistogramOpts};
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_openmetrics_encoder() {
+        let counter = CounterVec::new(
+            Opts::new("requests_total", "Total \"requests\"."),
+            &["code"],
+        )
+        .unwrap();
+        counter.with_label_values(&["200"]).inc_by(3.0);
+        let gauge = Gauge::new("queue", "test help").unwrap();
+        gauge.set(f64::INFINITY);
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("latency", "test help").buckets(vec![0.5, 1.0]),
+        )
+        .unwrap();
+        histogram.observe(0.25);
+
+        let mut mfs = counter.collect();
+        mfs.extend(gauge.collect());
+        mfs.extend(histogram.collect());
+        mfs[1].mut_metric()[0].set_timestamp_ms(1_500);
+
+        let created = |mf: &MetricFamily| {
+            let m = &mf.get_metric()[0];
+            let ts = match mf.get_field_type() {
+                MetricType::COUNTER => m.get_counter().get_created_timestamp(),
+                _ => m.get_histogram().get_created_timestamp(),
+            };
+            format_float(ts.unwrap().as_secs_f64())
+        };
+        let expected = format!(
+            r##"# HELP requests Total \"requests\".
+# TYPE requests counter
+requests_total{{code="200"}} 3.0
+requests_created{{code="200"}} {}
+# HELP queue test help
+# TYPE queue gauge
+queue +Inf 1.5
+# HELP latency test help
+# TYPE latency histogram
+latency_bucket{{le="0.5"}} 1.0
+latency_bucket{{le="1.0"}} 1.0
+latency_bucket{{le="+Inf"}} 1.0
+latency_sum 0.25
+latency_count 1.0
+latency_created {}
+# EOF
+"##,
+            created(&mfs[0]),
+            created(&mfs[2]),
+        );
+
+        let encoder = OpenMetricsEncoder::new();
+        assert_eq!(encoder.encode_to_string(&mfs).unwrap(), expected);
+        let mut writer = Vec::<u8>::new();
+        encoder.encode(&mfs, &mut writer).unwrap();
+        assert_eq!(writer, expected.as_bytes());
+        assert_eq!(encoder.format_type(), OPENMETRICS_FORMAT);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_openmetrics_encoder_untyped() {
+        use crate::proto::{LabelPair, Metric, Untyped};
+
+        let mut label = LabelPair::default();
+        label.set_name("a".to_owned());
+        label.set_value("1".to_owned());
+        let mut untyped = Untyped::default();
+        untyped.set_value(2.5);
+        let mut m = Metric::from_label(vec![label]);
+        m.set_untyped(untyped);
+
+        let mut mf = MetricFamily::default();
+        mf.set_name("test_untyped".to_owned());
+        mf.set_help("test help".to_owned());
+        mf.set_field_type(MetricType::UNTYPED);
+        mf.set_metric(vec![m]);
+
+        let expected = r##"# HELP test_untyped test help
+# TYPE test_untyped unknown
+test_untyped{a="1"} 2.5
+# EOF
+"##;
+        let encoder = OpenMetricsEncoder::new();
+        assert_eq!(encoder.encode_to_string(&[mf]).unwrap(), expected);
+    }
+}
@@ -59,7 +59,10 @@ mod tests {
         reg.register(Box::new(cv.clone())).unwrap();
 
         cv.get_metric_with_label_values(&["2230"]).unwrap().inc();
-        let mf = reg.gather();
+        let mut mf = reg.gather();
+        // The created timestamp differs from run to run, the golden bytes
+        // predate it.
+        mf[0].mut_metric()[0].counter.mut_or_insert_default().created_timestamp.clear();
         let mut writer = Vec::<u8>::new();
         let encoder = super::ProtobufEncoder::new();
         let res = encoder.encode(&mf, &mut writer);
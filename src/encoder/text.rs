@@ -14,8 +14,8 @@ use super::{check_metric_family, Encoder};
 /// The text format of metric family.
 pub const TEXT_FORMAT: &str = "text/plain; version=0.0.4";
 
-const POSITIVE_INF: &str = "+Inf";
-const QUANTILE: &str = "quantile";
+pub(super) const POSITIVE_INF: &str = "+Inf";
+pub(super) const QUANTILE: &str = "quantile";
 
 /// An implementation of an [`Encoder`] that converts a [`MetricFamily`] proto message
 /// into text format.
@@ -211,7 +211,7 @@ fn write_sample(
 /// written. Otherwise, the label pairs are written, escaped as required by the
 /// text format, and enclosed in '{...}'. The function returns the number of
 /// bytes written and any error encountered.
-fn label_pairs_to_text(
+pub(super) fn label_pairs_to_text(
     pairs: &[proto::LabelPair],
     additional_label: Option<(&str, &str)>,
     writer: &mut dyn WriteUtf8,
@@ -257,7 +257,7 @@ fn find_first_occurence(v: &str, include_double_quote: bool) -> Option<usize> {
 ///
 /// Implementation adapted from
 /// https://lise-henry.github.io/articles/optimising_strings.html
-fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
+pub(super) fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
     let first_occurence = find_first_occurence(v, include_double_quote);
 
     if let Some(first) = first_occurence {
@@ -288,7 +288,7 @@ fn escape_string(v: &str, include_double_quote: bool) -> Cow<'_, str> {
     }
 }
 
-trait WriteUtf8 {
+pub(super) trait WriteUtf8 {
     fn write_all(&mut self, text: &str) -> io::Result<()>;
 }
 
@@ -300,7 +300,7 @@ impl<W: Write> WriteUtf8 for W {
 
 /// Coherence forbids to impl `WriteUtf8` directly on `String`, need this
 /// wrapper as a work-around.
-struct StringBuf<'a>(&'a mut String);
+pub(super) struct StringBuf<'a>(pub(super) &'a mut String);
 
 impl WriteUtf8 for StringBuf<'_> {
     fn write_all(&mut self, text: &str) -> io::Result<()> {
@@ -2,12 +2,19 @@
 
 use thiserror::Error;
 
+use crate::proto::MetricType;
+
 /// The error types for prometheus.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     /// A duplicate metric collector has already been registered.
     #[error("Duplicate metrics collector registration attempted")]
     AlreadyReg,
+    /// The collector to unregister was never registered, or is an unchecked
+    /// collector which cannot be unregistered.
+    #[error("Collector is not registered")]
+    NotRegistered,
     /// The label cardinality was inconsistent.
     #[error("Inconsistent label cardinality, expect {expect} label values, but got {got}")]
     InconsistentCardinality {
@@ -16,6 +23,187 @@ pub enum Error {
         /// The actual number of labels.
         got: usize,
     },
+    /// A metric name does not match `[a-zA-Z_:][a-zA-Z0-9_:]*`.
+    #[error("'{name}' is not a valid metric name")]
+    InvalidMetricName {
+        /// The offending metric name.
+        name: String,
+    },
+    /// A label name does not match `[a-zA-Z_][a-zA-Z0-9_]*`.
+    #[error("'{name}' is not a valid label name")]
+    InvalidLabelName {
+        /// The offending label name.
+        name: String,
+    },
+    /// A label name is used more than once.
+    #[error("Duplicate label name {name}")]
+    DuplicateLabel {
+        /// The repeated label name.
+        name: String,
+    },
+    /// A label name is reserved by the metric type, e.g. `le` for histograms.
+    #[error("'{name}' is a reserved label name")]
+    ReservedLabel {
+        /// The reserved label name.
+        name: String,
+    },
+    /// A descriptor was created with an empty help string.
+    #[error("Empty help string for metric {name}")]
+    EmptyHelp {
+        /// The fully-qualified name of the metric.
+        name: String,
+    },
+    /// A registry was created with an empty prefix.
+    #[error("Empty prefix namespace")]
+    EmptyPrefix,
+    /// A descriptor with the same fully-qualified name but different label
+    /// names or a different help string has been registered before.
+    #[error(
+        "A previously registered descriptor with the same fully-qualified name as {name} \
+         has different label names or a different help string"
+    )]
+    DescriptorMismatch {
+        /// The fully-qualified name of the metric.
+        name: String,
+    },
+    /// A collector returned the same descriptor more than once.
+    #[error("Duplicate descriptor {name} within the same collector")]
+    DuplicateDescriptor {
+        /// The fully-qualified name of the metric.
+        name: String,
+    },
+    /// A collected metric family was not described by its collector.
+    #[error("Collected metric family {name} was not described by its collector")]
+    UndescribedMetric {
+        /// The name of the metric family.
+        name: String,
+    },
+    /// Metric families of the same name disagree on their help string.
+    #[error("Metric family {name} has help {got:?} but should have {expect:?}")]
+    HelpMismatch {
+        /// The name of the metric family.
+        name: String,
+        /// The expected help string.
+        expect: String,
+        /// The actual help string.
+        got: String,
+    },
+    /// Metric families of the same name disagree on their type.
+    #[error("Metric family {name} has type {got:?} but should have {expect:?}")]
+    TypeMismatch {
+        /// The name of the metric family.
+        name: String,
+        /// The expected metric type.
+        expect: MetricType,
+        /// The actual metric type.
+        got: MetricType,
+    },
+    /// A collected metric has labels not matching its descriptor.
+    #[error("Collected metric {name} {labels:?} is not consistent with its descriptor")]
+    InconsistentLabels {
+        /// The name of the metric family.
+        name: String,
+        /// The label pairs of the metric.
+        labels: Vec<(String, String)>,
+    },
+    /// A metric with the same label values was collected more than once.
+    #[error("Collected metric {name} {labels:?} was collected before with the same label values")]
+    DuplicateMetric {
+        /// The name of the metric family.
+        name: String,
+        /// The label pairs of the metric.
+        labels: Vec<(String, String)>,
+    },
+    /// A metric family to encode has no metrics.
+    #[error("Metric family {name} has no metrics")]
+    EmptyMetricFamily {
+        /// The name of the metric family.
+        name: String,
+    },
+    /// No metric with the given label values exists in a metric vector.
+    #[error("Missing label values {values:?}")]
+    MissingLabelValues {
+        /// The label values looked up.
+        values: Vec<String>,
+    },
+    /// A label map lacks one of the variable labels of a metric vector.
+    #[error("Label name {name} missing in label map")]
+    MissingLabel {
+        /// The missing label name.
+        name: String,
+    },
+    /// Histogram buckets are not in strictly increasing order.
+    #[error("Histogram buckets must be in increasing order: {upper_bound} >= {next}")]
+    BucketsNotSorted {
+        /// The upper bound of a bucket.
+        upper_bound: f64,
+        /// The upper bound of the next bucket, not greater than `upper_bound`.
+        next: f64,
+    },
+    /// A bucket generator was called with an invalid parameter.
+    #[error("Invalid bucket parameter {param}: {value}")]
+    InvalidBucketParameter {
+        /// The name of the parameter.
+        param: &'static str,
+        /// The rejected value.
+        value: f64,
+    },
+    /// The window of a windowed metric can't be split into the requested
+    /// number of slices of at least one millisecond each.
+    #[error("Window {window:?} can't be split into {slices} slices of at least 1ms")]
+    InvalidWindow {
+        /// The requested window.
+        window: std::time::Duration,
+        /// The requested number of slices.
+        slices: usize,
+    },
+    /// The job or a grouping label value cannot be used for a push, e.g. an
+    /// empty job name.
+    #[error("Grouping label {name} has invalid value '{value}'")]
+    InvalidGrouping {
+        /// The grouping label name, `job` for the job name.
+        name: String,
+        /// The rejected value.
+        value: String,
+    },
+    /// A pushed metric already contains the job label or a grouping label.
+    #[error("Pushed metric {metric} already contains grouping label {label}")]
+    GroupingLabelConflict {
+        /// The name of the metric family.
+        metric: String,
+        /// The conflicting label name.
+        label: String,
+    },
+    /// The Pushgateway, or the remote write or OTLP endpoint, responded with
+    /// an unexpected status code.
+    #[error("Unexpected status code {status} while pushing to {url}")]
+    PushFailed {
+        /// The HTTP status code.
+        status: u16,
+        /// The URL pushed to.
+        url: String,
+    },
+    /// A header name or value given to a push client is invalid.
+    #[error("Invalid header {name}")]
+    InvalidHeader {
+        /// The header name.
+        name: String,
+    },
+    /// The background thread of an exporter or push scheduler panicked.
+    #[error("Background thread {name} panicked")]
+    ThreadPanicked {
+        /// The name of the thread.
+        name: String,
+    },
+    /// A field of a JSON metric family to decode is missing or malformed.
+    #[cfg(feature = "json")]
+    #[error("Invalid JSON metric family: {field} must be {expect}")]
+    InvalidJson {
+        /// The name of the field.
+        field: &'static str,
+        /// What the field must be, e.g. `"an object"`.
+        expect: &'static str,
+    },
     /// An error message which is only a string.
     #[error("Error: {0}")]
     Msg(String),
@@ -26,6 +214,14 @@ pub enum Error {
     #[cfg(feature = "protobuf")]
     #[error("Protobuf error: {0}")]
     Protobuf(#[from] protobuf::Error),
+    /// An error containing a [`serde_json::Error`].
+    #[cfg(feature = "json")]
+    #[error("Json error: {0}")]
+    Json(#[from] serde_json::Error),
+    /// An error containing a [`reqwest::Error`].
+    #[cfg(any(feature = "push", feature = "remote-write", feature = "otlp-http"))]
+    #[error("Http error: {0}")]
+    Http(#[from] reqwest::Error),
 }
 
 /// A specialized Result type for prometheus.
@@ -1,14 +1,18 @@
 // Copyright 2014 The Prometheus Authors
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::marker::PhantomData;
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::atomic64::{Atomic, AtomicF64, AtomicI64, Number};
+use crate::atomic64::{Atomic, AtomicF64, AtomicI64, AtomicMinMax, Number, ShardedAtomic};
 use crate::desc::Desc;
 use crate::errors::Result;
-use crate::metrics::{Collector, Metric, Opts};
+use crate::metrics::{Collector, LocalMetric, Metric, Opts};
 use crate::proto;
+use crate::timer;
 use crate::value::{Value, ValueType};
 use crate::vec::{MetricVec, MetricVecBuilder};
 
@@ -26,6 +30,13 @@ pub type Gauge = GenericGauge<AtomicF64>;
 /// all integers.
 pub type IntGauge = GenericGauge<AtomicI64>;
 
+/// A [`Gauge`] backed by a [`ShardedAtomic`], for gauges updated by many
+/// threads at once.
+pub type StripedGauge = GenericGauge<ShardedAtomic<AtomicF64>>;
+
+/// The integer version of [`StripedGauge`].
+pub type StripedIntGauge = GenericGauge<ShardedAtomic<AtomicI64>>;
+
 impl<P: Atomic> Clone for GenericGauge<P> {
     fn clone(&self) -> Self {
         Self {
@@ -88,6 +99,91 @@ impl<P: Atomic> GenericGauge<P> {
     pub fn get(&self) -> P::T {
         self.v.get()
     }
+
+    /// Increase the gauge by 1 and return a [`GaugeGuard`] decreasing it
+    /// again when dropped, e.g. to track in-progress requests across early
+    /// returns.
+    pub fn track_inprogress(&self) -> GaugeGuard<P> {
+        self.inc();
+        GaugeGuard {
+            gauge: self.clone(),
+        }
+    }
+
+    /// Set the gauge to the current Unix time in seconds, truncated for
+    /// integer gauges.
+    pub fn set_to_current_time(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_secs_f64());
+        self.set(P::T::from_f64(now));
+    }
+
+    /// Set the gauge to `v` as observed at `time`, e.g. for a value relayed
+    /// from a device that was read earlier. The gauge is exposed with the
+    /// timestamp until it is set again with another one or
+    /// [`clear_timestamp`](Self::clear_timestamp) is called.
+    ///
+    /// # Sticky timestamps
+    ///
+    /// The other setters, [`set`](Self::set), [`inc`](Self::inc),
+    /// [`dec`](Self::dec), [`add`](Self::add) and [`sub`](Self::sub), keep the
+    /// timestamp, so that they stay as cheap as a single atomic operation.
+    /// A gauge mixing them with `set_with_timestamp` exposes their changes
+    /// with the stale timestamp; call `clear_timestamp` before switching back
+    /// to them.
+    ///
+    /// The value and the timestamp are stored separately, not atomically
+    /// together. A collect racing with `set_with_timestamp` may expose the
+    /// new value with the previous timestamp or the previous value with the
+    /// new one, so a consistent pair needs the caller to synchronize the
+    /// update with the collection.
+    pub fn set_with_timestamp(&self, v: P::T, time: SystemTime) {
+        self.v.set(v);
+        self.v.set_timestamp_ms(timer::unix_millis(time));
+    }
+
+    /// Drop the timestamp set by
+    /// [`set_with_timestamp`](Self::set_with_timestamp), so that the gauge is
+    /// exposed at the time of the scrape again.
+    pub fn clear_timestamp(&self) {
+        self.v.set_timestamp_ms(0);
+    }
+
+    /// Return a [`GenericLocalGauge`] for single thread usage.
+    pub fn local(&self) -> GenericLocalGauge<P> {
+        GenericLocalGauge::new(self.clone())
+    }
+}
+
+impl<P: AtomicMinMax> GenericGauge<P> {
+    /// Set the gauge to `v` if it is greater than the current value,
+    /// atomically, e.g. for a high watermark.
+    #[inline]
+    pub fn set_max(&self, v: P::T) {
+        self.v.set_max(v);
+    }
+
+    /// Set the gauge to `v` if it is less than the current value,
+    /// atomically, e.g. for a low watermark.
+    #[inline]
+    pub fn set_min(&self, v: P::T) {
+        self.v.set_min(v);
+    }
+}
+
+/// A guard decreasing a [`GenericGauge`] by 1 when dropped, returned by
+/// [`GenericGauge::track_inprogress`].
+#[must_use = "Guard should be kept in a variable otherwise the gauge is decreased immediately"]
+#[derive(Debug)]
+pub struct GaugeGuard<P: Atomic> {
+    gauge: GenericGauge<P>,
+}
+
+impl<P: Atomic> Drop for GaugeGuard<P> {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
 }
 
 impl<P: Atomic> Collector for GenericGauge<P> {
@@ -158,6 +254,184 @@ impl<P: Atomic> GenericGaugeVec<P> {
 
         Ok(metric_vec as Self)
     }
+
+    /// Return a [`GenericLocalGaugeVec`] for single thread usage.
+    pub fn local(&self) -> GenericLocalGaugeVec<P> {
+        GenericLocalGaugeVec::new(self.clone())
+    }
+}
+
+/// The underlying implementation for [`LocalGauge`] and [`LocalIntGauge`].
+///
+/// It accumulates the changes of the gauge and adds them to the
+/// [`GenericGauge`] on flush or when dropped, so it can't set the gauge.
+#[derive(Debug)]
+pub struct GenericLocalGauge<P: Atomic> {
+    gauge: GenericGauge<P>,
+    delta: RefCell<P::T>,
+}
+
+/// For auto_flush::AFLocalGauge to use to make type inference possible
+pub trait GaugeWithValueType {
+    ///the exact type which implements Atomic
+    type ValueType: Atomic;
+}
+
+impl<P: Atomic> GaugeWithValueType for GenericLocalGauge<P> {
+    type ValueType = P;
+}
+
+/// An unsync [`Gauge`].
+pub type LocalGauge = GenericLocalGauge<AtomicF64>;
+
+/// The integer version of [`LocalGauge`]. Provides better performance if
+/// metric values are all integers.
+pub type LocalIntGauge = GenericLocalGauge<AtomicI64>;
+
+impl<P: Atomic> GenericLocalGauge<P> {
+    fn new(gauge: GenericGauge<P>) -> Self {
+        Self {
+            gauge,
+            delta: RefCell::new(P::T::from_i64(0)),
+        }
+    }
+
+    /// Increase the local gauge by 1.
+    #[inline]
+    pub fn inc(&self) {
+        *self.delta.borrow_mut() += P::T::from_i64(1);
+    }
+
+    /// Decrease the local gauge by 1.
+    #[inline]
+    pub fn dec(&self) {
+        *self.delta.borrow_mut() -= P::T::from_i64(1);
+    }
+
+    /// Add the given value to the local gauge. (The value can be
+    /// negative, resulting in a decrement of the gauge.)
+    #[inline]
+    pub fn add(&self, v: P::T) {
+        *self.delta.borrow_mut() += v;
+    }
+
+    /// Subtract the given value from the local gauge. (The value can be
+    /// negative, resulting in an increment of the gauge.)
+    #[inline]
+    pub fn sub(&self, v: P::T) {
+        *self.delta.borrow_mut() -= v;
+    }
+
+    /// Return the change of the gauge not yet flushed.
+    #[inline]
+    pub fn get(&self) -> P::T {
+        *self.delta.borrow()
+    }
+
+    /// Drop the change of the gauge not yet flushed.
+    #[inline]
+    pub fn clear(&self) {
+        *self.delta.borrow_mut() = P::T::from_i64(0);
+    }
+
+    /// Flush the local metrics to the [`Gauge`].
+    #[inline]
+    pub fn flush(&self) {
+        if *self.delta.borrow() == P::T::from_i64(0) {
+            return;
+        }
+        self.gauge.add(*self.delta.borrow());
+        *self.delta.borrow_mut() = P::T::from_i64(0);
+    }
+}
+
+impl<P: Atomic> LocalMetric for GenericLocalGauge<P> {
+    /// Flush the local metrics to the [`Gauge`].
+    #[inline]
+    fn flush(&self) {
+        GenericLocalGauge::flush(self);
+    }
+}
+
+impl<P: Atomic> Drop for GenericLocalGauge<P> {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+impl<P: Atomic> Clone for GenericLocalGauge<P> {
+    fn clone(&self) -> Self {
+        Self::new(self.gauge.clone())
+    }
+}
+
+/// The underlying implementation for [`LocalGaugeVec`] and
+/// [`LocalIntGaugeVec`].
+pub struct GenericLocalGaugeVec<P: Atomic> {
+    vec: GenericGaugeVec<P>,
+    local: HashMap<u64, GenericLocalGauge<P>>,
+}
+
+impl<P: Atomic> std::fmt::Debug for GenericLocalGaugeVec<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "GenericLocalGaugeVec ({} locals)",
+            self.local.keys().len()
+        )
+    }
+}
+
+/// An unsync [`GaugeVec`].
+pub type LocalGaugeVec = GenericLocalGaugeVec<AtomicF64>;
+
+/// The integer version of [`LocalGaugeVec`]. Provides better performance if
+/// metric values are all integers.
+pub type LocalIntGaugeVec = GenericLocalGaugeVec<AtomicI64>;
+
+impl<P: Atomic> GenericLocalGaugeVec<P> {
+    fn new(vec: GenericGaugeVec<P>) -> Self {
+        let local = HashMap::with_capacity(vec.v.children.read().len());
+        Self { vec, local }
+    }
+
+    /// Get a [`GenericLocalGauge`] by label values.
+    /// See more [MetricVec::with_label_values].
+    pub fn with_label_values<'a>(&'a mut self, vals: &[&str]) -> &'a mut GenericLocalGauge<P> {
+        let hash = self.vec.v.hash_label_values(vals).unwrap();
+        let vec = &self.vec;
+        self.local
+            .entry(hash)
+            .or_insert_with(|| vec.with_label_values(vals).local())
+    }
+
+    /// Remove a [`GenericLocalGauge`] by label values.
+    /// See more [MetricVec::remove_label_values].
+    pub fn remove_label_values(&mut self, vals: &[&str]) -> Result<()> {
+        let hash = self.vec.v.hash_label_values(vals)?;
+        self.local.remove(&hash);
+        self.vec.v.delete_label_values(vals)
+    }
+
+    /// Flush the local metrics to the [`GaugeVec`] metric.
+    pub fn flush(&self) {
+        for g in self.local.values() {
+            g.flush();
+        }
+    }
+}
+
+impl<P: Atomic> LocalMetric for GenericLocalGaugeVec<P> {
+    /// Flush the local metrics to the [`GaugeVec`] metric.
+    fn flush(&self) {
+        GenericLocalGaugeVec::flush(self);
+    }
+}
+
+impl<P: Atomic> Clone for GenericLocalGaugeVec<P> {
+    fn clone(&self) -> Self {
+        Self::new(self.vec.clone())
+    }
 }
 
 #[cfg(test)]
@@ -195,6 +469,144 @@ mod tests {
         assert_eq!(m.get_gauge().get_value() as u64, 42);
     }
 
+    #[test]
+    fn test_gauge_trackers() {
+        let gauge = IntGauge::new("test_inprogress", "test help").unwrap();
+        let track = |fail: bool| -> std::result::Result<(), ()> {
+            let _guard = gauge.track_inprogress();
+            assert_eq!(gauge.get(), 1);
+            if fail {
+                return Err(());
+            }
+            Ok(())
+        };
+        track(false).unwrap();
+        track(true).unwrap_err();
+        assert_eq!(gauge.get(), 0);
+
+        gauge.set_max(5);
+        gauge.set_max(3);
+        assert_eq!(gauge.get(), 5);
+        gauge.set_min(-2);
+        gauge.set_min(4);
+        assert_eq!(gauge.get(), -2);
+
+        let gauge = Gauge::new("test_watermark", "test help").unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let gauge = gauge.clone();
+                std::thread::spawn(move || {
+                    for v in 0..1000 {
+                        gauge.set_max((v * 4 + i) as f64);
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        assert_eq!(gauge.get(), 3999.0);
+        gauge.set_min(0.5);
+        assert_eq!(gauge.get(), 0.5);
+
+        gauge.set_to_current_time();
+        assert!(gauge.get() > 1_500_000_000.0);
+    }
+
+    #[test]
+    fn test_local_gauge() {
+        let gauge = IntGauge::new("test_local_gauge", "test help").unwrap();
+        gauge.set(10);
+        let local = gauge.local();
+
+        local.inc();
+        local.add(5);
+        local.sub(2);
+        local.dec();
+        assert_eq!(local.get(), 3);
+        assert_eq!(gauge.get(), 10);
+        local.flush();
+        assert_eq!(local.get(), 0);
+        assert_eq!(gauge.get(), 13);
+
+        local.sub(20);
+        local.clear();
+        local.flush();
+        assert_eq!(gauge.get(), 13);
+
+        let vec = GaugeVec::new(Opts::new("test_local_gauge_vec", "test help"), &["l"]).unwrap();
+        let mut local_vec = vec.local();
+        local_vec.with_label_values(&["a"]).add(1.5);
+        local_vec.with_label_values(&["b"]).sub(0.5);
+        assert_eq!(vec.with_label_values(&["a"]).get(), 0.0);
+        local_vec.flush();
+        assert_eq!(vec.with_label_values(&["a"]).get(), 1.5);
+        assert_eq!(vec.with_label_values(&["b"]).get(), -0.5);
+
+        local_vec.remove_label_values(&["a"]).unwrap();
+        assert!(local_vec.remove_label_values(&["a"]).is_err());
+
+        // Unflushed changes are flushed on drop.
+        let local = gauge.local();
+        local.add(7);
+        drop(local);
+        assert_eq!(gauge.get(), 20);
+    }
+
+    #[test]
+    fn test_auto_flush_local_gauge() {
+        use std::cell::Cell;
+        use std::thread::LocalKey;
+
+        use crate::local::{AFLocalGauge, GaugeDelegator, MayFlush};
+
+        lazy_static::lazy_static! {
+            static ref INFLIGHT: IntGauge = IntGauge::new("test_inflight", "test help").unwrap();
+        }
+
+        struct Local {
+            inflight: LocalIntGauge,
+            last_flush: Cell<u64>,
+        }
+
+        impl LocalMetric for Local {
+            fn flush(&self) {
+                self.inflight.flush();
+            }
+        }
+
+        impl MayFlush for Local {
+            fn may_flush(&self) {
+                MayFlush::try_flush(self, &self.last_flush, 0);
+            }
+        }
+
+        thread_local! {
+            static LOCAL: Local = Local {
+                inflight: INFLIGHT.local(),
+                last_flush: Cell::new(0),
+            };
+        }
+
+        struct Delegator;
+
+        impl GaugeDelegator<Local, LocalIntGauge> for Delegator {
+            fn get_root_metric(&self) -> &'static LocalKey<Local> {
+                &LOCAL
+            }
+
+            fn get_local<'a>(&self, root_metric: &'a Local) -> &'a LocalIntGauge {
+                &root_metric.inflight
+            }
+        }
+
+        let gauge = AFLocalGauge::new(Delegator);
+        gauge.add(3);
+        gauge.dec();
+        assert_eq!(gauge.get(), 0);
+        assert_eq!(INFLIGHT.get(), 2);
+    }
+
     #[test]
     fn test_gauge_vec_with_labels() {
         let vec = GaugeVec::new(
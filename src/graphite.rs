@@ -0,0 +1,359 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::convert::TryFrom;
+use std::io::{self, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::errors::Result;
+use crate::histogram::BUCKET_LABEL;
+use crate::periodic::PeriodicTask;
+use crate::proto::{self, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+use crate::registry::Registry;
+
+const QUANTILE_LABEL: &str = "quantile";
+
+/// How a [`GraphiteBridge`] maps the name and the labels of a sample to a
+/// Graphite path.
+#[derive(Clone, Copy, Debug)]
+pub enum LabelMapping {
+    /// Append each label name and value as path components, e.g.
+    /// `requests_total.code.200`.
+    Path,
+    /// Use Graphite tags, e.g. `requests_total;code=200`.
+    Tags,
+    /// Build the path with a function of the sanitized sample name and label
+    /// pairs.
+    Custom(fn(&str, &[(String, String)]) -> String),
+}
+
+/// A struct that bundles the options of a [`GraphiteBridge`].
+#[derive(Clone, Debug)]
+pub struct GraphiteOpts {
+    /// A prefix joined to every path with a '.'. It may contain '.' itself,
+    /// each of its components is sanitized.
+    pub prefix: String,
+    /// The mapping of labels to paths.
+    pub label_mapping: LabelMapping,
+    /// The sanitization applied to the metric name, label names and label
+    /// values.
+    pub sanitizer: fn(&str) -> String,
+    /// The timeout for connecting to and writing to Carbon.
+    pub timeout: Duration,
+}
+
+impl GraphiteOpts {
+    /// `new` creates the options without prefix, mapping labels to path
+    /// components, replacing every character but ASCII letters, digits and
+    /// '_' with '_' as the Python client does, and with a timeout of 30
+    /// seconds.
+    pub fn new() -> GraphiteOpts {
+        GraphiteOpts {
+            prefix: String::new(),
+            label_mapping: LabelMapping::Path,
+            sanitizer: sanitize,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// `prefix` sets the prefix.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// `label_mapping` sets the mapping of labels to paths.
+    pub fn label_mapping(mut self, label_mapping: LabelMapping) -> Self {
+        self.label_mapping = label_mapping;
+        self
+    }
+
+    /// `sanitizer` sets the sanitization.
+    pub fn sanitizer(mut self, sanitizer: fn(&str) -> String) -> Self {
+        self.sanitizer = sanitizer;
+        self
+    }
+
+    /// `timeout` sets the timeout.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+fn sanitize(s: &str) -> String {
+    s.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// `GraphiteBridge` writes the metrics of a registry to a Carbon endpoint
+/// using the plaintext protocol, one `path value timestamp` line per sample.
+/// Histograms and summaries are expanded into their `_bucket`, `_sum` and
+/// `_count` samples and their quantiles, as in the text format.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use prometheus::{GraphiteBridge, GraphiteOpts};
+///
+/// let opts = GraphiteOpts::new().prefix("app");
+/// let bridge = GraphiteBridge::with_opts("127.0.0.1:2003", prometheus::default_registry(), opts);
+/// let task = bridge.start(Duration::from_secs(60));
+/// // Do the work.
+/// task.shutdown().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct GraphiteBridge {
+    address: String,
+    registry: Registry,
+    opts: GraphiteOpts,
+}
+
+impl GraphiteBridge {
+    /// `new` creates a bridge writing the metrics of `registry` to the Carbon
+    /// endpoint at `address`, e.g. `127.0.0.1:2003`.
+    pub fn new<S: Into<String>>(address: S, registry: &Registry) -> GraphiteBridge {
+        GraphiteBridge::with_opts(address, registry, GraphiteOpts::new())
+    }
+
+    /// `with_opts` creates a bridge with custom options.
+    pub fn with_opts<S: Into<String>>(
+        address: S,
+        registry: &Registry,
+        opts: GraphiteOpts,
+    ) -> GraphiteBridge {
+        GraphiteBridge {
+            address: address.into(),
+            registry: registry.clone(),
+            opts,
+        }
+    }
+
+    /// `push` gathers the metrics and writes them to Carbon over a new
+    /// connection.
+    pub fn push(&self) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let lines = self.render(&self.registry.gather(), now);
+
+        let addr = self.address.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "no address to connect to")
+        })?;
+        let mut stream = TcpStream::connect_timeout(&addr, self.opts.timeout)?;
+        stream.set_write_timeout(Some(self.opts.timeout))?;
+        stream.write_all(lines.as_bytes())?;
+        stream.flush()?;
+        Ok(())
+    }
+
+    /// `start` pushes every `interval` from a background thread.
+    pub fn start(self, interval: Duration) -> PeriodicTask {
+        PeriodicTask::spawn("prometheus-graphite", interval, move || self.push())
+    }
+
+    /// `render` renders the metric families as plaintext lines, stamping
+    /// metrics without their own timestamp with `now` in seconds. Samples with
+    /// a non-finite value are skipped.
+    fn render(&self, mfs: &[proto::MetricFamily], now: u64) -> String {
+        let mut lines = String::new();
+        for mf in mfs {
+            let name = mf.name();
+            for m in mf.get_metric() {
+                let timestamp = match m.timestamp_ms() {
+                    0 => now,
+                    t => u64::try_from(t).map_or(now, |t| t / 1000),
+                };
+                let mut write = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+                    if value.is_finite() {
+                        let path = self.path(&format!("{}{}", name, suffix), m, extra);
+                        lines.push_str(&format!("{} {} {}\n", path, value, timestamp));
+                    }
+                };
+
+                match mf.get_field_type() {
+                    MetricType::COUNTER => write("", None, m.get_counter().get_value()),
+                    MetricType::GAUGE => write("", None, m.get_gauge().get_value()),
+                    MetricType::HISTOGRAM => {
+                        let h = m.get_histogram();
+                        let mut inf_seen = false;
+                        for b in h.get_bucket() {
+                            let upper_bound = b.upper_bound();
+                            inf_seen |= upper_bound == f64::INFINITY;
+                            let le = (BUCKET_LABEL, format_bound(upper_bound));
+                            write("_bucket", Some(le), b.cumulative_count() as f64);
+                        }
+                        if !inf_seen {
+                            let le = (BUCKET_LABEL, format_bound(f64::INFINITY));
+                            write("_bucket", Some(le), h.get_sample_count() as f64);
+                        }
+                        write("_sum", None, h.get_sample_sum());
+                        write("_count", None, h.get_sample_count() as f64);
+                    }
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+                        for q in s.get_quantile() {
+                            let quantile = (QUANTILE_LABEL, q.quantile().to_string());
+                            write("", Some(quantile), q.value());
+                        }
+                        write("_sum", None, s.sample_sum());
+                        write("_count", None, s.sample_count() as f64);
+                    }
+                    MetricType::UNTYPED => {
+                        #[allow(deprecated)]
+                        let value = m.get_untyped().get_value();
+                        write("", None, value);
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn path(&self, name: &str, m: &proto::Metric, extra: Option<(&str, String)>) -> String {
+        let sanitizer = self.opts.sanitizer;
+        let mut labels: Vec<(String, String)> = m
+            .get_label()
+            .iter()
+            .map(|lp| (sanitizer(lp.name()), sanitizer(lp.value())))
+            .collect();
+        if let Some((k, v)) = extra {
+            labels.push((sanitizer(k), sanitizer(&v)));
+        }
+        labels.sort();
+        let name = sanitizer(name);
+
+        let path = match self.opts.label_mapping {
+            LabelMapping::Path => {
+                let mut path = name;
+                for (k, v) in &labels {
+                    path.push_str(&format!(".{}.{}", k, v));
+                }
+                path
+            }
+            LabelMapping::Tags => {
+                let mut path = name;
+                for (k, v) in &labels {
+                    path.push_str(&format!(";{}={}", k, v));
+                }
+                path
+            }
+            LabelMapping::Custom(f) => f(&name, &labels),
+        };
+
+        if self.opts.prefix.is_empty() {
+            path
+        } else {
+            let prefix: Vec<String> = self.opts.prefix.split('.').map(sanitizer).collect();
+            format!("{}.{}", prefix.join("."), path)
+        }
+    }
+}
+
+fn format_bound(f: f64) -> String {
+    if f == f64::INFINITY {
+        "+Inf".to_owned()
+    } else {
+        f.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::{CounterVec, Histogram, HistogramOpts, Opts};
+
+    fn registry() -> Registry {
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code", "path"]).unwrap();
+        counter.with_label_values(&["200", "/api"]).inc_by(2.0);
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("latency", "help").buckets(vec![0.5])).unwrap();
+        histogram.observe(0.25);
+        registry.register(Box::new(counter)).unwrap();
+        registry.register(Box::new(histogram)).unwrap();
+        registry
+    }
+
+    #[test]
+    fn test_render() {
+        let registry = registry();
+        let bridge = GraphiteBridge::with_opts("", &registry, GraphiteOpts::new().prefix("app"));
+        assert_eq!(
+            bridge.render(&registry.gather(), 100),
+            "app.latency_bucket.le.0_5 1 100\n\
+             app.latency_bucket.le._Inf 1 100\n\
+             app.latency_sum 0.25 100\n\
+             app.latency_count 1 100\n\
+             app.requests.code.200.path._api 2 100\n"
+        );
+
+        let opts = GraphiteOpts::new().label_mapping(LabelMapping::Tags);
+        let bridge = GraphiteBridge::with_opts("", &registry, opts);
+        assert!(bridge
+            .render(&registry.gather(), 100)
+            .ends_with("requests;code=200;path=_api 2 100\n"));
+
+        fn custom(name: &str, labels: &[(String, String)]) -> String {
+            let values: Vec<&str> = labels.iter().map(|(_, v)| v.as_str()).collect();
+            format!("{}.{}", values.join("."), name)
+        }
+        let opts = GraphiteOpts::new()
+            .label_mapping(LabelMapping::Custom(custom))
+            .sanitizer(|s| s.replace('/', ""));
+        let bridge = GraphiteBridge::with_opts("", &registry, opts);
+        assert!(bridge
+            .render(&registry.gather(), 100)
+            .ends_with("200.api.requests 2 100\n"));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_render_untyped() {
+        use crate::proto::{Metric, MetricFamily, Untyped};
+
+        let mut untyped = Untyped::default();
+        untyped.set_value(2.5);
+        let mut m = Metric::default();
+        m.set_untyped(untyped);
+        let mut mf = MetricFamily::default();
+        mf.set_name("queue".to_owned());
+        mf.set_field_type(MetricType::UNTYPED);
+        mf.set_metric(vec![m]);
+
+        let bridge = GraphiteBridge::new("", &Registry::new());
+        assert_eq!(bridge.render(&[mf.clone()], 100), "queue 2.5 100\n");
+
+        // Timestamps before the epoch fall back to now, and each component of
+        // the prefix is sanitized.
+        mf.mut_metric()[0].set_timestamp_ms(-5_000);
+        let opts = GraphiteOpts::new().prefix("my app.web-1");
+        let bridge = GraphiteBridge::with_opts("", &Registry::new(), opts);
+        assert_eq!(bridge.render(&[mf], 100), "my_app.web_1.queue 2.5 100\n");
+    }
+
+    #[test]
+    fn test_push() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = thread::spawn(move || {
+            let mut received = String::new();
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.read_to_string(&mut received).unwrap();
+            received
+        });
+
+        let bridge = GraphiteBridge::new(addr, &registry());
+        bridge.start(Duration::from_secs(3600)).shutdown().unwrap();
+        let received = handle.join().unwrap();
+        assert_eq!(received.lines().count(), 5);
+        assert!(received.starts_with("latency_bucket.le.0_5 1 "));
+    }
+}
@@ -1,14 +1,15 @@
 // Copyright 2014 The Prometheus Authors
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
-use std::cell::RefCell;
+use std::cell::{RefCell, UnsafeCell};
 use std::collections::HashMap;
 use std::convert::From;
+use std::fmt;
 use std::sync::{
     atomic::{AtomicU64 as StdAtomicU64, Ordering},
     Arc, Mutex,
 };
-use std::time::{Duration, Instant as StdInstant};
+use std::time::{Duration, Instant as StdInstant, SystemTime};
 
 use crate::atomic64::{Atomic, AtomicF64, AtomicU64};
 use crate::desc::{Desc, Describer};
@@ -26,6 +27,41 @@ pub const DEFAULT_BUCKETS: &[f64; 11] = &[
     0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
 ];
 
+/// [`Histogram`] buckets for HTTP request latencies in seconds, from 1ms to
+/// one minute.
+pub const HTTP_LATENCY_BUCKETS: &[f64; 15] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// [`Histogram`] buckets for sizes in bytes, powers of 4 from 64B to 1GiB.
+pub const BYTE_SIZE_BUCKETS: &[f64; 13] = &[
+    64.0,
+    256.0,
+    1024.0,
+    4096.0,
+    16384.0,
+    65536.0,
+    262144.0,
+    1048576.0,
+    4194304.0,
+    16777216.0,
+    67108864.0,
+    268435456.0,
+    1073741824.0,
+];
+
+/// [`Histogram`] buckets for queue depths, 0 and powers of 2 up to 1024.
+pub const QUEUE_DEPTH_BUCKETS: &[f64; 12] = &[
+    0.0, 1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0, 256.0, 512.0, 1024.0,
+];
+
+/// Below this many buckets, [`validate_buckets`] warns about too few buckets.
+const MIN_RESOLUTION_BUCKETS: usize = 5;
+
+/// Above this ratio of adjacent upper bounds, [`validate_buckets`] warns
+/// about a too wide bucket.
+const MAX_BUCKET_RATIO: f64 = 10.0;
+
 /// Used for the label that defines the upper bound of a
 /// bucket of a histogram ("le" -> "less or equal").
 pub const BUCKET_LABEL: &str = "le";
@@ -33,9 +69,9 @@ pub const BUCKET_LABEL: &str = "le";
 #[inline]
 fn check_bucket_label(label: &str) -> Result<()> {
     if label == BUCKET_LABEL {
-        return Err(Error::Msg(
-            "`le` is not allowed as label name in histograms".to_owned(),
-        ));
+        return Err(Error::ReservedLabel {
+            name: label.to_owned(),
+        });
     }
 
     Ok(())
@@ -48,12 +84,10 @@ fn check_and_adjust_buckets(mut buckets: Vec<f64>) -> Result<Vec<f64>> {
 
     for (i, upper_bound) in buckets.iter().enumerate() {
         if i < (buckets.len() - 1) && *upper_bound >= buckets[i + 1] {
-            return Err(Error::Msg(format!(
-                "histogram buckets must be in increasing \
-                 order: {} >= {}",
-                upper_bound,
-                buckets[i + 1]
-            )));
+            return Err(Error::BucketsNotSorted {
+                upper_bound: *upper_bound,
+                next: buckets[i + 1],
+            });
         }
     }
 
@@ -70,8 +104,10 @@ fn check_and_adjust_buckets(mut buckets: Vec<f64>) -> Result<Vec<f64>> {
 /// mandatory to set Name and Help to a non-empty string. All other fields are
 /// optional and can safely be left at their zero value.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HistogramOpts {
     /// A container holding various options.
+    #[cfg_attr(feature = "serde", serde(flatten))]
     pub common_opts: Opts,
 
     /// Defines the buckets into which observations are counted. Each
@@ -79,9 +115,15 @@ pub struct HistogramOpts {
     /// values must be sorted in strictly increasing order. There is no need
     /// to add a highest bucket with +Inf bound, it will be added
     /// implicitly. The default value is DefBuckets.
+    #[cfg_attr(feature = "serde", serde(default = "default_buckets"))]
     pub buckets: Vec<f64>,
 }
 
+#[cfg(feature = "serde")]
+fn default_buckets() -> Vec<f64> {
+    Vec::from(DEFAULT_BUCKETS as &'static [f64])
+}
+
 impl HistogramOpts {
     /// Create a [`HistogramOpts`] with the `name` and `help` arguments.
     pub fn new<S1: Into<String>, S2: Into<String>>(name: S1, help: S2) -> HistogramOpts {
@@ -154,6 +196,135 @@ impl From<Opts> for HistogramOpts {
     }
 }
 
+/// Up to this many buckets, a linear scan is the fastest way to find the
+/// bucket of an observation.
+const LINEAR_SCAN_MAX_BUCKETS: usize = 16;
+
+/// The relative error tolerated when detecting evenly spaced buckets.
+const SPACING_TOLERANCE: f64 = 1e-9;
+
+/// The strategy to find the bucket of an observation, picked per histogram
+/// from its upper bounds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BucketLookup {
+    /// Scan the upper bounds, for few buckets.
+    LinearScan,
+    /// Binary search the upper bounds.
+    BinarySearch,
+    /// Compute the index of buckets `width` apart, as created by
+    /// [`linear_buckets`].
+    Linear { start: f64, width: f64 },
+    /// Compute the index of buckets growing by a constant factor, as created
+    /// by [`exponential_buckets`].
+    Exponential { start: f64, ln_factor: f64 },
+}
+
+impl BucketLookup {
+    fn new(upper_bounds: &[f64]) -> BucketLookup {
+        let n = upper_bounds.len();
+        if n <= LINEAR_SCAN_MAX_BUCKETS {
+            return BucketLookup::LinearScan;
+        }
+
+        let (start, last) = (upper_bounds[0], upper_bounds[n - 1]);
+        let width = (last - start) / (n - 1) as f64;
+        let is_linear = upper_bounds.iter().enumerate().all(|(i, &b)| {
+            let expected = start + width * i as f64;
+            (b - expected).abs() <= SPACING_TOLERANCE * expected.abs().max(width)
+        });
+        if is_linear {
+            return BucketLookup::Linear { start, width };
+        }
+
+        if start > 0.0 {
+            let ln_factor = (last / start).ln() / (n - 1) as f64;
+            let is_exponential = upper_bounds.iter().enumerate().all(|(i, &b)| {
+                ((b / start).ln() - ln_factor * i as f64).abs() <= SPACING_TOLERANCE * ln_factor
+            });
+            if is_exponential {
+                return BucketLookup::Exponential { start, ln_factor };
+            }
+        }
+
+        BucketLookup::BinarySearch
+    }
+
+    /// Return the index of the first upper bound not less than `v`, or `None`
+    /// if `v` only falls into the +Inf bucket.
+    #[inline]
+    fn find(&self, upper_bounds: &[f64], v: f64) -> Option<usize> {
+        let i = match *self {
+            BucketLookup::LinearScan => return upper_bounds.iter().position(|&b| v <= b),
+            _ if v.is_nan() => return None,
+            BucketLookup::BinarySearch => upper_bounds.partition_point(|&b| b < v),
+            BucketLookup::Linear { start, width } => {
+                fix_index(upper_bounds, v, ((v - start) / width).ceil())
+            }
+            BucketLookup::Exponential { start, ln_factor } => {
+                fix_index(upper_bounds, v, ((v / start).ln() / ln_factor).ceil())
+            }
+        };
+        if i < upper_bounds.len() {
+            Some(i)
+        } else {
+            None
+        }
+    }
+}
+
+/// Clamp an estimated bucket index and step it to the exact bucket, to
+/// compensate for floating point errors.
+#[inline]
+fn fix_index(upper_bounds: &[f64], v: f64, estimate: f64) -> usize {
+    // Casting saturates, NaN of a non-positive `v` with exponential buckets
+    // becomes 0.
+    let mut i = (estimate.max(0.0) as usize).min(upper_bounds.len());
+    while i > 0 && v <= upper_bounds[i - 1] {
+        i -= 1;
+    }
+    while i < upper_bounds.len() && v > upper_bounds[i] {
+        i += 1;
+    }
+    i
+}
+
+/// The buckets of a histogram: their upper bounds, without +Inf, and how to
+/// look them up.
+#[derive(Debug)]
+pub(crate) struct BucketLayout {
+    pub(crate) upper_bounds: Vec<f64>,
+    lookup: BucketLookup,
+}
+
+impl BucketLayout {
+    fn new(upper_bounds: Vec<f64>) -> BucketLayout {
+        BucketLayout {
+            lookup: BucketLookup::new(&upper_bounds),
+            upper_bounds,
+        }
+    }
+
+    /// Return the index of the bucket of `v`, or `None` for the +Inf bucket.
+    #[inline]
+    pub(crate) fn find(&self, v: f64) -> Option<usize> {
+        self.lookup.find(&self.upper_bounds, v)
+    }
+}
+
+/// What happens to the observations of a [`Histogram`] when its buckets
+/// change, see [`Histogram::set_buckets`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BucketMigration {
+    /// Drop all observations, restarting the histogram like
+    /// [`Histogram::reset`].
+    Reset,
+    /// Keep the count and the sum, and move the count of each old bucket to
+    /// the new bucket containing its upper bound. Cumulative counts stay exact
+    /// at upper bounds common to both layouts and are underestimated in
+    /// between.
+    Remap,
+}
+
 /// Representation of a hot or cold shard.
 ///
 /// See [`HistogramCore`] for details.
@@ -161,22 +332,63 @@ impl From<Opts> for HistogramOpts {
 struct Shard {
     sum: AtomicF64,
     count: AtomicU64,
-    buckets: Vec<AtomicU64>,
+    buckets: UnsafeCell<ShardBuckets>,
 }
 
-impl Shard {
-    fn new(num_buckets: usize) -> Self {
-        let mut buckets = Vec::new();
-        for _ in 0..num_buckets {
-            buckets.push(AtomicU64::new(0));
-        }
+/// The bucket counts of a [`Shard`] together with their layout.
+#[derive(Debug)]
+struct ShardBuckets {
+    layout: Arc<BucketLayout>,
+    counts: Vec<AtomicU64>,
+}
 
+impl ShardBuckets {
+    fn new(layout: Arc<BucketLayout>) -> Self {
+        let counts = layout
+            .upper_bounds
+            .iter()
+            .map(|_| AtomicU64::new(0))
+            .collect();
+        ShardBuckets { layout, counts }
+    }
+}
+
+// SAFETY: The buckets are only replaced through `Shard::replace_buckets`,
+// whose callers guarantee that no other reference to them exists. All other
+// accesses are shared and atomic.
+unsafe impl Sync for Shard {}
+
+impl Shard {
+    fn new(layout: Arc<BucketLayout>) -> Self {
         Shard {
             sum: AtomicF64::new(0.0),
             count: AtomicU64::new(0),
-            buckets,
+            buckets: UnsafeCell::new(ShardBuckets::new(layout)),
         }
     }
+
+    /// Return the buckets of the shard.
+    ///
+    /// # Safety
+    ///
+    /// The buckets must not be replaced while the reference is alive. This
+    /// holds while holding the collect lock, or while observing into the hot
+    /// shard, see [`HistogramCore::record`].
+    unsafe fn buckets(&self) -> &ShardBuckets {
+        &*self.buckets.get()
+    }
+
+    /// Replace the buckets with empty ones of `layout`, returning the old
+    /// ones.
+    ///
+    /// # Safety
+    ///
+    /// The collect lock must be held and the shard must be cold with all
+    /// `observe` calls on it finished, so that no other reference to the
+    /// buckets exists.
+    unsafe fn replace_buckets(&self, layout: Arc<BucketLayout>) -> ShardBuckets {
+        std::mem::replace(&mut *self.buckets.get(), ShardBuckets::new(layout))
+    }
 }
 
 /// Index into an array of [`Shard`]s.
@@ -222,6 +434,10 @@ impl From<ShardIndex> for usize {
     }
 }
 
+/// The largest count of a single `observe_n` call. The observation count of a
+/// histogram has 63 bits, a larger count would carry into its shard bit.
+const MAX_OBSERVE_COUNT: u64 = (1 << 63) - 1;
+
 /// An atomic u64 with the most significant used as a [`ShardIndex`] and the
 /// remaining 63 bits used to count [`Histogram`] observations.
 #[derive(Debug)]
@@ -263,10 +479,10 @@ impl ShardAndCount {
         ShardAndCount::split_shard_index_and_count(n)
     }
 
-    /// Increment the observation count by one leaving the most significant bit
-    /// i.e. the [`ShardIndex`] untouched.
-    fn inc(&self, ordering: Ordering) -> (ShardIndex, u64) {
-        self.inc_by(1, ordering)
+    /// Decrement the observation count leaving the most significant bit i.e.
+    /// the [`ShardIndex`] untouched. The count must not go below zero.
+    fn dec_by(&self, delta: u64, ordering: Ordering) {
+        self.inner.fetch_sub(delta, ordering);
     }
 
     fn split_shard_index_and_count(n: u64) -> (ShardIndex, u64) {
@@ -323,7 +539,12 @@ pub struct HistogramCore {
     /// and which one the cold at any given point in time.
     shards: [Shard; 2],
 
-    upper_bounds: Vec<f64>,
+    /// The current bucket layout, for readers outside of `observe` and
+    /// collect operations. The shards keep their own reference.
+    layout: Mutex<Arc<BucketLayout>>,
+
+    /// The time the histogram was created or last reset.
+    created: Mutex<SystemTime>,
 }
 
 impl HistogramCore {
@@ -340,6 +561,7 @@ impl HistogramCore {
         let label_pairs = make_label_pairs(&desc, label_values)?;
 
         let buckets = check_and_adjust_buckets(opts.buckets.clone())?;
+        let layout = Arc::new(BucketLayout::new(buckets));
 
         Ok(HistogramCore {
             desc,
@@ -348,42 +570,88 @@ impl HistogramCore {
             collect_lock: Mutex::new(()),
 
             shard_and_count: ShardAndCount::new(),
-            shards: [Shard::new(buckets.len()), Shard::new(buckets.len())],
+            shards: [
+                Shard::new(Arc::clone(&layout)),
+                Shard::new(Arc::clone(&layout)),
+            ],
+
+            layout: Mutex::new(layout),
 
-            upper_bounds: buckets,
+            created: Mutex::new(SystemTime::now()),
         })
     }
 
     /// Record a given observation (f64) in the histogram.
+    pub fn observe(&self, v: f64) {
+        self.observe_n(v, 1)
+    }
+
+    /// Record the observation `v` `count` times in the histogram. Counts above
+    /// [`MAX_OBSERVE_COUNT`] are ignored.
+    pub fn observe_n(&self, v: f64, count: u64) {
+        if count == 0 || count > MAX_OBSERVE_COUNT {
+            return;
+        }
+        self.record(count, |buckets| {
+            if let Some(i) = buckets.layout.find(v) {
+                buckets.counts[i].inc_by(count);
+            }
+            v * count as f64
+        })
+    }
+
+    /// Record all observations of `vs` in the histogram. They are collected
+    /// together, never partially.
+    pub fn observe_batch(&self, vs: &[f64]) {
+        if vs.is_empty() {
+            return;
+        }
+        self.record(vs.len() as u64, |buckets| {
+            let mut sum = 0.0;
+            for &v in vs {
+                if let Some(i) = buckets.layout.find(v) {
+                    buckets.counts[i].inc_by(1);
+                }
+                sum += v;
+            }
+            sum
+        })
+    }
+
+    /// Record `count` observations in the hot shard. `f` updates the buckets
+    /// of the shard and returns the sum of the observations.
+    ///
+    /// The buckets of the hot shard aren't replaced until its count is
+    /// increased, see [`HistogramCore::set_buckets`].
     //
     // First increase the overall observation counter and thus learn which shard
     // is the current hot shard. Subsequently on the hot shard update the
-    // corresponding bucket count, adjust the shard's sum and finally increase
+    // corresponding bucket counts, adjust the shard's sum and finally increase
     // the shard's count.
-    pub fn observe(&self, v: f64) {
+    #[inline]
+    fn record<F: FnOnce(&ShardBuckets) -> f64>(&self, count: u64, f: F) {
+        debug_assert!(count <= MAX_OBSERVE_COUNT);
         // The collect code path uses `self.shard_and_count` and
         // `self.shards[x].count` to ensure not to collect data from a shard
         // while observe calls are still operating on it.
         //
-        // To ensure the above, this `inc` needs to use `Acquire` ordering to
+        // To ensure the above, this `inc_by` needs to use `Acquire` ordering to
         // force anything below this line to stay below it.
-        let (shard_index, _count) = self.shard_and_count.inc(Ordering::Acquire);
+        let (shard_index, _count) = self.shard_and_count.inc_by(count, Ordering::Acquire);
 
         let shard: &Shard = &self.shards[usize::from(shard_index)];
 
-        // Try find the bucket.
-        let mut iter = self
-            .upper_bounds
-            .iter()
-            .enumerate()
-            .filter(|&(_, f)| v <= *f);
-        if let Some((i, _)) = iter.next() {
-            shard.buckets[i].inc_by(1);
-        }
-
-        shard.sum.inc_by(v);
+        // SAFETY: The shard can't be drained and thus its buckets can't be
+        // replaced before its count is increased below.
+        let sum = f(unsafe { shard.buckets() });
+        shard.sum.inc_by(sum);
         // Use `Release` ordering to ensure all operations above stay above.
-        shard.count.inc_by_with_ordering(1, Ordering::Release);
+        shard.count.inc_by_with_ordering(count, Ordering::Release);
+    }
+
+    /// Return the current bucket layout.
+    pub(crate) fn layout(&self) -> Arc<BucketLayout> {
+        Arc::clone(&self.layout.lock().expect("Lock poisoned"))
     }
 
     /// Make a snapshot of the current histogram state exposed as a Protobuf
@@ -395,6 +663,54 @@ impl HistogramCore {
     pub fn proto(&self) -> proto::Histogram {
         let collect_guard = self.collect_lock.lock().expect("Lock poisoned");
 
+        let (cold_shard, hot_shard, overall_count) = self.flip_and_drain();
+
+        // Get cold shard sum and reset to 0.
+        //
+        // Use `Acquire` for load and `Release` for store to ensure not to
+        // interfere with previous or upcoming collect calls.
+        let cold_shard_sum = cold_shard.sum.swap(0.0, Ordering::AcqRel);
+
+        let mut h = proto::Histogram::default();
+        h.set_sample_sum(cold_shard_sum);
+        h.set_sample_count(overall_count);
+        h.set_created_timestamp((*self.created.lock().expect("Lock poisoned")).into());
+
+        // SAFETY: The collect lock is held, so the buckets aren't replaced.
+        let (cold_buckets, hot_buckets) = unsafe { (cold_shard.buckets(), hot_shard.buckets()) };
+        let mut cumulative_count = 0;
+        let mut buckets = Vec::with_capacity(cold_buckets.counts.len());
+        for (i, upper_bound) in cold_buckets.layout.upper_bounds.iter().enumerate() {
+            // Reset the cold shard and update the hot shard.
+            //
+            // Use `Acquire` for load and `Release` for store to ensure not to
+            // interfere with previous or upcoming collect calls.
+            let cold_bucket_count = cold_buckets.counts[i].swap(0, Ordering::AcqRel);
+            hot_buckets.counts[i].inc_by(cold_bucket_count);
+
+            cumulative_count += cold_bucket_count;
+            let mut b = proto::Bucket::default();
+            b.set_cumulative_count(cumulative_count);
+            b.set_upper_bound(*upper_bound);
+            buckets.push(b);
+        }
+        h.set_bucket(buckets);
+
+        // Update the hot shard.
+        hot_shard.count.inc_by(overall_count);
+        hot_shard.sum.inc_by(cold_shard_sum);
+
+        drop(collect_guard);
+
+        h
+    }
+
+    /// Switch the hot and the cold shard and wait for all remaining `observe`
+    /// calls on the now cold shard to finish. Return the cold shard with its
+    /// count reset, the hot shard and the overall count at the switch.
+    ///
+    /// The collect lock must be held.
+    fn flip_and_drain(&self) -> (&Shard, &Shard, u64) {
         // `flip` needs to use AcqRel ordering to ensure the lock operation
         // above stays above and the histogram operations (especially the shard
         // resets) below stay below.
@@ -427,41 +743,85 @@ impl HistogramCore {
             .is_err()
         {}
 
-        // Get cold shard sum and reset to 0.
-        //
-        // Use `Acquire` for load and `Release` for store to ensure not to
-        // interfere with previous or upcoming collect calls.
-        let cold_shard_sum = cold_shard.sum.swap(0.0, Ordering::AcqRel);
-
-        let mut h = proto::Histogram::default();
-        h.set_sample_sum(cold_shard_sum);
-        h.set_sample_count(overall_count);
+        (cold_shard, hot_shard, overall_count)
+    }
 
-        let mut cumulative_count = 0;
-        let mut buckets = Vec::with_capacity(self.upper_bounds.len());
-        for (i, upper_bound) in self.upper_bounds.iter().enumerate() {
-            // Reset the cold shard and update the hot shard.
-            //
-            // Use `Acquire` for load and `Release` for store to ensure not to
-            // interfere with previous or upcoming collect calls.
-            let cold_bucket_count = cold_shard.buckets[i].swap(0, Ordering::AcqRel);
-            hot_shard.buckets[i].inc_by(cold_bucket_count);
+    /// Drop all observations so far.
+    //
+    // All observations are in the hot shard, as a collect leaves them. Switch
+    // the shards and reset the now cold shard, keeping only the observations
+    // since the switch.
+    pub fn reset(&self) {
+        let collect_guard = self.collect_lock.lock().expect("Lock poisoned");
 
-            cumulative_count += cold_bucket_count;
-            let mut b = proto::Bucket::default();
-            b.set_cumulative_count(cumulative_count);
-            b.set_upper_bound(*upper_bound);
-            buckets.push(b);
+        let (cold_shard, _hot_shard, overall_count) = self.flip_and_drain();
+        cold_shard.sum.swap(0.0, Ordering::AcqRel);
+        // SAFETY: The collect lock is held, so the buckets aren't replaced.
+        let cold_buckets = unsafe { cold_shard.buckets() };
+        for count in &cold_buckets.counts {
+            count.swap(0, Ordering::AcqRel);
         }
-        h.set_bucket(buckets);
+        self.shard_and_count.dec_by(overall_count, Ordering::AcqRel);
+        *self.created.lock().expect("Lock poisoned") = SystemTime::now();
 
-        // Update the hot shard.
+        drop(collect_guard);
+    }
+
+    /// Change the buckets to `buckets`, migrating the observations so far as
+    /// told by `migration`.
+    //
+    // The buckets of a shard can only be replaced while it is cold and
+    // drained. So first move everything to the hot shard as a collect does
+    // and give the cold shard the new buckets. Then switch the shards, so that
+    // new observations use the new buckets, and move everything from the old
+    // buckets of the now cold shard to the new ones, or drop it.
+    pub fn set_buckets(&self, buckets: Vec<f64>, migration: BucketMigration) -> Result<()> {
+        let layout = Arc::new(BucketLayout::new(check_and_adjust_buckets(buckets)?));
+        let collect_guard = self.collect_lock.lock().expect("Lock poisoned");
+
+        let (cold_shard, hot_shard, overall_count) = self.flip_and_drain();
+        let cold_shard_sum = cold_shard.sum.swap(0.0, Ordering::AcqRel);
+        // SAFETY: The collect lock is held and the cold shard is drained.
+        let old = unsafe { cold_shard.replace_buckets(Arc::clone(&layout)) };
+        // SAFETY: The collect lock is held.
+        let hot_buckets = unsafe { hot_shard.buckets() };
+        for (hot_count, old_count) in hot_buckets.counts.iter().zip(&old.counts) {
+            hot_count.inc_by(old_count.get());
+        }
         hot_shard.count.inc_by(overall_count);
         hot_shard.sum.inc_by(cold_shard_sum);
 
+        let (cold_shard, hot_shard, overall_count) = self.flip_and_drain();
+        let cold_shard_sum = cold_shard.sum.swap(0.0, Ordering::AcqRel);
+        // SAFETY: As above.
+        let old = unsafe { cold_shard.replace_buckets(Arc::clone(&layout)) };
+        match migration {
+            BucketMigration::Reset => {
+                self.shard_and_count.dec_by(overall_count, Ordering::AcqRel);
+                *self.created.lock().expect("Lock poisoned") = SystemTime::now();
+            }
+            BucketMigration::Remap => {
+                // SAFETY: The collect lock is held.
+                let hot_buckets = unsafe { hot_shard.buckets() };
+                for (upper_bound, old_count) in old.layout.upper_bounds.iter().zip(&old.counts) {
+                    if let Some(i) = layout.find(*upper_bound) {
+                        hot_buckets.counts[i].inc_by(old_count.get());
+                    }
+                }
+                hot_shard.count.inc_by(overall_count);
+                hot_shard.sum.inc_by(cold_shard_sum);
+            }
+        }
+
+        *self.layout.lock().expect("Lock poisoned") = layout;
         drop(collect_guard);
+        Ok(())
+    }
 
-        h
+    /// Estimate the `q`-quantile of all observations so far, see
+    /// [`histogram_quantile`].
+    pub fn quantile(&self, q: f64) -> f64 {
+        histogram_quantile(q, &self.proto())
     }
 
     fn sample_sum(&self) -> f64 {
@@ -546,6 +906,56 @@ impl Instant {
     }
 }
 
+/// A source of time for [`HistogramTimer`]s and [`LocalHistogramTimer`]s,
+/// e.g. to time in milliseconds or in CPU time, or to inject a mock clock in
+/// tests.
+pub trait Clock {
+    /// A point in time of the clock.
+    type Instant;
+
+    /// Return the current time.
+    fn now(&self) -> Self::Instant;
+
+    /// Return the time elapsed since `start`, in the unit to observe.
+    fn elapsed(&self, start: &Self::Instant) -> f64;
+}
+
+/// The default [`Clock`] of timers, measuring wall-clock seconds with a
+/// monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MonotonicClock;
+
+impl Clock for MonotonicClock {
+    type Instant = Instant;
+
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn elapsed(&self, start: &Instant) -> f64 {
+        start.elapsed_sec()
+    }
+}
+
+/// A [`Clock`] measuring wall-clock milliseconds with a monotonic clock.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MillisClock;
+
+impl Clock for MillisClock {
+    type Instant = StdInstant;
+
+    fn now(&self) -> StdInstant {
+        StdInstant::now()
+    }
+
+    fn elapsed(&self, start: &StdInstant) -> f64 {
+        StdInstant::now()
+            .saturating_duration_since(*start)
+            .as_secs_f64()
+            * 1000.0
+    }
+}
+
 #[cfg(all(feature = "nightly", target_os = "linux"))]
 use self::coarse::*;
 
@@ -576,24 +986,24 @@ mod coarse {
 /// This timer can be stopped and observed at most once, either automatically (when it
 /// goes out of scope) or manually.
 /// Alternatively, it can be manually stopped and discarded in order to not record its value.
+///
+/// The duration is measured with a [`Clock`], [`MonotonicClock`] by default.
 #[must_use = "Timer should be kept in a variable otherwise it cannot observe duration"]
 #[derive(Debug)]
-pub struct HistogramTimer {
+pub struct HistogramTimer<C: Clock = MonotonicClock> {
     /// A histogram for automatic recording of observations.
     histogram: Histogram,
     /// Whether the timer has already been observed once.
     observed: bool,
+    /// The clock measuring the duration.
+    clock: C,
     /// Starting instant for the timer.
-    start: Instant,
+    start: C::Instant,
 }
 
 impl HistogramTimer {
     fn new(histogram: Histogram) -> Self {
-        Self {
-            histogram,
-            observed: false,
-            start: Instant::now(),
-        }
+        Self::with_clock(histogram, MonotonicClock)
     }
 
     #[cfg(feature = "nightly")]
@@ -601,9 +1011,21 @@ impl HistogramTimer {
         HistogramTimer {
             histogram,
             observed: false,
+            clock: MonotonicClock,
             start: Instant::now_coarse(),
         }
     }
+}
+
+impl<C: Clock> HistogramTimer<C> {
+    fn with_clock(histogram: Histogram, clock: C) -> Self {
+        Self {
+            histogram,
+            observed: false,
+            start: clock.now(),
+            clock,
+        }
+    }
 
     /// Observe and record timer duration (in seconds).
     ///
@@ -632,7 +1054,7 @@ impl HistogramTimer {
     }
 
     fn observe(&mut self, record: bool) -> f64 {
-        let v = self.start.elapsed_sec();
+        let v = self.clock.elapsed(&self.start);
         self.observed = true;
         if record {
             self.histogram.observe(v);
@@ -641,7 +1063,7 @@ impl HistogramTimer {
     }
 }
 
-impl Drop for HistogramTimer {
+impl<C: Clock> Drop for HistogramTimer<C> {
     fn drop(&mut self) {
         if !self.observed {
             self.observe(true);
@@ -695,11 +1117,31 @@ impl Histogram {
         self.core.observe(v)
     }
 
+    /// Add the observation `v` `count` times to the [`Histogram`], e.g. for
+    /// pre-aggregated samples. The count, sum and bucket are updated once.
+    ///
+    /// A `count` of 2^63 or more exceeds the observation counter and is
+    /// ignored.
+    pub fn observe_n(&self, v: f64, count: u64) {
+        self.core.observe_n(v, count)
+    }
+
+    /// Add all observations of `vs` to the [`Histogram`] at once, so that no
+    /// collect sees only part of them.
+    pub fn observe_batch(&self, vs: &[f64]) {
+        self.core.observe_batch(vs)
+    }
+
     /// Return a [`HistogramTimer`] to track a duration.
     pub fn start_timer(&self) -> HistogramTimer {
         HistogramTimer::new(self.clone())
     }
 
+    /// Return a [`HistogramTimer`] to track a duration measured with `clock`.
+    pub fn start_timer_with_clock<C: Clock>(&self, clock: C) -> HistogramTimer<C> {
+        HistogramTimer::with_clock(self.clone(), clock)
+    }
+
     /// Return a [`HistogramTimer`] to track a duration.
     /// It is faster but less precise.
     #[cfg(feature = "nightly")]
@@ -746,6 +1188,32 @@ impl Histogram {
     pub fn get_sample_count(&self) -> u64 {
         self.core.sample_count()
     }
+
+    /// Estimate the `q`-quantile of all samples, see [`histogram_quantile`].
+    pub fn quantile(&self, q: f64) -> f64 {
+        self.core.quantile(q)
+    }
+
+    /// Reset the [`Histogram`], dropping all observations so far. Its created
+    /// timestamp becomes now.
+    pub fn reset(&self) {
+        self.core.reset()
+    }
+
+    /// Change the buckets of the [`Histogram`] at runtime, e.g. on a config
+    /// reload. `migration` tells what happens to the observations so far.
+    /// Local histograms move their pending observations to the new buckets on
+    /// their next flush.
+    ///
+    /// The buckets are validated like [`HistogramOpts::buckets`].
+    pub fn set_buckets(&self, buckets: Vec<f64>, migration: BucketMigration) -> Result<()> {
+        self.core.set_buckets(buckets, migration)
+    }
+
+    /// Return the current bucket layout.
+    pub(crate) fn layout(&self) -> Arc<BucketLayout> {
+        self.core.layout()
+    }
 }
 
 impl Metric for Histogram {
@@ -811,6 +1279,20 @@ impl HistogramVec {
         let vec = self.clone();
         LocalHistogramVec::new(vec)
     }
+
+    /// Change the buckets of all histograms in the vector at runtime, and of
+    /// those created later, see [`Histogram::set_buckets`].
+    pub fn set_buckets(&self, buckets: Vec<f64>, migration: BucketMigration) -> Result<()> {
+        let buckets = check_and_adjust_buckets(buckets)?;
+        // Hold the children lock, so that no histogram is created meanwhile
+        // with the old buckets.
+        let children = self.v.children.write();
+        self.v.opts.write().buckets = buckets.clone();
+        for child in children.values() {
+            child.set_buckets(buckets.clone(), migration)?;
+        }
+        Ok(())
+    }
 }
 
 /// Create `count` buckets, each `width` wide, where the lowest
@@ -822,16 +1304,16 @@ impl HistogramVec {
 /// negative.
 pub fn linear_buckets(start: f64, width: f64, count: usize) -> Result<Vec<f64>> {
     if count < 1 {
-        return Err(Error::Msg(format!(
-            "LinearBuckets needs a positive count, count: {}",
-            count
-        )));
+        return Err(Error::InvalidBucketParameter {
+            param: "count",
+            value: count as f64,
+        });
     }
     if width <= 0.0 {
-        return Err(Error::Msg(format!(
-            "LinearBuckets needs a width greater then 0, width: {}",
-            width
-        )));
+        return Err(Error::InvalidBucketParameter {
+            param: "width",
+            value: width,
+        });
     }
 
     let buckets: Vec<_> = (0..count)
@@ -851,24 +1333,22 @@ pub fn linear_buckets(start: f64, width: f64, count: usize) -> Result<Vec<f64>>
 /// negative, or if `factor` is less than or equal 1.
 pub fn exponential_buckets(start: f64, factor: f64, count: usize) -> Result<Vec<f64>> {
     if count < 1 {
-        return Err(Error::Msg(format!(
-            "exponential_buckets needs a positive count, count: {}",
-            count
-        )));
+        return Err(Error::InvalidBucketParameter {
+            param: "count",
+            value: count as f64,
+        });
     }
     if start <= 0.0 {
-        return Err(Error::Msg(format!(
-            "exponential_buckets needs a positive start value, \
-             start: {}",
-            start
-        )));
+        return Err(Error::InvalidBucketParameter {
+            param: "start",
+            value: start,
+        });
     }
     if factor <= 1.0 {
-        return Err(Error::Msg(format!(
-            "exponential_buckets needs a factor greater than 1, \
-             factor: {}",
-            factor
-        )));
+        return Err(Error::InvalidBucketParameter {
+            param: "factor",
+            value: factor,
+        });
     }
 
     let mut next = start;
@@ -881,9 +1361,231 @@ pub fn exponential_buckets(start: f64, factor: f64, count: usize) -> Result<Vec<
     Ok(buckets)
 }
 
+/// Create `count` buckets from `min` to `max`, where each bucket's upper bound
+/// is a constant factor times the previous one. The final +Inf bucket is not
+/// counted and not included in the returned slice. The returned slice is meant
+/// to be used for the Buckets field of [`HistogramOpts`].
+///
+/// The function returns an error if `count` is zero, if `min` or `max` is not
+/// finite, if `min` is zero or negative, or if `max` is not greater than `min`
+/// for more than one bucket.
+pub fn exponential_buckets_range(min: f64, max: f64, count: usize) -> Result<Vec<f64>> {
+    if count < 1 {
+        return Err(Error::InvalidBucketParameter {
+            param: "count",
+            value: count as f64,
+        });
+    }
+    if !min.is_finite() || min <= 0.0 {
+        return Err(Error::InvalidBucketParameter {
+            param: "min",
+            value: min,
+        });
+    }
+    if !max.is_finite() {
+        return Err(Error::InvalidBucketParameter {
+            param: "max",
+            value: max,
+        });
+    }
+    if count == 1 {
+        return Ok(vec![min]);
+    }
+    if max <= min {
+        return Err(Error::InvalidBucketParameter {
+            param: "max",
+            value: max,
+        });
+    }
+
+    let factor = (max / min).powf(1.0 / (count - 1) as f64);
+    let mut buckets = exponential_buckets(min, factor, count)?;
+    // Avoid accumulated rounding errors in the last bucket.
+    buckets[count - 1] = max;
+    Ok(buckets)
+}
+
+/// The powers of ten bounding [`log_linear_buckets`], within the normal range
+/// of `f64`.
+const LOG_LINEAR_MIN_EXP: i32 = -307;
+const LOG_LINEAR_MAX_EXP: i32 = 308;
+
+/// Create log-linear buckets: `steps` evenly spaced buckets per power of ten,
+/// from `10^min_exp` to `10^max_exp`. For example `(0, 2, 9)` gives 1, 2, ...,
+/// 9, 10, 20, ..., 90, 100. The final +Inf bucket is not counted and not
+/// included in the returned slice. The returned slice is meant to be used for
+/// the Buckets field of [`HistogramOpts`].
+///
+/// The function returns an error if `steps` is zero, if `max_exp` is not
+/// greater than `min_exp`, or if the buckets would leave the normal range of
+/// `f64`, i.e. `min_exp` is below -307 or `max_exp` above 308.
+pub fn log_linear_buckets(min_exp: i32, max_exp: i32, steps: usize) -> Result<Vec<f64>> {
+    if steps < 1 {
+        return Err(Error::InvalidBucketParameter {
+            param: "steps",
+            value: steps as f64,
+        });
+    }
+    if min_exp < LOG_LINEAR_MIN_EXP {
+        return Err(Error::InvalidBucketParameter {
+            param: "min_exp",
+            value: min_exp as f64,
+        });
+    }
+    if max_exp <= min_exp || max_exp > LOG_LINEAR_MAX_EXP {
+        return Err(Error::InvalidBucketParameter {
+            param: "max_exp",
+            value: max_exp as f64,
+        });
+    }
+
+    // Divide by negative powers of ten rather than multiplying by their
+    // inexact inverse, so that e.g. 0.3 stays 0.3.
+    let scale = |m: f64, exp: i32| {
+        if exp < 0 {
+            m / 10f64.powi(-exp)
+        } else {
+            m * 10f64.powi(exp)
+        }
+    };
+    let mut buckets = vec![scale(1.0, min_exp)];
+    for exp in min_exp..max_exp {
+        for step in 1..=steps {
+            buckets.push(scale(1.0 + 9.0 * step as f64 / steps as f64, exp));
+        }
+    }
+    Ok(buckets)
+}
+
+/// A possible problem with the resolution of histogram buckets, found by
+/// [`validate_buckets`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum BucketWarning {
+    /// There are too few buckets to tell the observations apart.
+    TooFewBuckets {
+        /// The number of buckets, without +Inf.
+        count: usize,
+    },
+    /// A bucket spans more than a factor of ten, so quantiles within it may
+    /// be off by as much.
+    WideBucket {
+        /// The upper bound of the previous bucket.
+        lower: f64,
+        /// The upper bound of the bucket.
+        upper: f64,
+    },
+}
+
+impl fmt::Display for BucketWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BucketWarning::TooFewBuckets { count } => write!(
+                f,
+                "only {} buckets, at least {} are recommended",
+                count, MIN_RESOLUTION_BUCKETS
+            ),
+            BucketWarning::WideBucket { lower, upper } => write!(
+                f,
+                "bucket ({}, {}] spans more than a factor of {}",
+                lower, upper, MAX_BUCKET_RATIO
+            ),
+        }
+    }
+}
+
+/// Check `buckets` for too little resolution. It returns an error for buckets
+/// a [`Histogram`] rejects, and otherwise the warnings, meant to be logged
+/// when picking buckets or loading them from a config.
+///
+/// It warns about fewer than 5 buckets and about buckets whose upper bound
+/// is more than ten times the previous positive one.
+pub fn validate_buckets(buckets: &[f64]) -> Result<Vec<BucketWarning>> {
+    let buckets = check_and_adjust_buckets(buckets.to_vec())?;
+
+    let mut warnings = vec![];
+    if buckets.len() < MIN_RESOLUTION_BUCKETS {
+        warnings.push(BucketWarning::TooFewBuckets {
+            count: buckets.len(),
+        });
+    }
+    for pair in buckets.windows(2) {
+        let (lower, upper) = (pair[0], pair[1]);
+        if lower > 0.0 && upper / lower > MAX_BUCKET_RATIO {
+            warnings.push(BucketWarning::WideBucket { lower, upper });
+        }
+    }
+    Ok(warnings)
+}
+
+/// Estimate the `q`-quantile of the observations in a histogram, e.g. one
+/// gathered from a [`Registry`](crate::Registry), with the linear
+/// interpolation within a bucket of PromQL's `histogram_quantile`. The first
+/// bucket is assumed to start at 0, unless its upper bound is not positive.
+///
+/// The result is NaN if there are no observations or no finite buckets, the
+/// highest finite upper bound if the quantile falls into the +Inf bucket, and
+/// -Inf or +Inf if `q` is below 0 or above 1.
+pub fn histogram_quantile(q: f64, h: &proto::Histogram) -> f64 {
+    let buckets: Vec<_> = h
+        .get_bucket()
+        .iter()
+        .map(|b| (b.upper_bound(), b.cumulative_count()))
+        .collect();
+    bucket_quantile(q, &buckets, h.get_sample_count())
+}
+
+/// `bucket_quantile` estimates the `q`-quantile of `count` observations from
+/// cumulative buckets of `(upper_bound, cumulative_count)` sorted by upper
+/// bound, interpolating linearly within a bucket like PromQL's
+/// `histogram_quantile`. The implicit +Inf bucket holds `count`.
+///
+/// The result is NaN without observations or finite buckets, and the highest
+/// finite upper bound if the quantile falls into the +Inf bucket.
+pub(crate) fn bucket_quantile(q: f64, buckets: &[(f64, u64)], count: u64) -> f64 {
+    if q.is_nan() {
+        return f64::NAN;
+    }
+    if q < 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if q > 1.0 {
+        return f64::INFINITY;
+    }
+    if count == 0 {
+        return f64::NAN;
+    }
+
+    let rank = q * count as f64;
+    let (mut lower_bound, mut lower_count) = (0.0, 0);
+    for (i, &(upper_bound, cumulative_count)) in buckets.iter().enumerate() {
+        if upper_bound == f64::INFINITY {
+            break;
+        }
+        if cumulative_count as f64 >= rank {
+            if i == 0 && upper_bound <= 0.0 {
+                return upper_bound;
+            }
+            if cumulative_count == lower_count {
+                return lower_bound;
+            }
+            let fraction = (rank - lower_count as f64) / (cumulative_count - lower_count) as f64;
+            return lower_bound + (upper_bound - lower_bound) * fraction;
+        }
+        lower_bound = upper_bound;
+        lower_count = cumulative_count;
+    }
+
+    match buckets.first() {
+        Some(&(upper_bound, _)) if upper_bound != f64::INFINITY => lower_bound,
+        _ => f64::NAN,
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct LocalHistogramCore {
     histogram: Histogram,
+    layout: Arc<BucketLayout>,
     counts: Vec<u64>,
     count: u64,
     sum: f64,
@@ -907,30 +1609,40 @@ impl Clone for LocalHistogram {
 /// An unsync [`HistogramTimer`].
 #[must_use = "Timer should be kept in a variable otherwise it cannot observe duration"]
 #[derive(Debug)]
-pub struct LocalHistogramTimer {
+pub struct LocalHistogramTimer<C: Clock = MonotonicClock> {
     /// A local histogram for automatic recording of observations.
     local: LocalHistogram,
     /// Whether the timer has already been observed once.
     observed: bool,
+    /// The clock measuring the duration.
+    clock: C,
     /// Starting instant for the timer.
-    start: Instant,
+    start: C::Instant,
 }
 
 impl LocalHistogramTimer {
     fn new(histogram: LocalHistogram) -> Self {
+        Self::with_clock(histogram, MonotonicClock)
+    }
+
+    #[cfg(feature = "nightly")]
+    fn new_coarse(histogram: LocalHistogram) -> Self {
         Self {
             local: histogram,
             observed: false,
-            start: Instant::now(),
+            clock: MonotonicClock,
+            start: Instant::now_coarse(),
         }
     }
+}
 
-    #[cfg(feature = "nightly")]
-    fn new_coarse(histogram: LocalHistogram) -> Self {
+impl<C: Clock> LocalHistogramTimer<C> {
+    fn with_clock(histogram: LocalHistogram, clock: C) -> Self {
         Self {
             local: histogram,
             observed: false,
-            start: Instant::now_coarse(),
+            start: clock.now(),
+            clock,
         }
     }
 
@@ -961,7 +1673,7 @@ impl LocalHistogramTimer {
     }
 
     fn observe(&mut self, record: bool) -> f64 {
-        let v = self.start.elapsed_sec();
+        let v = self.clock.elapsed(&self.start);
         self.observed = true;
         if record {
             self.local.observe(v);
@@ -970,7 +1682,7 @@ impl LocalHistogramTimer {
     }
 }
 
-impl Drop for LocalHistogramTimer {
+impl<C: Clock> Drop for LocalHistogramTimer<C> {
     fn drop(&mut self) {
         if !self.observed {
             self.observe(true);
@@ -980,10 +1692,12 @@ impl Drop for LocalHistogramTimer {
 
 impl LocalHistogramCore {
     fn new(histogram: Histogram) -> LocalHistogramCore {
-        let counts = vec![0; histogram.core.upper_bounds.len()];
+        let layout = histogram.layout();
+        let counts = vec![0; layout.upper_bounds.len()];
 
         LocalHistogramCore {
             histogram,
+            layout,
             counts,
             count: 0,
             sum: 0.0,
@@ -991,20 +1705,29 @@ impl LocalHistogramCore {
     }
 
     pub fn observe(&mut self, v: f64) {
-        // Try find the bucket.
-        let mut iter = self
-            .histogram
-            .core
-            .upper_bounds
-            .iter()
-            .enumerate()
-            .filter(|&(_, f)| v <= *f);
-        if let Some((i, _)) = iter.next() {
-            self.counts[i] += 1;
+        self.observe_n(v, 1)
+    }
+
+    pub fn observe_n(&mut self, v: f64, count: u64) {
+        if count == 0 || count > MAX_OBSERVE_COUNT {
+            return;
+        }
+        // Flush first rather than buffer more than a single record can take.
+        if count > MAX_OBSERVE_COUNT - self.count {
+            self.flush();
+        }
+        if let Some(i) = self.layout.find(v) {
+            self.counts[i] += count;
         }
 
-        self.count += 1;
-        self.sum += v;
+        self.count += count;
+        self.sum += v * count as f64;
+    }
+
+    pub fn observe_batch(&mut self, vs: &[f64]) {
+        for &v in vs {
+            self.observe(v);
+        }
     }
 
     pub fn clear(&mut self) {
@@ -1022,33 +1745,31 @@ impl LocalHistogramCore {
             return;
         }
 
-        {
-            // The collect code path uses `self.shard_and_count` and
-            // `self.shards[x].count` to ensure not to collect data from a shard
-            // while observe calls are still operating on it.
-            //
-            // To ensure the above, this `inc` needs to use `Acquire` ordering
-            // to force anything below this line to stay below it.
-            let (shard_index, _count) = self
-                .histogram
-                .core
-                .shard_and_count
-                .inc_by(self.count, Ordering::Acquire);
-            let shard = &self.histogram.core.shards[shard_index as usize];
-
-            for (i, v) in self.counts.iter().enumerate() {
-                if *v > 0 {
-                    shard.buckets[i].inc_by(*v);
+        let (layout, counts) = (&self.layout, &self.counts);
+        let mut changed_layout = None;
+        self.histogram.core.record(self.count, |buckets| {
+            if Arc::ptr_eq(&buckets.layout, layout) {
+                for (i, v) in counts.iter().enumerate() {
+                    if *v > 0 {
+                        buckets.counts[i].inc_by(*v);
+                    }
+                }
+            } else {
+                // The buckets changed since the observations, remap them.
+                for (upper_bound, v) in layout.upper_bounds.iter().zip(counts) {
+                    if let Some(i) = buckets.layout.find(*upper_bound) {
+                        buckets.counts[i].inc_by(*v);
+                    }
                 }
+                changed_layout = Some(Arc::clone(&buckets.layout));
             }
+            self.sum
+        });
 
-            shard.sum.inc_by(self.sum);
-            // Use `Release` ordering to ensure all operations above stay above.
-            shard
-                .count
-                .inc_by_with_ordering(self.count, Ordering::Release);
+        if let Some(layout) = changed_layout {
+            self.counts = vec![0; layout.upper_bounds.len()];
+            self.layout = layout;
         }
-
         self.clear()
     }
 
@@ -1074,11 +1795,30 @@ impl LocalHistogram {
         self.core.borrow_mut().observe(v);
     }
 
+    /// Add the observation `v` `count` times to the [`Histogram`].
+    ///
+    /// A `count` of 2^63 or more exceeds the observation counter and is
+    /// ignored.
+    pub fn observe_n(&self, v: f64, count: u64) {
+        self.core.borrow_mut().observe_n(v, count);
+    }
+
+    /// Add all observations of `vs` to the [`Histogram`].
+    pub fn observe_batch(&self, vs: &[f64]) {
+        self.core.borrow_mut().observe_batch(vs);
+    }
+
     /// Return a `LocalHistogramTimer` to track a duration.
     pub fn start_timer(&self) -> LocalHistogramTimer {
         LocalHistogramTimer::new(self.clone())
     }
 
+    /// Return a `LocalHistogramTimer` to track a duration measured with
+    /// `clock`.
+    pub fn start_timer_with_clock<C: Clock>(&self, clock: C) -> LocalHistogramTimer<C> {
+        LocalHistogramTimer::with_clock(self.clone(), clock)
+    }
+
     /// Return a `LocalHistogramTimer` to track a duration.
     /// It is faster but less precise.
     #[cfg(feature = "nightly")]
@@ -1317,6 +2057,14 @@ mod tests {
                 assert_eq!(got.unwrap().len(), length);
             }
         }
+
+        match check_and_adjust_buckets(vec![1.0, 3.0, 2.0]) {
+            Err(Error::BucketsNotSorted { upper_bound, next }) => {
+                assert_eq!(upper_bound, 3.0);
+                assert_eq!(next, 2.0);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
     }
 
     #[test]
@@ -1354,6 +2102,88 @@ mod tests {
                 assert_eq!(got.unwrap(), vec);
             }
         }
+
+        let exponential_range_table = vec![
+            (1.0, 1000.0, 4, true, vec![1.0, 10.0, 100.0, 1000.0]),
+            (5.0, 5.0, 1, true, vec![5.0]),
+            (0.0, 1000.0, 4, false, vec![]),
+            (10.0, 1.0, 4, false, vec![]),
+            (1.0, 1000.0, 0, false, vec![]),
+            (f64::NAN, 1000.0, 4, false, vec![]),
+            (1.0, f64::NAN, 4, false, vec![]),
+            (1.0, f64::NAN, 1, false, vec![]),
+            (1.0, f64::INFINITY, 4, false, vec![]),
+            (f64::INFINITY, f64::INFINITY, 1, false, vec![]),
+        ];
+
+        for (param1, param2, param3, is_ok, vec) in exponential_range_table {
+            let got = exponential_buckets_range(param1, param2, param3);
+            assert_eq!(got.is_ok(), is_ok);
+            if let Ok(got) = got {
+                assert_eq!(got.len(), vec.len());
+                for (got, expected) in got.iter().zip(&vec) {
+                    assert!((got - expected).abs() < 1e-9 * expected, "{:?}", got);
+                }
+            }
+        }
+
+        let log_linear_table = vec![
+            (-1, 1, 3, true, vec![0.1, 0.4, 0.7, 1.0, 4.0, 7.0, 10.0]),
+            (
+                0,
+                1,
+                9,
+                true,
+                vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0],
+            ),
+            (1, 1, 9, false, vec![]),
+            (0, 2, 0, false, vec![]),
+            (i32::MIN, 0, 1, false, vec![]),
+            (-308, 0, 1, false, vec![]),
+            (0, 309, 1, false, vec![]),
+            (0, i32::MAX, 1, false, vec![]),
+        ];
+
+        for (param1, param2, param3, is_ok, vec) in log_linear_table {
+            let got = log_linear_buckets(param1, param2, param3);
+            assert_eq!(got.is_ok(), is_ok);
+            if let Ok(got) = got {
+                assert_eq!(got, vec);
+            }
+        }
+
+        let extremes = log_linear_buckets(-307, 308, 1).unwrap();
+        assert_eq!(extremes.len(), 616);
+        assert!(extremes.iter().all(|b| b.is_normal()));
+    }
+
+    #[test]
+    fn test_validate_buckets() {
+        for preset in [
+            &DEFAULT_BUCKETS[..],
+            &HTTP_LATENCY_BUCKETS[..],
+            &BYTE_SIZE_BUCKETS[..],
+            &QUEUE_DEPTH_BUCKETS[..],
+        ] {
+            assert_eq!(validate_buckets(preset).unwrap(), vec![]);
+        }
+
+        let warnings = validate_buckets(&[0.1, 5.0, f64::INFINITY]).unwrap();
+        assert_eq!(
+            warnings,
+            vec![
+                BucketWarning::TooFewBuckets { count: 2 },
+                BucketWarning::WideBucket {
+                    lower: 0.1,
+                    upper: 5.0
+                },
+            ]
+        );
+        assert_eq!(
+            warnings[1].to_string(),
+            "bucket (0.1, 5] spans more than a factor of 10"
+        );
+        assert!(validate_buckets(&[2.0, 1.0]).is_err());
     }
 
     #[test]
@@ -1447,6 +2277,48 @@ mod tests {
         check(3, 7.0);
     }
 
+    #[test]
+    fn test_histogram_observe_n_and_batch() {
+        let opts = HistogramOpts::new("test_histogram_batch", "test histogram batch help")
+            .buckets(vec![1.0, 2.0, 3.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let local = histogram.local();
+
+        histogram.observe_n(1.5, 10_000);
+        histogram.observe_n(0.5, 0);
+        histogram.observe_batch(&[0.5, 2.5, 5.0]);
+        local.observe_n(2.5, 3);
+        local.observe_batch(&[0.5, 0.5]);
+        local.flush();
+
+        let proto = histogram.core.proto();
+        assert_eq!(proto.get_sample_count(), 10_008);
+        assert!((proto.get_sample_sum() - 15_016.5).abs() < f64::EPSILON);
+        let counts: Vec<_> = proto
+            .get_bucket()
+            .iter()
+            .map(|b| b.cumulative_count())
+            .collect();
+        assert_eq!(counts, vec![3, 10_003, 10_007]);
+
+        // Counts beyond the 63 bit observation counter are ignored instead of
+        // corrupting its shard bit.
+        histogram.observe_n(1.5, 1 << 63);
+        histogram.observe_n(1.5, u64::MAX);
+        local.observe_n(1.5, u64::MAX);
+        local.flush();
+        assert_eq!(histogram.core.proto().get_sample_count(), 10_008);
+
+        // Local counts near the limit are flushed before they overflow.
+        let histogram = Histogram::with_opts(HistogramOpts::new("test", "test help")).unwrap();
+        let local = histogram.local();
+        local.observe_n(0.5, MAX_OBSERVE_COUNT);
+        local.observe_n(0.5, 1);
+        assert_eq!(histogram.get_sample_count(), MAX_OBSERVE_COUNT);
+        assert_eq!(local.core.borrow().count, 1);
+        local.clear();
+    }
+
     #[test]
     fn test_histogram_vec_local() {
         let vec = HistogramVec::new(
@@ -1561,6 +2433,187 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_histogram_reset() {
+        let opts = HistogramOpts::new("test_reset", "test help").buckets(vec![1.0, 2.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        histogram.observe_batch(&[0.5, 1.5, 3.0]);
+        histogram.collect();
+        histogram.observe(1.5);
+
+        histogram.reset();
+        assert_eq!(histogram.get_sample_count(), 0);
+        assert_eq!(histogram.get_sample_sum(), 0.0);
+
+        histogram.observe(0.5);
+        let h = histogram.core.proto();
+        assert_eq!(h.get_sample_count(), 1);
+        assert_eq!(h.get_sample_sum(), 0.5);
+        assert_eq!(h.get_bucket()[0].cumulative_count(), 1);
+        assert_eq!(h.get_bucket()[1].cumulative_count(), 1);
+    }
+
+    #[test]
+    fn test_histogram_created_timestamp() {
+        let created = |h: proto::Histogram| {
+            let t = h.get_created_timestamp().unwrap().clone();
+            (t.seconds, t.nanos)
+        };
+
+        let opts = HistogramOpts::new("test_created", "test help").buckets(vec![1.0, 2.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let first = created(histogram.core.proto());
+        histogram.observe(0.5);
+        histogram
+            .set_buckets(vec![1.0, 3.0], BucketMigration::Remap)
+            .unwrap();
+        assert_eq!(created(histogram.core.proto()), first);
+
+        thread::sleep(Duration::from_millis(10));
+        histogram.reset();
+        let reset = created(histogram.core.proto());
+        assert!(reset > first);
+
+        thread::sleep(Duration::from_millis(10));
+        histogram
+            .set_buckets(vec![1.0], BucketMigration::Reset)
+            .unwrap();
+        assert!(created(histogram.core.proto()) > reset);
+    }
+
+    #[test]
+    fn test_timer_with_clock() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        #[derive(Debug)]
+        struct MockClock(Rc<Cell<f64>>);
+
+        impl Clock for MockClock {
+            type Instant = f64;
+
+            fn now(&self) -> f64 {
+                self.0.get()
+            }
+
+            fn elapsed(&self, start: &f64) -> f64 {
+                self.0.get() - start
+            }
+        }
+
+        let opts = HistogramOpts::new("test_clock", "test help").buckets(vec![1.0, 5.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let local = histogram.local();
+        let time = Rc::new(Cell::new(10.0));
+
+        let timer = histogram.start_timer_with_clock(MockClock(time.clone()));
+        time.set(13.0);
+        assert_eq!(timer.stop_and_record(), 3.0);
+
+        let timer = local.start_timer_with_clock(MockClock(time.clone()));
+        time.set(13.5);
+        drop(timer);
+        local.flush();
+
+        let timer = histogram.start_timer_with_clock(MillisClock);
+        thread::sleep(Duration::from_millis(10));
+        assert!(timer.stop_and_discard() >= 10.0);
+
+        let h = histogram.core.proto();
+        assert_eq!(h.get_sample_count(), 2);
+        assert_eq!(h.get_sample_sum(), 3.5);
+        assert_eq!(h.get_bucket()[0].cumulative_count(), 1);
+    }
+
+    #[test]
+    fn test_set_buckets() {
+        let cumulative_counts = |h: &proto::Histogram| -> Vec<u64> {
+            h.get_bucket()
+                .iter()
+                .map(|b| b.cumulative_count())
+                .collect()
+        };
+
+        let opts = HistogramOpts::new("test_set_buckets", "test help").buckets(vec![1.0, 2.0, 4.0]);
+        let histogram = Histogram::with_opts(opts).unwrap();
+        let local = histogram.local();
+        histogram.observe_batch(&[0.5, 1.5, 3.0, 10.0]);
+        local.observe(1.5);
+
+        histogram
+            .set_buckets(vec![2.0, 8.0], BucketMigration::Remap)
+            .unwrap();
+        histogram.observe(5.0);
+        local.flush();
+        let h = histogram.core.proto();
+        assert_eq!(h.get_sample_count(), 6);
+        assert!((h.get_sample_sum() - 21.5).abs() < f64::EPSILON);
+        assert_eq!(cumulative_counts(&h), vec![3, 5]);
+
+        histogram
+            .set_buckets(vec![1.0], BucketMigration::Reset)
+            .unwrap();
+        histogram.observe(0.5);
+        let h = histogram.core.proto();
+        assert_eq!(h.get_sample_count(), 1);
+        assert!((h.get_sample_sum() - 0.5).abs() < f64::EPSILON);
+        assert_eq!(cumulative_counts(&h), vec![1]);
+        assert_eq!(histogram.get_sample_count(), 1);
+
+        assert!(histogram
+            .set_buckets(vec![2.0, 1.0], BucketMigration::Reset)
+            .is_err());
+
+        let vec = HistogramVec::new(HistogramOpts::new("test_vec", "test help"), &["l"]).unwrap();
+        vec.with_label_values(&["a"]).observe(0.5);
+        vec.set_buckets(vec![1.0, 2.0], BucketMigration::Remap)
+            .unwrap();
+        vec.with_label_values(&["b"]).observe(1.5);
+        for (l, counts) in [("a", vec![1, 1]), ("b", vec![0, 1])] {
+            let h = vec.with_label_values(&[l]).core.proto();
+            assert_eq!(cumulative_counts(&h), counts);
+        }
+    }
+
+    #[test]
+    fn atomic_set_buckets_during_observe() {
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("test_histogram", "test help").buckets(vec![1.0]),
+        )
+        .unwrap();
+
+        let done = Arc::new(std::sync::atomic::AtomicBool::default());
+        let observing_thread = {
+            let histogram = histogram.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    histogram.observe(1.0);
+                }
+            })
+        };
+
+        // All observations are in the bucket of 1.0 in both layouts.
+        let layouts = [vec![1.0], vec![0.5, 1.0, 2.0]];
+        for i in 0..10_000 {
+            histogram
+                .set_buckets(layouts[i % 2].clone(), BucketMigration::Remap)
+                .unwrap();
+            let h = histogram.core.proto();
+            let cumulative_count = h
+                .get_bucket()
+                .iter()
+                .find(|b| b.upper_bound() == 1.0)
+                .unwrap()
+                .cumulative_count();
+            assert_eq!(h.get_sample_count(), cumulative_count);
+            assert_eq!(h.get_sample_count(), h.get_sample_sum() as u64);
+        }
+
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        observing_thread.join().unwrap();
+    }
+
     #[test]
     fn test_error_on_inconsistent_label_cardinality() {
         let hist = Histogram::with_opts(
@@ -1579,4 +2632,110 @@ mod tests {
             panic!("Expected InconsistentCardinality error.")
         }
     }
+
+    #[test]
+    fn test_bucket_lookup() {
+        let irregular: Vec<f64> = (0..50).map(|i| (i * i) as f64 + 0.5).collect();
+        let cases = vec![
+            (Vec::from(DEFAULT_BUCKETS as &[f64]), "scan"),
+            (linear_buckets(-5.0, 0.1, 200).unwrap(), "linear"),
+            (linear_buckets(0.0, 3.0, 20).unwrap(), "linear"),
+            (exponential_buckets(0.001, 1.1, 200).unwrap(), "exponential"),
+            (exponential_buckets(1.0, 2.0, 50).unwrap(), "exponential"),
+            (irregular, "binary"),
+        ];
+
+        for (upper_bounds, expected) in cases {
+            let lookup = BucketLookup::new(&upper_bounds);
+            let kind = match lookup {
+                BucketLookup::LinearScan => "scan",
+                BucketLookup::BinarySearch => "binary",
+                BucketLookup::Linear { .. } => "linear",
+                BucketLookup::Exponential { .. } => "exponential",
+            };
+            assert_eq!(kind, expected, "{:?}", upper_bounds);
+
+            let mut values = vec![
+                f64::NEG_INFINITY,
+                -1e10,
+                0.0,
+                f64::MIN_POSITIVE,
+                1e300,
+                f64::INFINITY,
+                f64::NAN,
+            ];
+            for &b in &upper_bounds {
+                values.extend_from_slice(&[b, b - b.abs() * 1e-12, b + b.abs() * 1e-12]);
+                values.extend_from_slice(&[b - 0.05, b + 0.05, b * 0.99, b * 1.01]);
+            }
+            for v in values {
+                let expected = upper_bounds.iter().position(|&b| v <= b);
+                assert_eq!(lookup.find(&upper_bounds, v), expected, "{} in {}", v, kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_histogram_quantile() {
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("test_histogram", "test help").buckets(vec![1.0, 2.0, 4.0]),
+        )
+        .unwrap();
+        assert!(histogram.quantile(0.5).is_nan());
+
+        for v in &[0.5, 1.5, 1.5, 3.0, 10.0] {
+            histogram.observe(*v);
+        }
+        assert_eq!(histogram.quantile(0.0), 0.0);
+        assert_eq!(histogram.quantile(0.2), 1.0);
+        assert_eq!(histogram.quantile(0.4), 1.5);
+        assert_eq!(histogram.quantile(0.7), 3.0);
+        // The +Inf bucket yields the highest finite upper bound.
+        assert_eq!(histogram.quantile(0.99), 4.0);
+        assert_eq!(histogram.quantile(-0.1), f64::NEG_INFINITY);
+        assert_eq!(histogram.quantile(1.1), f64::INFINITY);
+        assert!(histogram.quantile(f64::NAN).is_nan());
+
+        let mut h = proto::Histogram::default();
+        h.set_sample_count(4);
+        let buckets = [(-1.0, 2), (0.0, 3), (f64::INFINITY, 4)]
+            .iter()
+            .map(|&(upper_bound, count)| {
+                let mut b = proto::Bucket::default();
+                b.set_upper_bound(upper_bound);
+                b.set_cumulative_count(count);
+                b
+            })
+            .collect();
+        h.set_bucket(buckets);
+        assert_eq!(histogram_quantile(0.25, &h), -1.0);
+        assert_eq!(histogram_quantile(0.75, &h), 0.0);
+        assert_eq!(histogram_quantile(1.0, &h), 0.0);
+
+        h.set_bucket(vec![]);
+        assert!(histogram_quantile(0.5, &h).is_nan());
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde() {
+        let opts: HistogramOpts = serde_json::from_str(
+            r#"{"name": "test_histogram", "help": "test help", "const_labels": {"a": "1"}}"#,
+        )
+        .unwrap();
+        assert_eq!(opts.buckets, DEFAULT_BUCKETS);
+        let opts: HistogramOpts = serde_json::from_str(
+            r#"{"namespace": "ns", "name": "test_histogram", "help": "test help", "buckets": [1.0]}"#,
+        )
+        .unwrap();
+        assert_eq!(opts.fq_name(), "ns_test_histogram");
+        assert_eq!(opts.buckets, vec![1.0]);
+
+        let histogram = Histogram::with_opts(opts).unwrap();
+        histogram.observe(0.5);
+        let mfs = histogram.collect();
+        let json = serde_json::to_string(&mfs).unwrap();
+        let decoded: Vec<proto::MetricFamily> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, mfs);
+    }
 }
@@ -104,13 +104,23 @@ additional functionality.
 
 # Features
 
-This library supports four features:
+This library supports twelve features:
 
 * `gen`: To generate protobuf client with the latest protobuf version instead of
   using the pre-generated client.
+* `graphite`: Enable the [`GraphiteBridge`] to Carbon.
+* `json`: Enable the [`JsonEncoder`] and [`JsonDecoder`].
+* `serde`: Implement `Serialize` and `Deserialize` for the [`proto`] model,
+  [`Opts`] and [`HistogramOpts`].
 * `nightly`: Enable nightly only features.
 * `process`: For collecting process info.
 * `push`: Enable push support.
+* `push-async`: Enable the async [`PushClient`], implies `push`.
+* `remote-write`: Enable the [`RemoteWriteClient`] for the Prometheus remote
+  write protocol.
+* `otlp`: Enable the conversion to OpenTelemetry metrics in [`otlp`].
+* `otlp-http`: Enable the OTLP/HTTP exporter, implies `otlp`.
+* `statsd`: Enable the [`StatsdExporter`] for StatsD and DogStatsD.
 
 */
 
@@ -135,30 +145,54 @@ pub mod proto;
 
 #[cfg(feature = "protobuf")]
 mod proto_ext;
+#[cfg(all(feature = "protobuf", feature = "serde"))]
+mod proto_serde;
 
 #[macro_use]
 mod macros;
 mod atomic64;
 mod auto_flush;
+#[cfg(any(feature = "push", feature = "statsd", feature = "graphite"))]
+mod background;
 mod counter;
 mod desc;
 mod encoder;
 mod errors;
 mod gauge;
+#[cfg(feature = "graphite")]
+mod graphite;
 mod histogram;
 mod metrics;
 mod nohash;
+#[cfg(any(feature = "statsd", feature = "graphite"))]
+mod periodic;
 mod pulling_gauge;
 #[cfg(feature = "push")]
 mod push;
+#[cfg(feature = "push-async")]
+mod push_async;
+#[cfg(feature = "push")]
+mod push_scheduler;
 mod registry;
+#[cfg(feature = "remote-write")]
+mod remote_write;
+#[cfg(feature = "statsd")]
+mod statsd;
 mod value;
 mod vec;
+mod windowed;
+#[cfg(any(feature = "remote-write", feature = "otlp"))]
+mod wire;
 
 // Public for generated code.
 #[doc(hidden)]
 pub mod timer;
 
+pub mod snapshot;
+
+#[cfg(feature = "otlp")]
+pub mod otlp;
+
 #[cfg(all(feature = "process", target_os = "linux"))]
 pub mod process_collector;
 
@@ -171,11 +205,15 @@ pub mod local {
     pub use super::counter::{
         CounterWithValueType, LocalCounter, LocalCounterVec, LocalIntCounter, LocalIntCounterVec,
     };
+    pub use super::gauge::{
+        GaugeWithValueType, LocalGauge, LocalGaugeVec, LocalIntGauge, LocalIntGaugeVec,
+    };
     pub use super::histogram::{LocalHistogram, LocalHistogramTimer, LocalHistogramVec};
     pub use super::metrics::{LocalMetric, MayFlush};
 
     pub use super::auto_flush::{
-        AFLocalCounter, AFLocalHistogram, CounterDelegator, HistogramDelegator,
+        AFLocalCounter, AFLocalGauge, AFLocalHistogram, CounterDelegator, GaugeDelegator,
+        HistogramDelegator,
     };
 }
 
@@ -191,12 +229,16 @@ pub mod core {
         GenericCounter, GenericCounterVec, GenericLocalCounter, GenericLocalCounterVec,
     };
     pub use super::desc::{Desc, Describer};
-    pub use super::gauge::{GenericGauge, GenericGaugeVec};
+    pub use super::gauge::{
+        GenericGauge, GenericGaugeVec, GenericLocalGauge, GenericLocalGaugeVec,
+    };
     pub use super::metrics::{Collector, Metric, Opts};
     pub use super::vec::{MetricVec, MetricVecBuilder};
 }
 
-pub use self::counter::{Counter, CounterVec, IntCounter, IntCounterVec};
+pub use self::counter::{
+    Counter, CounterVec, IntCounter, IntCounterVec, StripedCounter, StripedIntCounter,
+};
 pub use self::encoder::Encoder;
 #[cfg(feature = "protobuf")]
 pub use self::encoder::ProtobufEncoder;
@@ -204,17 +246,46 @@ pub use self::encoder::TextEncoder;
 #[cfg(feature = "protobuf")]
 pub use self::encoder::PROTOBUF_FORMAT;
 pub use self::encoder::TEXT_FORMAT;
+#[cfg(feature = "json")]
+pub use self::encoder::{JsonDecoder, JsonEncoder, JSON_FORMAT};
+pub use self::encoder::{OpenMetricsEncoder, OPENMETRICS_FORMAT};
 pub use self::errors::{Error, Result};
-pub use self::gauge::{Gauge, GaugeVec, IntGauge, IntGaugeVec};
-pub use self::histogram::DEFAULT_BUCKETS;
-pub use self::histogram::{exponential_buckets, linear_buckets};
-pub use self::histogram::{Histogram, HistogramOpts, HistogramTimer, HistogramVec};
-pub use self::metrics::Opts;
+pub use self::gauge::{
+    Gauge, GaugeGuard, GaugeVec, IntGauge, IntGaugeVec, StripedGauge, StripedIntGauge,
+};
+#[cfg(feature = "graphite")]
+pub use self::graphite::{GraphiteBridge, GraphiteOpts, LabelMapping};
+pub use self::histogram::{
+    exponential_buckets, exponential_buckets_range, histogram_quantile, linear_buckets,
+    log_linear_buckets, validate_buckets, BucketWarning,
+};
+pub use self::histogram::{
+    BucketMigration, Clock, Histogram, HistogramOpts, HistogramTimer, HistogramVec, MillisClock,
+    MonotonicClock,
+};
+pub use self::histogram::{
+    BYTE_SIZE_BUCKETS, DEFAULT_BUCKETS, HTTP_LATENCY_BUCKETS, QUEUE_DEPTH_BUCKETS,
+};
+pub use self::metrics::{MetricWithTimestamp, Opts};
+#[cfg(any(feature = "statsd", feature = "graphite"))]
+pub use self::periodic::PeriodicTask;
 pub use self::pulling_gauge::PullingGauge;
 #[cfg(feature = "push")]
 pub use self::push::{
     hostname_grouping_key, push_add_collector, push_add_metrics, push_collector, push_metrics,
-    BasicAuthentication,
+    BasicAuthentication, Pusher,
 };
-pub use self::registry::Registry;
+#[cfg(feature = "push-async")]
+pub use self::push_async::{PushClient, PushClientBuilder};
+#[cfg(feature = "push")]
+pub use self::push_scheduler::{PushScheduler, PushSchedulerOpts};
 pub use self::registry::{default_registry, gather, register, unregister};
+pub use self::registry::{Registry, RegistryOpts};
+#[cfg(feature = "remote-write")]
+pub use self::remote_write::{
+    Label, RemoteWriteClient, RemoteWriteClientBuilder, Sample, TimeSeries, WriteRequest,
+    REMOTE_WRITE_VERSION,
+};
+#[cfg(feature = "statsd")]
+pub use self::statsd::{StatsdExporter, StatsdFormat, StatsdOpts};
+pub use self::windowed::{WindowedCounter, WindowedHistogram};
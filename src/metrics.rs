@@ -3,6 +3,7 @@
 
 use std::cmp::{Eq, Ord, Ordering, PartialOrd};
 use std::collections::HashMap;
+use std::time::SystemTime;
 
 use crate::desc::{Desc, Describer};
 use crate::errors::Result;
@@ -27,6 +28,54 @@ pub trait Metric: Sync + Send + Clone {
     fn metric(&self) -> proto::Metric;
 }
 
+/// A [`Metric`] or [`Collector`] exposed with a fixed timestamp instead of the
+/// time of the scrape, e.g. for a const metric built from data that was
+/// collected earlier.
+#[derive(Clone, Debug)]
+pub struct MetricWithTimestamp<M> {
+    inner: M,
+    timestamp_ms: i64,
+}
+
+impl<M> MetricWithTimestamp<M> {
+    /// `new` wraps `inner` to be exposed as observed at `time`.
+    pub fn new(inner: M, time: SystemTime) -> Self {
+        MetricWithTimestamp {
+            inner,
+            timestamp_ms: timer::unix_millis(time),
+        }
+    }
+
+    /// Return the wrapped metric.
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<M: Metric> Metric for MetricWithTimestamp<M> {
+    fn metric(&self) -> proto::Metric {
+        let mut m = self.inner.metric();
+        m.set_timestamp_ms(self.timestamp_ms);
+        m
+    }
+}
+
+impl<M: Collector> Collector for MetricWithTimestamp<M> {
+    fn desc(&self) -> Vec<&Desc> {
+        self.inner.desc()
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        let mut mfs = self.inner.collect();
+        for mf in &mut mfs {
+            for m in mf.mut_metric() {
+                m.set_timestamp_ms(self.timestamp_ms);
+            }
+        }
+        mfs
+    }
+}
+
 /// An interface models a Metric only usable in single thread environment.
 pub trait LocalMetric {
     /// Flush the local metrics to the global one.
@@ -54,18 +103,21 @@ pub trait MayFlush: LocalMetric {
 
 /// A struct that bundles the options for creating most [`Metric`] types.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Opts {
     /// namespace, subsystem, and name are components of the fully-qualified
     /// name of the [`Metric`] (created by joining these components with
     /// "_"). Only Name is mandatory, the others merely help structuring the
     /// name. Note that the fully-qualified name of the metric must be a
     /// valid Prometheus metric name.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub namespace: String,
     /// namespace, subsystem, and name are components of the fully-qualified
     /// name of the [`Metric`] (created by joining these components with
     /// "_"). Only Name is mandatory, the others merely help structuring the
     /// name. Note that the fully-qualified name of the metric must be a
     /// valid Prometheus metric name.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub subsystem: String,
     /// namespace, subsystem, and name are components of the fully-qualified
     /// name of the [`Metric`] (created by joining these components with
@@ -98,6 +150,7 @@ pub struct Opts {
     /// If the value of a label never changes (not even between binaries),
     /// that label most likely should not be a label at all (but part of the
     /// metric name).
+    #[cfg_attr(feature = "serde", serde(default))]
     pub const_labels: HashMap<String, String>,
 
     /// variable_labels contains names of labels for which the metric maintains
@@ -106,6 +159,7 @@ pub struct Opts {
     ///
     /// Note that variable_labels is used in `MetricVec`. To create a single
     /// metric must leave it empty.
+    #[cfg_attr(feature = "serde", serde(default))]
     pub variable_labels: Vec<String>,
 }
 
@@ -259,6 +313,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_metric_with_timestamp() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use crate::gauge::Gauge;
+
+        let gauge = Gauge::new("test_gauge", "test help").unwrap();
+        gauge.set(3.0);
+        let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let stamped = MetricWithTimestamp::new(gauge, time);
+
+        assert_eq!(stamped.metric().timestamp_ms(), 1_700_000_000_123);
+        let mfs = stamped.collect();
+        assert_eq!(mfs[0].get_metric()[0].timestamp_ms(), 1_700_000_000_123);
+        assert_eq!(stamped.desc()[0].fq_name, "test_gauge");
+        assert_eq!(stamped.inner().get(), 3.0);
+    }
+
     #[test]
     fn test_different_generic_types() {
         Opts::new(format!("{}_{}", "string", "label"), "&str_label");
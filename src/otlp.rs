@@ -0,0 +1,649 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! Convert metric families to OpenTelemetry (OTLP) metrics.
+//!
+//! [`ExportMetricsServiceRequest::from_metric_families`] maps counters to
+//! monotonic sums with cumulative temporality, gauges to gauges, and
+//! histograms and summaries to their OTLP counterparts. With the `otlp-http`
+//! feature, the [`OtlpExporter`] sends them to an OTLP/HTTP endpoint, e.g. of
+//! an OpenTelemetry collector.
+
+use std::convert::TryFrom;
+#[cfg(feature = "otlp-http")]
+use std::time::Duration;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "otlp-http")]
+use reqwest::blocking::Client;
+#[cfg(feature = "otlp-http")]
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+
+#[cfg(feature = "otlp-http")]
+use crate::errors::{Error, Result};
+use crate::proto::{self, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+use crate::wire::{write_bytes, write_double, write_fixed64, write_uint64};
+
+/// The `AGGREGATION_TEMPORALITY_CUMULATIVE` value of OTLP.
+const CUMULATIVE: u64 = 2;
+
+/// A struct that bundles the options of the conversion to OTLP.
+#[derive(Clone, Debug)]
+pub struct OtlpOpts {
+    /// The attributes of the resource, e.g. `service.name`.
+    pub resource_attributes: Vec<KeyValue>,
+    /// The name of the instrumentation scope.
+    pub scope_name: String,
+    /// The version of the instrumentation scope.
+    pub scope_version: String,
+    /// The start of the cumulative sums, histograms and summaries.
+    pub start_time: SystemTime,
+}
+
+impl OtlpOpts {
+    /// `new` creates the options with the `prometheus` scope, and the current
+    /// time as the start time. Create them once when the process starts.
+    pub fn new() -> OtlpOpts {
+        OtlpOpts {
+            resource_attributes: Vec::new(),
+            scope_name: "prometheus".to_owned(),
+            scope_version: env!("CARGO_PKG_VERSION").to_owned(),
+            start_time: SystemTime::now(),
+        }
+    }
+
+    /// `resource_attribute` adds a resource attribute.
+    pub fn resource_attribute<S1: Into<String>, S2: Into<String>>(
+        mut self,
+        key: S1,
+        value: S2,
+    ) -> Self {
+        self.resource_attributes.push(KeyValue {
+            key: key.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// `scope` sets the name and version of the instrumentation scope.
+    pub fn scope<S1: Into<String>, S2: Into<String>>(mut self, name: S1, version: S2) -> Self {
+        self.scope_name = name.into();
+        self.scope_version = version.into();
+        self
+    }
+
+    /// `start_time` sets the start time.
+    pub fn start_time(mut self, start_time: SystemTime) -> Self {
+        self.start_time = start_time;
+        self
+    }
+}
+
+/// An OTLP export request with the metrics of one resource and scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExportMetricsServiceRequest {
+    /// The attributes of the resource.
+    pub resource_attributes: Vec<KeyValue>,
+    /// The name of the instrumentation scope.
+    pub scope_name: String,
+    /// The version of the instrumentation scope.
+    pub scope_version: String,
+    /// The metrics.
+    pub metrics: Vec<Metric>,
+}
+
+/// A string valued attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyValue {
+    /// The attribute key.
+    pub key: String,
+    /// The attribute value.
+    pub value: String,
+}
+
+/// An OTLP metric.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    /// The metric name.
+    pub name: String,
+    /// The metric description, i.e. the help string.
+    pub description: String,
+    /// The data points of the metric.
+    pub data: MetricData,
+}
+
+/// The data points of a [`Metric`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum MetricData {
+    /// A gauge.
+    Gauge(Vec<NumberDataPoint>),
+    /// A monotonic sum with cumulative temporality.
+    Sum(Vec<NumberDataPoint>),
+    /// A histogram with cumulative temporality.
+    Histogram(Vec<HistogramDataPoint>),
+    /// A summary.
+    Summary(Vec<SummaryDataPoint>),
+}
+
+/// A data point of a gauge or a sum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NumberDataPoint {
+    /// The attributes, i.e. the labels.
+    pub attributes: Vec<KeyValue>,
+    /// The start time in nanoseconds since the Unix epoch.
+    pub start_time_unix_nano: u64,
+    /// The time in nanoseconds since the Unix epoch.
+    pub time_unix_nano: u64,
+    /// The value.
+    pub value: f64,
+}
+
+/// A data point of a histogram.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistogramDataPoint {
+    /// The attributes, i.e. the labels.
+    pub attributes: Vec<KeyValue>,
+    /// The start time in nanoseconds since the Unix epoch.
+    pub start_time_unix_nano: u64,
+    /// The time in nanoseconds since the Unix epoch.
+    pub time_unix_nano: u64,
+    /// The number of observations.
+    pub count: u64,
+    /// The sum of observations.
+    pub sum: f64,
+    /// The non-cumulative count of each bucket, one more than
+    /// `explicit_bounds` for the bucket above the highest bound.
+    pub bucket_counts: Vec<u64>,
+    /// The upper bounds of the buckets, excluding `+Inf`.
+    pub explicit_bounds: Vec<f64>,
+}
+
+/// A data point of a summary.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SummaryDataPoint {
+    /// The attributes, i.e. the labels.
+    pub attributes: Vec<KeyValue>,
+    /// The start time in nanoseconds since the Unix epoch.
+    pub start_time_unix_nano: u64,
+    /// The time in nanoseconds since the Unix epoch.
+    pub time_unix_nano: u64,
+    /// The number of observations.
+    pub count: u64,
+    /// The sum of observations.
+    pub sum: f64,
+    /// The quantiles, as `(quantile, value)` pairs.
+    pub quantile_values: Vec<(f64, f64)>,
+}
+
+impl ExportMetricsServiceRequest {
+    /// `from_metric_families` converts the metric families, stamping metrics
+    /// without their own timestamp with `time`. Untyped metrics are skipped.
+    pub fn from_metric_families(
+        mfs: &[proto::MetricFamily],
+        opts: &OtlpOpts,
+        time: SystemTime,
+    ) -> ExportMetricsServiceRequest {
+        let start = unix_nanos(opts.start_time);
+        let time = unix_nanos(time);
+
+        let metrics = mfs
+            .iter()
+            .filter_map(|mf| {
+                let ms = mf.get_metric();
+                let data = match mf.get_field_type() {
+                    MetricType::COUNTER => MetricData::Sum(
+                        ms.iter()
+                            .map(|m| number_point(m, start, time, m.get_counter().get_value()))
+                            .collect(),
+                    ),
+                    MetricType::GAUGE => MetricData::Gauge(
+                        ms.iter()
+                            .map(|m| number_point(m, start, time, m.get_gauge().get_value()))
+                            .collect(),
+                    ),
+                    MetricType::HISTOGRAM => MetricData::Histogram(
+                        ms.iter().map(|m| histogram_point(m, start, time)).collect(),
+                    ),
+                    MetricType::SUMMARY => MetricData::Summary(
+                        ms.iter().map(|m| summary_point(m, start, time)).collect(),
+                    ),
+                    MetricType::UNTYPED => return None,
+                };
+                Some(Metric {
+                    name: mf.name().to_owned(),
+                    description: mf.help().to_owned(),
+                    data,
+                })
+            })
+            .collect();
+
+        ExportMetricsServiceRequest {
+            resource_attributes: opts.resource_attributes.clone(),
+            scope_name: opts.scope_name.clone(),
+            scope_version: opts.scope_version.clone(),
+            metrics,
+        }
+    }
+
+    /// `encode` encodes the request in the OTLP protobuf format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut resource = Vec::new();
+        for kv in &self.resource_attributes {
+            write_bytes(&mut resource, 1, &encode_key_value(kv));
+        }
+
+        let mut scope = Vec::new();
+        write_bytes(&mut scope, 1, self.scope_name.as_bytes());
+        write_bytes(&mut scope, 2, self.scope_version.as_bytes());
+
+        let mut scope_metrics = Vec::new();
+        write_bytes(&mut scope_metrics, 1, &scope);
+        for m in &self.metrics {
+            write_bytes(&mut scope_metrics, 2, &encode_metric(m));
+        }
+
+        let mut resource_metrics = Vec::new();
+        write_bytes(&mut resource_metrics, 1, &resource);
+        write_bytes(&mut resource_metrics, 2, &scope_metrics);
+
+        let mut buf = Vec::new();
+        write_bytes(&mut buf, 1, &resource_metrics);
+        buf
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+fn attributes(m: &proto::Metric) -> Vec<KeyValue> {
+    m.get_label()
+        .iter()
+        .map(|lp| KeyValue {
+            key: lp.name().to_owned(),
+            value: lp.value().to_owned(),
+        })
+        .collect()
+}
+
+/// `point_time` returns the explicit timestamp of `m` in nanoseconds, or
+/// `time` if it has none or it can't be represented.
+fn point_time(m: &proto::Metric, time: u64) -> u64 {
+    match m.timestamp_ms() {
+        0 => time,
+        t => u64::try_from(t)
+            .ok()
+            .and_then(|t| t.checked_mul(1_000_000))
+            .unwrap_or(time),
+    }
+}
+
+fn number_point(m: &proto::Metric, start: u64, time: u64, value: f64) -> NumberDataPoint {
+    NumberDataPoint {
+        attributes: attributes(m),
+        start_time_unix_nano: start,
+        time_unix_nano: point_time(m, time),
+        value,
+    }
+}
+
+fn histogram_point(m: &proto::Metric, start: u64, time: u64) -> HistogramDataPoint {
+    let h = m.get_histogram();
+    let mut bucket_counts = Vec::new();
+    let mut explicit_bounds = Vec::new();
+    let mut prev = 0;
+    for b in h.get_bucket() {
+        if b.upper_bound() == f64::INFINITY {
+            break;
+        }
+        explicit_bounds.push(b.upper_bound());
+        bucket_counts.push(b.cumulative_count().saturating_sub(prev));
+        prev = b.cumulative_count();
+    }
+    bucket_counts.push(h.get_sample_count().saturating_sub(prev));
+
+    HistogramDataPoint {
+        attributes: attributes(m),
+        start_time_unix_nano: start,
+        time_unix_nano: point_time(m, time),
+        count: h.get_sample_count(),
+        sum: h.get_sample_sum(),
+        bucket_counts,
+        explicit_bounds,
+    }
+}
+
+fn summary_point(m: &proto::Metric, start: u64, time: u64) -> SummaryDataPoint {
+    let s = m.get_summary();
+    SummaryDataPoint {
+        attributes: attributes(m),
+        start_time_unix_nano: start,
+        time_unix_nano: point_time(m, time),
+        count: s.sample_count(),
+        sum: s.sample_sum(),
+        quantile_values: s
+            .get_quantile()
+            .iter()
+            .map(|q| (q.quantile(), q.value()))
+            .collect(),
+    }
+}
+
+fn encode_key_value(kv: &KeyValue) -> Vec<u8> {
+    let mut any_value = Vec::new();
+    write_bytes(&mut any_value, 1, kv.value.as_bytes());
+    let mut buf = Vec::new();
+    write_bytes(&mut buf, 1, kv.key.as_bytes());
+    write_bytes(&mut buf, 2, &any_value);
+    buf
+}
+
+fn encode_metric(m: &Metric) -> Vec<u8> {
+    let mut data = Vec::new();
+    let field = match &m.data {
+        MetricData::Gauge(points) => {
+            for p in points {
+                write_bytes(&mut data, 1, &encode_number_point(p));
+            }
+            5
+        }
+        MetricData::Sum(points) => {
+            for p in points {
+                write_bytes(&mut data, 1, &encode_number_point(p));
+            }
+            write_uint64(&mut data, 2, CUMULATIVE);
+            write_uint64(&mut data, 3, 1);
+            7
+        }
+        MetricData::Histogram(points) => {
+            for p in points {
+                write_bytes(&mut data, 1, &encode_histogram_point(p));
+            }
+            write_uint64(&mut data, 2, CUMULATIVE);
+            9
+        }
+        MetricData::Summary(points) => {
+            for p in points {
+                write_bytes(&mut data, 1, &encode_summary_point(p));
+            }
+            11
+        }
+    };
+
+    let mut buf = Vec::new();
+    write_bytes(&mut buf, 1, m.name.as_bytes());
+    write_bytes(&mut buf, 2, m.description.as_bytes());
+    write_bytes(&mut buf, field, &data);
+    buf
+}
+
+fn encode_number_point(p: &NumberDataPoint) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_fixed64(&mut buf, 2, p.start_time_unix_nano);
+    write_fixed64(&mut buf, 3, p.time_unix_nano);
+    write_double(&mut buf, 4, p.value);
+    for kv in &p.attributes {
+        write_bytes(&mut buf, 7, &encode_key_value(kv));
+    }
+    buf
+}
+
+fn encode_histogram_point(p: &HistogramDataPoint) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_fixed64(&mut buf, 2, p.start_time_unix_nano);
+    write_fixed64(&mut buf, 3, p.time_unix_nano);
+    write_fixed64(&mut buf, 4, p.count);
+    write_double(&mut buf, 5, p.sum);
+    let packed: Vec<u8> = p
+        .bucket_counts
+        .iter()
+        .flat_map(|c| c.to_le_bytes())
+        .collect();
+    write_bytes(&mut buf, 6, &packed);
+    let packed: Vec<u8> = p
+        .explicit_bounds
+        .iter()
+        .flat_map(|b| b.to_le_bytes())
+        .collect();
+    write_bytes(&mut buf, 7, &packed);
+    for kv in &p.attributes {
+        write_bytes(&mut buf, 9, &encode_key_value(kv));
+    }
+    buf
+}
+
+fn encode_summary_point(p: &SummaryDataPoint) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_fixed64(&mut buf, 2, p.start_time_unix_nano);
+    write_fixed64(&mut buf, 3, p.time_unix_nano);
+    write_fixed64(&mut buf, 4, p.count);
+    write_double(&mut buf, 5, p.sum);
+    for (quantile, value) in &p.quantile_values {
+        let mut q = Vec::new();
+        write_double(&mut q, 1, *quantile);
+        write_double(&mut q, 2, *value);
+        write_bytes(&mut buf, 6, &q);
+    }
+    for kv in &p.attributes {
+        write_bytes(&mut buf, 7, &encode_key_value(kv));
+    }
+    buf
+}
+
+/// An exporter sending metrics to an OTLP/HTTP endpoint in the protobuf
+/// encoding.
+///
+/// ```no_run
+/// # fn run() -> prometheus::Result<()> {
+/// use prometheus::otlp::{OtlpExporter, OtlpOpts};
+///
+/// let opts = OtlpOpts::new().resource_attribute("service.name", "worker");
+/// let exporter = OtlpExporter::builder("http://127.0.0.1:4318/v1/metrics")
+///     .opts(opts)
+///     .build()?;
+/// exporter.export(&prometheus::gather())?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "otlp-http")]
+#[derive(Debug)]
+pub struct OtlpExporter {
+    client: Client,
+    url: String,
+    opts: OtlpOpts,
+}
+
+#[cfg(feature = "otlp-http")]
+impl OtlpExporter {
+    /// `builder` creates an [`OtlpExporterBuilder`] for the endpoint at `url`,
+    /// including the `/v1/metrics` path.
+    pub fn builder<S: Into<String>>(url: S) -> OtlpExporterBuilder {
+        OtlpExporterBuilder {
+            url: url.into(),
+            opts: OtlpOpts::new(),
+            timeout: Duration::from_secs(10),
+            headers: Vec::new(),
+        }
+    }
+
+    /// `export` converts the metric families, stamping metrics without a
+    /// timestamp with the current time, and sends them.
+    pub fn export(&self, mfs: &[proto::MetricFamily]) -> Result<()> {
+        let request =
+            ExportMetricsServiceRequest::from_metric_families(mfs, &self.opts, SystemTime::now());
+        let response = self
+            .client
+            .post(self.url.as_str())
+            .header(CONTENT_TYPE, "application/x-protobuf")
+            .body(request.encode())
+            .send()?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::PushFailed {
+                status: response.status().as_u16(),
+                url: self.url.clone(),
+            })
+        }
+    }
+}
+
+/// A builder for [`OtlpExporter`].
+#[cfg(feature = "otlp-http")]
+#[derive(Debug)]
+pub struct OtlpExporterBuilder {
+    url: String,
+    opts: OtlpOpts,
+    timeout: Duration,
+    headers: Vec<(String, String)>,
+}
+
+#[cfg(feature = "otlp-http")]
+impl OtlpExporterBuilder {
+    /// `opts` sets the options of the conversion.
+    pub fn opts(mut self, opts: OtlpOpts) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// `timeout` sets the timeout of each request, 10 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `header` adds a header sent with every request.
+    pub fn header<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// `build` creates the [`OtlpExporter`]. It fails if a header is invalid.
+    pub fn build(self) -> Result<OtlpExporter> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes());
+            let header_value = HeaderValue::from_str(&value);
+            match (header_name, header_value) {
+                (Ok(n), Ok(v)) => {
+                    headers.append(n, v);
+                }
+                _ => return Err(Error::InvalidHeader { name }),
+            }
+        }
+
+        let client = Client::builder()
+            .timeout(self.timeout)
+            .default_headers(headers)
+            .build()?;
+
+        Ok(OtlpExporter {
+            client,
+            url: self.url,
+            opts: self.opts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{Counter, Histogram, HistogramOpts, Opts, Registry};
+
+    #[test]
+    fn test_from_metric_families() {
+        let registry = Registry::new();
+        let counter =
+            Counter::with_opts(Opts::new("test_counter", "help").const_label("a", "1")).unwrap();
+        counter.inc_by(2.0);
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("test_histogram", "help").buckets(vec![1.0, 2.0]),
+        )
+        .unwrap();
+        for v in [0.5, 0.7, 1.5, 3.0] {
+            histogram.observe(v);
+        }
+        registry.register(Box::new(counter)).unwrap();
+        registry.register(Box::new(histogram)).unwrap();
+
+        let start = UNIX_EPOCH + Duration::from_secs(1);
+        let opts = OtlpOpts::new()
+            .resource_attribute("service.name", "test")
+            .start_time(start);
+        let time = UNIX_EPOCH + Duration::from_secs(2);
+        let req =
+            ExportMetricsServiceRequest::from_metric_families(&registry.gather(), &opts, time);
+
+        assert_eq!(req.metrics.len(), 2);
+        assert_eq!(req.metrics[0].name, "test_counter");
+        assert_eq!(
+            req.metrics[0].data,
+            MetricData::Sum(vec![NumberDataPoint {
+                attributes: vec![KeyValue {
+                    key: "a".to_owned(),
+                    value: "1".to_owned(),
+                }],
+                start_time_unix_nano: 1_000_000_000,
+                time_unix_nano: 2_000_000_000,
+                value: 2.0,
+            }])
+        );
+        match &req.metrics[1].data {
+            MetricData::Histogram(points) => {
+                assert_eq!(points[0].count, 4);
+                assert_eq!(points[0].sum, 5.7);
+                assert_eq!(points[0].explicit_bounds, vec![1.0, 2.0]);
+                assert_eq!(points[0].bucket_counts, vec![2, 1, 1]);
+            }
+            other => panic!("unexpected data {:?}", other),
+        }
+        assert!(!req.encode().is_empty());
+    }
+
+    #[test]
+    fn test_point_time() {
+        let mut m = proto::Metric::default();
+        assert_eq!(point_time(&m, 5), 5);
+        m.set_timestamp_ms(1_500);
+        assert_eq!(point_time(&m, 5), 1_500_000_000);
+        m.set_timestamp_ms(-1_500);
+        assert_eq!(point_time(&m, 5), 5);
+        m.set_timestamp_ms(i64::MAX);
+        assert_eq!(point_time(&m, 5), 5);
+    }
+
+    #[test]
+    fn test_encode() {
+        let req = ExportMetricsServiceRequest {
+            resource_attributes: vec![],
+            scope_name: "s".to_owned(),
+            scope_version: "v".to_owned(),
+            metrics: vec![Metric {
+                name: "m".to_owned(),
+                description: "h".to_owned(),
+                data: MetricData::Gauge(vec![NumberDataPoint {
+                    attributes: vec![],
+                    start_time_unix_nano: 0,
+                    time_unix_nano: 1,
+                    value: 1.0,
+                }]),
+            }],
+        };
+        let point = vec![
+            0x11, 0, 0, 0, 0, 0, 0, 0, 0, // start_time_unix_nano
+            0x19, 1, 0, 0, 0, 0, 0, 0, 0, // time_unix_nano
+            0x21, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, // as_double
+        ];
+        let mut expect = vec![0x0a, 0x33, 0x0a, 0x00, 0x12, 0x2f];
+        expect.extend_from_slice(&[0x0a, 0x06, 0x0a, 0x01, b's', 0x12, 0x01, b'v']);
+        expect.extend_from_slice(&[0x12, 0x25, 0x0a, 0x01, b'm', 0x12, 0x01, b'h']);
+        expect.extend_from_slice(&[0x2a, 0x1d, 0x0a, 0x1b]);
+        expect.extend_from_slice(&point);
+        assert_eq!(req.encode(), expect);
+    }
+}
@@ -0,0 +1,44 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::time::{Duration, Instant};
+
+use crate::background::BackgroundThread;
+use crate::errors::Result;
+
+/// `PeriodicTask` is a handle of a background thread running an exporter on
+/// a fixed interval, e.g. started by `StatsdExporter::start` or
+/// `GraphiteBridge::start`.
+///
+/// Failures of the periodic runs are ignored, the next run tries again. When
+/// the task is shut down or dropped, the exporter runs one last time.
+#[derive(Debug)]
+pub struct PeriodicTask {
+    thread: BackgroundThread,
+}
+
+impl PeriodicTask {
+    /// `spawn` starts a thread named `name` calling `f` every `interval`.
+    pub(crate) fn spawn<F>(name: &str, interval: Duration, mut f: F) -> PeriodicTask
+    where
+        F: FnMut() -> Result<()> + Send + 'static,
+    {
+        let thread = BackgroundThread::spawn(name, move |shutdown| {
+            let mut next = Instant::now() + interval;
+            loop {
+                if shutdown.wait_until(next) {
+                    return f();
+                }
+                let _ = f();
+                next = (next + interval).max(Instant::now());
+            }
+        });
+
+        PeriodicTask { thread }
+    }
+
+    /// `shutdown` stops the background thread after a final run, and returns
+    /// the result of that run.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.thread.stop()
+    }
+}
@@ -5,7 +5,11 @@
 
 #![allow(missing_docs)]
 
+use std::time::{SystemTime, UNIX_EPOCH};
+
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct LabelPair {
     name: String,
     value: String,
@@ -55,6 +59,8 @@ impl LabelPair {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Gauge {
     value: f64,
 }
@@ -75,8 +81,12 @@ impl Gauge {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Counter {
     value: f64,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Counter {
@@ -93,9 +103,20 @@ impl Counter {
     pub fn get_value(&self) -> f64 {
         self.value
     }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    /// Returns the time the series was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Quantile {
     quantile: f64,
     value: f64,
@@ -137,10 +158,14 @@ impl Quantile {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Summary {
     sample_count: u64,
     sample_sum: f64,
     quantile: Vec<Quantile>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Summary {
@@ -184,9 +209,20 @@ impl Summary {
     pub fn get_quantile(&self) -> &[Quantile] {
         &self.quantile
     }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    /// Returns the time the series was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Untyped {
     value: f64,
 }
@@ -215,10 +251,14 @@ impl Untyped {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Histogram {
     sample_count: u64,
     sample_sum: f64,
     bucket: Vec<Bucket>,
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    created_timestamp: Option<Timestamp>,
 }
 
 impl Histogram {
@@ -250,9 +290,53 @@ impl Histogram {
     pub fn get_bucket(&self) -> &[Bucket] {
         &self.bucket
     }
+
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = Some(v);
+    }
+
+    /// Returns the time the series was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
 }
 
+/// A point in time, as seconds and non-negative nanoseconds since the Unix
+/// epoch, like the `google.protobuf.Timestamp` of the protobuf model.
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Timestamp {
+    pub seconds: i64,
+    pub nanos: i32,
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Timestamp {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => Timestamp {
+                seconds: d.as_secs() as i64,
+                nanos: d.subsec_nanos() as i32,
+            },
+            Err(e) => {
+                let d = e.duration();
+                let (seconds, nanos) = (-(d.as_secs() as i64), d.subsec_nanos() as i32);
+                if nanos == 0 {
+                    Timestamp { seconds, nanos }
+                } else {
+                    Timestamp {
+                        seconds: seconds - 1,
+                        nanos: 1_000_000_000 - nanos,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Bucket {
     cumulative_count: u64,
     upper_bound: f64,
@@ -294,6 +378,8 @@ impl Bucket {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Metric {
     // message fields
     label: Vec<LabelPair>,
@@ -407,6 +493,7 @@ impl Metric {
 }
 
 #[derive(Clone, PartialEq, Eq, Debug, Hash, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MetricType {
     COUNTER,
     GAUGE,
@@ -422,9 +509,12 @@ impl Default for MetricType {
 }
 
 #[derive(PartialEq, Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct MetricFamily {
     name: String,
     help: String,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
     field_type: MetricType,
     metric: Vec<Metric>,
 }
@@ -499,3 +589,46 @@ impl MetricFamily {
         &self.metric
     }
 }
+
+#[cfg(all(test, feature = "serde", not(feature = "protobuf")))]
+mod tests {
+    use super::*;
+    use crate::counter::CounterVec;
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_serde() {
+        let cv = CounterVec::new(Opts::new("test_counter", "test help"), &["a"]).unwrap();
+        cv.with_label_values(&["1"]).inc();
+        let mut mfs = cv.collect();
+        let created = Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 5,
+        };
+        mfs[0].mut_metric()[0]
+            .counter
+            .set_created_timestamp(created);
+
+        let json = serde_json::to_string(&mfs).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"name":"test_counter","help":"test help","type":"COUNTER","metric":[{"label":[{"name":"a","value":"1"}],"gauge":{"value":0.0},"counter":{"value":1.0,"created_timestamp":{"seconds":1700000000,"nanos":5}},"summary":{"sample_count":0,"sample_sum":0.0,"quantile":[]},"untyped":{"value":0.0},"histogram":{"sample_count":0,"sample_sum":0.0,"bucket":[]},"timestamp_ms":0}]}]"#
+        );
+        let decoded: Vec<MetricFamily> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, mfs);
+
+        // The output of the protobuf model, which skips unset fields, decodes
+        // to the same families.
+        let decoded: Vec<MetricFamily> = serde_json::from_str(
+            r#"[{"name":"test_counter","help":"test help","type":"COUNTER","metric":[{"label":[{"name":"a","value":"1"}],"counter":{"value":1.0,"created_timestamp":{"seconds":1700000000,"nanos":5}}}]}]"#,
+        )
+        .unwrap();
+        assert_eq!(decoded, mfs);
+
+        // Missing fields take their default values.
+        let m: Metric = serde_json::from_str(r#"{"gauge":{"value":1.0}}"#).unwrap();
+        assert_eq!(m.get_gauge().get_value(), 1.0);
+        assert_eq!(m.get_counter(), &Counter::default());
+        assert_eq!(m.timestamp_ms(), 0);
+    }
+}
@@ -1,8 +1,9 @@
+use protobuf::well_known_types::timestamp::Timestamp;
 use protobuf::{EnumOrUnknown, MessageField};
 
 use crate::proto::{
     Bucket, Counter, Gauge, Histogram, LabelPair, Metric, MetricFamily, MetricType, Quantile,
-    Summary,
+    Summary, Untyped,
 };
 
 impl Metric {
@@ -73,6 +74,16 @@ impl Metric {
         self.gauge = gauge.into();
     }
 
+    /// Returns the untyped value of this metric.
+    pub fn get_untyped(&self) -> &MessageField<Untyped> {
+        &self.untyped
+    }
+
+    /// Sets the untyped value of this metric to the specified one.
+    pub fn set_untyped(&mut self, untyped: Untyped) {
+        self.untyped = untyped.into();
+    }
+
     /// Returns the histogram of this metric.
     pub fn get_histogram(&self) -> &MessageField<Histogram> {
         &self.histogram
@@ -150,6 +161,16 @@ impl Summary {
     pub fn get_sample_sum(&self) -> f64 {
         self.sample_sum()
     }
+
+    /// Sets the time this summary was created or last reset.
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = MessageField::some(v);
+    }
+
+    /// Returns the time this summary was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
 }
 
 impl Quantile {
@@ -184,6 +205,12 @@ impl MessageFieldExt for MessageField<Counter> {
     }
 }
 
+impl MessageFieldExt for MessageField<Untyped> {
+    fn get_value(&self) -> f64 {
+        self.value()
+    }
+}
+
 impl Histogram {
     /// Returns the sample count of this histogram.
     pub fn get_sample_count(&self) -> u64 {
@@ -204,6 +231,28 @@ impl Histogram {
     pub fn set_bucket(&mut self, bucket: Vec<Bucket>) {
         self.bucket = bucket;
     }
+
+    /// Sets the time this histogram was created or last reset.
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = MessageField::some(v);
+    }
+
+    /// Returns the time this histogram was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
+}
+
+impl Counter {
+    /// Sets the time this counter was created or last reset.
+    pub fn set_created_timestamp(&mut self, v: Timestamp) {
+        self.created_timestamp = MessageField::some(v);
+    }
+
+    /// Returns the time this counter was created or last reset, if known.
+    pub fn get_created_timestamp(&self) -> Option<&Timestamp> {
+        self.created_timestamp.as_ref()
+    }
 }
 
 impl Bucket {
@@ -252,6 +301,12 @@ impl From<Histogram> for MessageField<Histogram> {
     }
 }
 
+impl From<Untyped> for MessageField<Untyped> {
+    fn from(value: Untyped) -> Self {
+        MessageField::some(value)
+    }
+}
+
 impl From<Summary> for MessageField<Summary> {
     fn from(value: Summary) -> Self {
         MessageField::some(value)
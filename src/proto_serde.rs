@@ -0,0 +1,318 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! Serde support for the protobuf model. The generated structs can't derive
+//! the traits, so they are implemented here with the field names of the plain
+//! model. Unset fields are skipped when serializing.
+
+use protobuf::well_known_types::timestamp::Timestamp;
+use protobuf::{EnumOrUnknown, MessageField};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::proto::{
+    Bucket, Counter, Gauge, Histogram, LabelPair, Metric, MetricFamily, MetricType, Quantile,
+    Summary, Untyped,
+};
+
+/// Implements `Serialize` and `Deserialize` for a generated message, the
+/// latter through a mirror struct of the given field types.
+macro_rules! impl_serde {
+    ($ty:ident, $repr:ident { $($key:literal => $field:ident: $field_ty:ty,)* }) => {
+        impl Serialize for $ty {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let len = [$($key),*].len();
+                let mut state = serializer.serialize_struct(stringify!($ty), len)?;
+                $(
+                    if self.$field.is_unset() {
+                        state.skip_field($key)?;
+                    } else {
+                        self.$field.serialize_to(&mut state, $key)?;
+                    }
+                )*
+                state.end()
+            }
+        }
+
+        #[derive(Default, Deserialize)]
+        #[serde(default)]
+        struct $repr {
+            $(
+                #[serde(rename = $key)]
+                $field: $field_ty,
+            )*
+        }
+
+        impl<'de> Deserialize<'de> for $ty {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let repr = $repr::deserialize(deserializer)?;
+                let mut msg = $ty::default();
+                $(msg.$field = FromRepr::from_repr(repr.$field);)*
+                Ok(msg)
+            }
+        }
+    };
+}
+
+/// A field of a generated message.
+trait Field {
+    fn is_unset(&self) -> bool;
+
+    fn serialize_to<S: SerializeStruct>(
+        &self,
+        state: &mut S,
+        key: &'static str,
+    ) -> Result<(), S::Error>;
+}
+
+macro_rules! impl_optional_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for Option<$ty> {
+                fn is_unset(&self) -> bool {
+                    self.is_none()
+                }
+
+                fn serialize_to<S: SerializeStruct>(
+                    &self,
+                    state: &mut S,
+                    key: &'static str,
+                ) -> Result<(), S::Error> {
+                    state.serialize_field(key, self)
+                }
+            }
+        )*
+    };
+}
+
+impl_optional_field!(String, f64, u64, i64);
+
+impl<T: Serialize> Field for Vec<T> {
+    fn is_unset(&self) -> bool {
+        self.is_empty()
+    }
+
+    fn serialize_to<S: SerializeStruct>(
+        &self,
+        state: &mut S,
+        key: &'static str,
+    ) -> Result<(), S::Error> {
+        state.serialize_field(key, self)
+    }
+}
+
+macro_rules! impl_message_field {
+    ($($ty:ty),*) => {
+        $(
+            impl Field for MessageField<$ty> {
+                fn is_unset(&self) -> bool {
+                    self.is_none()
+                }
+
+                fn serialize_to<S: SerializeStruct>(
+                    &self,
+                    state: &mut S,
+                    key: &'static str,
+                ) -> Result<(), S::Error> {
+                    state.serialize_field(key, &self.as_ref())
+                }
+            }
+        )*
+    };
+}
+
+impl_message_field!(Gauge, Counter, Summary, Untyped, Histogram);
+
+/// The well-known `Timestamp` is not ours to implement serde for, so it is
+/// serialized through [`TimestampRepr`].
+impl Field for MessageField<Timestamp> {
+    fn is_unset(&self) -> bool {
+        self.is_none()
+    }
+
+    fn serialize_to<S: SerializeStruct>(
+        &self,
+        state: &mut S,
+        key: &'static str,
+    ) -> Result<(), S::Error> {
+        let t = self.as_ref().map(|t| TimestampRepr {
+            seconds: t.seconds,
+            nanos: t.nanos,
+        });
+        state.serialize_field(key, &t)
+    }
+}
+
+impl Field for Option<EnumOrUnknown<MetricType>> {
+    fn is_unset(&self) -> bool {
+        self.is_none()
+    }
+
+    fn serialize_to<S: SerializeStruct>(
+        &self,
+        state: &mut S,
+        key: &'static str,
+    ) -> Result<(), S::Error> {
+        let t = self.and_then(|t| t.enum_value().ok()).map(|t| match t {
+            MetricType::COUNTER => MetricTypeRepr::COUNTER,
+            MetricType::GAUGE => MetricTypeRepr::GAUGE,
+            MetricType::SUMMARY => MetricTypeRepr::SUMMARY,
+            MetricType::UNTYPED => MetricTypeRepr::UNTYPED,
+            MetricType::HISTOGRAM => MetricTypeRepr::HISTOGRAM,
+        });
+        state.serialize_field(key, &t)
+    }
+}
+
+/// Conversion of a mirror field to its generated field.
+trait FromRepr<T> {
+    fn from_repr(repr: T) -> Self;
+}
+
+impl<T> FromRepr<T> for T {
+    fn from_repr(repr: T) -> T {
+        repr
+    }
+}
+
+impl<T> FromRepr<Option<T>> for MessageField<T> {
+    fn from_repr(repr: Option<T>) -> Self {
+        MessageField::from_option(repr)
+    }
+}
+
+impl FromRepr<Option<MetricTypeRepr>> for Option<EnumOrUnknown<MetricType>> {
+    fn from_repr(repr: Option<MetricTypeRepr>) -> Self {
+        repr.map(|t| {
+            EnumOrUnknown::new(match t {
+                MetricTypeRepr::COUNTER => MetricType::COUNTER,
+                MetricTypeRepr::GAUGE => MetricType::GAUGE,
+                MetricTypeRepr::SUMMARY => MetricType::SUMMARY,
+                MetricTypeRepr::UNTYPED => MetricType::UNTYPED,
+                MetricTypeRepr::HISTOGRAM => MetricType::HISTOGRAM,
+            })
+        })
+    }
+}
+
+impl FromRepr<Option<TimestampRepr>> for MessageField<Timestamp> {
+    fn from_repr(repr: Option<TimestampRepr>) -> Self {
+        MessageField::from_option(repr.map(|t| Timestamp {
+            seconds: t.seconds,
+            nanos: t.nanos,
+            ..Default::default()
+        }))
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct TimestampRepr {
+    seconds: i64,
+    nanos: i32,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Serialize, Deserialize)]
+enum MetricTypeRepr {
+    COUNTER,
+    GAUGE,
+    SUMMARY,
+    UNTYPED,
+    HISTOGRAM,
+}
+
+impl_serde!(LabelPair, LabelPairRepr {
+    "name" => name: Option<String>,
+    "value" => value: Option<String>,
+});
+
+impl_serde!(Gauge, GaugeRepr {
+    "value" => value: Option<f64>,
+});
+
+impl_serde!(Counter, CounterRepr {
+    "value" => value: Option<f64>,
+    "created_timestamp" => created_timestamp: Option<TimestampRepr>,
+});
+
+impl_serde!(Quantile, QuantileRepr {
+    "quantile" => quantile: Option<f64>,
+    "value" => value: Option<f64>,
+});
+
+impl_serde!(Summary, SummaryRepr {
+    "sample_count" => sample_count: Option<u64>,
+    "sample_sum" => sample_sum: Option<f64>,
+    "quantile" => quantile: Vec<Quantile>,
+    "created_timestamp" => created_timestamp: Option<TimestampRepr>,
+});
+
+impl_serde!(Untyped, UntypedRepr {
+    "value" => value: Option<f64>,
+});
+
+impl_serde!(Histogram, HistogramRepr {
+    "sample_count" => sample_count: Option<u64>,
+    "sample_sum" => sample_sum: Option<f64>,
+    "bucket" => bucket: Vec<Bucket>,
+    "created_timestamp" => created_timestamp: Option<TimestampRepr>,
+});
+
+impl_serde!(Bucket, BucketRepr {
+    "cumulative_count" => cumulative_count: Option<u64>,
+    "upper_bound" => upper_bound: Option<f64>,
+});
+
+impl_serde!(Metric, MetricRepr {
+    "label" => label: Vec<LabelPair>,
+    "gauge" => gauge: Option<Gauge>,
+    "counter" => counter: Option<Counter>,
+    "summary" => summary: Option<Summary>,
+    "untyped" => untyped: Option<Untyped>,
+    "histogram" => histogram: Option<Histogram>,
+    "timestamp_ms" => timestamp_ms: Option<i64>,
+});
+
+impl_serde!(MetricFamily, MetricFamilyRepr {
+    "name" => name: Option<String>,
+    "help" => help: Option<String>,
+    "type" => type_: Option<MetricTypeRepr>,
+    "metric" => metric: Vec<Metric>,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::counter::CounterVec;
+    use crate::metrics::{Collector, Opts};
+
+    #[test]
+    fn test_serde() {
+        let cv = CounterVec::new(Opts::new("test_counter", "test help"), &["a"]).unwrap();
+        cv.with_label_values(&["1"]).inc();
+        let mut mfs = cv.collect();
+        let created = Timestamp {
+            seconds: 1_700_000_000,
+            nanos: 5,
+            ..Default::default()
+        };
+        mfs[0].mut_metric()[0]
+            .counter
+            .mut_or_insert_default()
+            .set_created_timestamp(created);
+
+        let json = serde_json::to_string(&mfs).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"name":"test_counter","help":"test help","type":"COUNTER","metric":[{"label":[{"name":"a","value":"1"}],"counter":{"value":1.0,"created_timestamp":{"seconds":1700000000,"nanos":5}}}]}]"#
+        );
+        let decoded: Vec<MetricFamily> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, mfs);
+
+        // Missing fields stay unset.
+        let m: Metric = serde_json::from_str(r#"{"gauge":{}}"#).unwrap();
+        assert!(m.gauge.is_some());
+        assert!(m.counter.is_none());
+        assert_eq!(m.timestamp_ms, None);
+    }
+}
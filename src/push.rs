@@ -7,12 +7,13 @@ use std::str::{self, FromStr};
 use std::time::Duration;
 
 use reqwest::blocking::Client;
-use reqwest::header::CONTENT_TYPE;
-use reqwest::{Method, StatusCode, Url};
+use reqwest::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Method, StatusCode};
 
 use lazy_static::lazy_static;
 
-use crate::encoder::{Encoder, ProtobufEncoder};
+use crate::desc::is_valid_label_name;
+use crate::encoder::{Encoder, ProtobufEncoder, PROTOBUF_FORMAT};
 use crate::errors::{Error, Result};
 use crate::metrics::Collector;
 use crate::proto;
@@ -41,9 +42,10 @@ pub struct BasicAuthentication {
 /// `push_metrics` pushes all gathered metrics to the Pushgateway specified by
 /// url, using the provided job name and the (optional) further grouping labels
 /// (the grouping map may be nil). See the Pushgateway documentation for
-/// detailed implications of the job and other grouping labels. Neither the job
-/// name nor any grouping label value may contain a "/". The metrics pushed must
-/// not contain a job label of their own nor any of the grouping labels.
+/// detailed implications of the job and other grouping labels. The job name
+/// and the grouping label values are escaped, so they may contain any
+/// character, but the job name must not be empty. The metrics pushed must not
+/// contain a job label of their own nor any of the grouping labels.
 ///
 /// You can use just host:port or ip:port as url, in which case 'http://' is
 /// added automatically. You can also include the schema in the URL. However, do
@@ -52,6 +54,8 @@ pub struct BasicAuthentication {
 /// Note that all previously pushed metrics with the same job and other grouping
 /// labels will be replaced with the metrics pushed by this call. (It uses HTTP
 /// method 'PUT' to push to the Pushgateway.)
+///
+/// See [`Pusher`] for more options, e.g. bearer tokens or custom headers.
 pub fn push_metrics<S: BuildHasher>(
     job: &str,
     grouping: HashMap<String, String, S>,
@@ -85,9 +89,30 @@ fn push<S: BuildHasher>(
     method: &str,
     basic_auth: Option<BasicAuthentication>,
 ) -> Result<()> {
-    // Suppress clippy warning needless_pass_by_value.
-    let grouping = grouping;
+    let push_url = build_push_url(url, job, &grouping)?;
+    let body = encode_push_body(mfs, &grouping)?;
+
+    let mut builder = HTTP_CLIENT
+        .request(Method::from_str(method).unwrap(), push_url.as_str())
+        .header(CONTENT_TYPE, PROTOBUF_FORMAT)
+        .body(body);
+
+    if let Some(BasicAuthentication { username, password }) = basic_auth {
+        builder = builder.basic_auth(username, Some(password));
+    }
 
+    let response = builder.send()?;
+    check_push_status(response.status(), push_url)
+}
+
+/// `build_push_url` builds the Pushgateway URL of the grouping identified by
+/// `job` and the grouping labels, adding 'http://' to `url` if it has no
+/// schema. The job and the label values are escaped, see `encode_component`.
+pub(crate) fn build_push_url<S: BuildHasher>(
+    url: &str,
+    job: &str,
+    grouping: &HashMap<String, String, S>,
+) -> Result<String> {
     let mut push_url = if url.contains("://") {
         url.to_owned()
     } else {
@@ -98,28 +123,90 @@ fn push<S: BuildHasher>(
         push_url.pop();
     }
 
-    let mut url_components = Vec::new();
-    if job.contains('/') {
-        return Err(Error::Msg(format!("job contains '/': {}", job)));
+    if job.is_empty() {
+        return Err(Error::InvalidGrouping {
+            name: LABEL_NAME_JOB.to_owned(),
+            value: job.to_owned(),
+        });
+    }
+    push_url.push_str("/metrics/");
+    push_url.push_str(&encode_component(LABEL_NAME_JOB, job));
+
+    let mut names: Vec<_> = grouping.keys().collect();
+    names.sort();
+    for ln in names {
+        if ln == LABEL_NAME_JOB {
+            return Err(Error::ReservedLabel { name: ln.clone() });
+        }
+        if !is_valid_label_name(ln) {
+            return Err(Error::InvalidLabelName { name: ln.clone() });
+        }
+        push_url.push('/');
+        push_url.push_str(&encode_component(ln, &grouping[ln]));
     }
 
-    // TODO: escape job
-    url_components.push(job.to_owned());
+    Ok(push_url)
+}
 
-    for (ln, lv) in &grouping {
-        // TODO: check label name
-        if lv.contains('/') {
-            return Err(Error::Msg(format!(
-                "value of grouping label {} contains '/': {}",
-                ln, lv
-            )));
+/// `encode_component` encodes a label pair of the grouping key as a URL path
+/// component. Values which are empty or contain a '/' use the `@base64`
+/// suffix of the Pushgateway, all others are percent-encoded.
+fn encode_component(name: &str, value: &str) -> String {
+    if value.is_empty() || value.contains('/') {
+        format!("{}@base64/{}", name, encode_base64_url(value.as_bytes()))
+    } else {
+        format!("{}/{}", name, escape_path(value))
+    }
+}
+
+/// `escape_path` percent-encodes all but the unreserved characters of RFC 3986.
+fn escape_path(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for b in value.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                escaped.push(b as char)
+            }
+            _ => escaped.push_str(&format!("%{:02X}", b)),
         }
-        url_components.push(ln.to_owned());
-        url_components.push(lv.to_owned());
+    }
+    escaped
+}
+
+/// `encode_base64_url` encodes with the padded, URL and filename safe base64
+/// alphabet of RFC 4648. An empty input is encoded as a single '=', as
+/// expected by the Pushgateway.
+fn encode_base64_url(input: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+    if input.is_empty() {
+        return "=".to_owned();
     }
 
-    push_url = format!("{}/metrics/job/{}", push_url, url_components.join("/"));
+    let mut encoded = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
 
+/// `encode_push_body` encodes the metric families to push in the protobuf
+/// format. The metrics must not contain a job label nor any of the grouping
+/// labels.
+pub(crate) fn encode_push_body<S: BuildHasher>(
+    mfs: Vec<proto::MetricFamily>,
+    grouping: &HashMap<String, String, S>,
+) -> Result<Vec<u8>> {
     let encoder = ProtobufEncoder::new();
     let mut buf = Vec::new();
 
@@ -127,20 +214,11 @@ fn push<S: BuildHasher>(
         // Check for pre-existing grouping labels:
         for m in mf.get_metric() {
             for lp in m.get_label() {
-                if lp.get_name() == LABEL_NAME_JOB {
-                    return Err(Error::Msg(format!(
-                        "pushed metric {} already contains a \
-                         job label",
-                        mf.get_name()
-                    )));
-                }
-                if grouping.contains_key(lp.get_name()) {
-                    return Err(Error::Msg(format!(
-                        "pushed metric {} already contains \
-                         grouping label {}",
-                        mf.get_name(),
-                        lp.get_name()
-                    )));
+                if lp.name() == LABEL_NAME_JOB || grouping.contains_key(lp.name()) {
+                    return Err(Error::GroupingLabelConflict {
+                        metric: mf.name().to_owned(),
+                        label: lp.name().to_owned(),
+                    });
                 }
             }
         }
@@ -148,28 +226,18 @@ fn push<S: BuildHasher>(
         let _ = encoder.encode(&[mf], &mut buf);
     }
 
-    let mut builder = HTTP_CLIENT
-        .request(
-            Method::from_str(method).unwrap(),
-            Url::from_str(&push_url).unwrap(),
-        )
-        .header(CONTENT_TYPE, encoder.format_type())
-        .body(buf);
-
-    if let Some(BasicAuthentication { username, password }) = basic_auth {
-        builder = builder.basic_auth(username, Some(password));
-    }
-
-    let response = builder.send().map_err(|e| Error::Msg(format!("{}", e)))?;
+    Ok(buf)
+}
 
-    match response.status() {
+/// `check_push_status` turns a Pushgateway response status into a result.
+pub(crate) fn check_push_status(status: StatusCode, push_url: String) -> Result<()> {
+    match status {
         StatusCode::ACCEPTED => Ok(()),
         StatusCode::OK => Ok(()),
-        _ => Err(Error::Msg(format!(
-            "unexpected status code {} while pushing to {}",
-            response.status(),
-            push_url
-        ))),
+        status => Err(Error::PushFailed {
+            status: status.as_u16(),
+            url: push_url,
+        }),
     }
 }
 
@@ -214,6 +282,171 @@ pub fn push_add_collector<S: BuildHasher>(
     push_from_collector(job, grouping, url, collectors, "POST", basic_auth)
 }
 
+/// `Pusher` manages a push to the Pushgateway. Use [`Pusher::new`] to create
+/// one, configure it with its builder methods, and finally use [`push`],
+/// [`add`] or [`delete`]:
+///
+/// ```no_run
+/// # fn run() -> prometheus::Result<()> {
+/// let counter = prometheus::IntCounter::new("batch_processed", "Processed items.")?;
+/// let pusher = prometheus::Pusher::new("127.0.0.1:9091", "batch")
+///     .grouping("instance", "worker-1")
+///     .collector(Box::new(counter))?;
+/// pusher.push()?;
+/// pusher.delete()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`push`]: Pusher::push
+/// [`add`]: Pusher::add
+/// [`delete`]: Pusher::delete
+#[derive(Debug)]
+pub struct Pusher {
+    url: String,
+    job: String,
+    grouping: HashMap<String, String>,
+    registry: Registry,
+    gatherers: Vec<Registry>,
+    basic_auth: Option<BasicAuthentication>,
+    bearer_token: Option<String>,
+    headers: Vec<(String, String)>,
+    timeout: Duration,
+}
+
+impl Pusher {
+    /// `new` creates a new `Pusher` to push to the provided URL with the
+    /// provided job name. You can use just host:port or ip:port as url, in
+    /// which case 'http://' is added automatically.
+    pub fn new<S1: Into<String>, S2: Into<String>>(url: S1, job: S2) -> Pusher {
+        Pusher {
+            url: url.into(),
+            job: job.into(),
+            grouping: HashMap::new(),
+            registry: Registry::new(),
+            gatherers: Vec::new(),
+            basic_auth: None,
+            bearer_token: None,
+            headers: Vec::new(),
+            timeout: REQWEST_TIMEOUT_SEC,
+        }
+    }
+
+    /// `grouping` adds a label pair to the grouping key of the `Pusher`,
+    /// replacing any previously added label pair with the same name.
+    pub fn grouping<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.grouping.insert(name.into(), value.into());
+        self
+    }
+
+    /// `collector` adds a [`Collector`] to the collectors whose metrics will be
+    /// pushed. It fails if the collector cannot be registered, see
+    /// [`Registry::register`].
+    pub fn collector(self, c: Box<dyn Collector>) -> Result<Self> {
+        self.registry.register(c)?;
+        Ok(self)
+    }
+
+    /// `gatherer` adds a [`Registry`] whose gathered metrics will be pushed in
+    /// addition to the ones of the added collectors.
+    pub fn gatherer(mut self, registry: &Registry) -> Self {
+        self.gatherers.push(registry.clone());
+        self
+    }
+
+    /// `basic_auth` configures the `Pusher` to use HTTP Basic Authentication.
+    pub fn basic_auth<S1: Into<String>, S2: Into<String>>(
+        mut self,
+        username: S1,
+        password: S2,
+    ) -> Self {
+        self.basic_auth = Some(BasicAuthentication {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// `bearer_token` configures the `Pusher` to send the token in the
+    /// `Authorization` header.
+    pub fn bearer_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// `header` adds a header sent with every request of the `Pusher`.
+    pub fn header<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// `timeout` sets the timeout of each request, 10 seconds by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `push` collects and pushes all metrics, replacing all previously
+    /// pushed metrics with the same job and other grouping labels. (It uses
+    /// HTTP method 'PUT'.)
+    pub fn push(&self) -> Result<()> {
+        self.send(Method::PUT, Some(self.gather()))
+    }
+
+    /// `add` works like `push`, but only previously pushed metrics with the
+    /// same name (and the same job and other grouping labels) will be
+    /// replaced. (It uses HTTP method 'POST'.)
+    pub fn add(&self) -> Result<()> {
+        self.send(Method::POST, Some(self.gather()))
+    }
+
+    /// `delete` deletes all metrics with the job and other grouping labels of
+    /// the `Pusher` from the Pushgateway. (It uses HTTP method 'DELETE'.)
+    pub fn delete(&self) -> Result<()> {
+        self.send(Method::DELETE, None)
+    }
+
+    pub(crate) fn gather(&self) -> Vec<proto::MetricFamily> {
+        let mut mfs = self.registry.gather();
+        for registry in &self.gatherers {
+            mfs.extend(registry.gather());
+        }
+        mfs
+    }
+
+    pub(crate) fn send(&self, method: Method, mfs: Option<Vec<proto::MetricFamily>>) -> Result<()> {
+        let push_url = build_push_url(&self.url, &self.job, &self.grouping)?;
+        let mut builder = HTTP_CLIENT
+            .request(method, push_url.as_str())
+            .timeout(self.timeout);
+
+        if let Some(mfs) = mfs {
+            builder = builder
+                .header(CONTENT_TYPE, PROTOBUF_FORMAT)
+                .body(encode_push_body(mfs, &self.grouping)?);
+        }
+
+        for (name, value) in &self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes());
+            let header_value = HeaderValue::from_str(value);
+            match (header_name, header_value) {
+                (Ok(n), Ok(v)) => builder = builder.header(n, v),
+                _ => return Err(Error::InvalidHeader { name: name.clone() }),
+            }
+        }
+
+        if let Some(BasicAuthentication { username, password }) = &self.basic_auth {
+            builder = builder.basic_auth(username, Some(password));
+        }
+        if let Some(token) = &self.bearer_token {
+            builder = builder.bearer_auth(token);
+        }
+
+        let response = builder.send()?;
+        check_push_status(response.status(), push_url)
+    }
+}
+
 const DEFAULT_GROUP_LABEL_PAIR: (&str, &str) = ("instance", "unknown");
 
 /// `hostname_grouping_key` returns a label map with the only entry
@@ -254,10 +487,54 @@ pub fn hostname_grouping_key() -> HashMap<String, String> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
     use super::*;
     use crate::proto;
 
+    // Serves one request per status in `statuses`, returning the request line
+    // and headers of each.
+    pub(crate) fn serve(statuses: &[&'static str]) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let statuses = statuses.to_vec();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (status, stream) in statuses.into_iter().zip(listener.incoming()) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" {
+                        break;
+                    }
+                    let lower = line.to_lowercase();
+                    if let Some(len) = lower.strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push(head);
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (addr, handle)
+    }
+
     #[test]
     fn test_hostname_grouping_key() {
         let map = hostname_grouping_key();
@@ -266,22 +543,124 @@ mod tests {
 
     #[test]
     fn test_push_bad_label_name() {
-        let table = vec![
-            // Error message: "pushed metric {} already contains a job label"
-            (LABEL_NAME_JOB, "job label"),
-            // Error message: "pushed metric {} already contains grouping label {}"
-            (DEFAULT_GROUP_LABEL_PAIR.0, "grouping label"),
-        ];
+        let table = vec![LABEL_NAME_JOB, DEFAULT_GROUP_LABEL_PAIR.0];
 
         for case in table {
             let mut l = proto::LabelPair::new();
-            l.set_name(case.0.to_owned());
+            l.set_name(case.to_owned());
             let mut m = proto::Metric::new();
             m.set_label(vec![l]);
             let mut mf = proto::MetricFamily::new();
             mf.set_metric(vec![m]);
             let res = push_metrics("test", hostname_grouping_key(), "mockurl", vec![mf], None);
-            assert!(format!("{}", res.unwrap_err()).contains(case.1));
+            match res.unwrap_err() {
+                Error::GroupingLabelConflict { label, .. } => assert_eq!(label, case),
+                other => panic!("{}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_pusher() {
+        let (addr, handle) = serve(&["200 OK"; 3]);
+        let counter = crate::IntCounter::new("test_counter", "test help").unwrap();
+        counter.inc();
+        let registry = Registry::new();
+        let gauge = crate::IntGauge::new("test_gauge", "test help").unwrap();
+        registry.register(Box::new(gauge)).unwrap();
+
+        let pusher = Pusher::new(addr, "job")
+            .grouping("instance", "a")
+            .collector(Box::new(counter))
+            .unwrap()
+            .gatherer(&registry)
+            .bearer_token("token")
+            .header("X-Test", "yes")
+            .timeout(Duration::from_secs(1));
+        assert_eq!(pusher.gather().len(), 2);
+        pusher.push().unwrap();
+        pusher.add().unwrap();
+        pusher.delete().unwrap();
+
+        let requests = handle.join().unwrap();
+        let methods = ["PUT", "POST", "DELETE"];
+        for (request, method) in requests.iter().zip(methods.iter()) {
+            let expect = format!("{} /metrics/job/job/instance/a HTTP/1.1", method);
+            assert!(request.starts_with(&expect), "{}", request);
+            let lower = request.to_lowercase();
+            assert!(lower.contains("x-test: yes"));
+            assert!(lower.contains("authorization: bearer token"));
+        }
+        assert!(requests[0].contains(PROTOBUF_FORMAT));
+        assert!(!requests[2].contains(PROTOBUF_FORMAT));
+    }
+
+    #[test]
+    fn test_pusher_errors() {
+        let counter = crate::IntCounter::new("test_counter", "test help").unwrap();
+        let res = Pusher::new("127.0.0.1:9091", "job")
+            .collector(Box::new(counter.clone()))
+            .unwrap()
+            .collector(Box::new(counter));
+        assert!(matches!(res.unwrap_err(), Error::AlreadyReg));
+
+        let (addr, handle) = serve(&["500 Internal Server Error"]);
+        match Pusher::new(addr, "job").delete().unwrap_err() {
+            Error::PushFailed { status, .. } => assert_eq!(status, 500),
+            other => panic!("{}", other),
+        }
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_build_push_url() {
+        let no_grouping: HashMap<String, String> = HashMap::new();
+        let table = vec![
+            ("job", vec![], "http://gw:9091/metrics/job/job"),
+            ("a/b", vec![], "http://gw:9091/metrics/job@base64/YS9i"),
+            ("a b", vec![], "http://gw:9091/metrics/job/a%20b"),
+            (
+                "job",
+                vec![("path", "/var/tmp"), ("empty", ""), ("instance", "x")],
+                "http://gw:9091/metrics/job/job/empty@base64/=/instance/x/path@base64/L3Zhci90bXA=",
+            ),
+        ];
+        for (job, labels, expect) in table {
+            let grouping: HashMap<_, _> = labels
+                .into_iter()
+                .map(|(n, v)| (n.to_owned(), v.to_owned()))
+                .collect();
+            assert_eq!(build_push_url("gw:9091/", job, &grouping).unwrap(), expect);
+        }
+
+        assert!(matches!(
+            build_push_url("gw:9091", "", &no_grouping),
+            Err(Error::InvalidGrouping { .. })
+        ));
+        let grouping = labels! {"job".to_owned() => "x".to_owned(),};
+        assert!(matches!(
+            build_push_url("gw:9091", "job", &grouping),
+            Err(Error::ReservedLabel { .. })
+        ));
+        let grouping = labels! {"in-valid".to_owned() => "x".to_owned(),};
+        assert!(matches!(
+            build_push_url("gw:9091", "job", &grouping),
+            Err(Error::InvalidLabelName { .. })
+        ));
+    }
+
+    #[test]
+    fn test_encode_base64_url() {
+        let table: Vec<(&[u8], &str)> = vec![
+            (b"", "="),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (&[0xfb, 0xff], "-_8="),
+        ];
+        for (input, expect) in table {
+            assert_eq!(encode_base64_url(input), expect);
         }
     }
 }
@@ -0,0 +1,285 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Certificate, Client, Method};
+
+use crate::encoder::PROTOBUF_FORMAT;
+use crate::errors::{Error, Result};
+use crate::proto;
+use crate::push::{build_push_url, check_push_status, encode_push_body, BasicAuthentication};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An asynchronous client for pushing metrics to a Pushgateway.
+///
+/// Unlike [`push_metrics`](crate::push_metrics), which blocks the calling
+/// thread, the methods of `PushClient` are `async` and can be awaited
+/// directly from an async runtime. A `PushClient` holds a connection pool, so
+/// it should be created once and reused.
+///
+/// ```no_run
+/// # async fn run() -> prometheus::Result<()> {
+/// use std::collections::HashMap;
+/// use std::time::Duration;
+///
+/// let client = prometheus::PushClient::builder("127.0.0.1:9091")
+///     .timeout(Duration::from_secs(5))
+///     .header("X-Scope-OrgID", "tenant")
+///     .build()?;
+/// let grouping: HashMap<String, String> = HashMap::new();
+/// client.push("batch", &grouping, prometheus::gather()).await?;
+/// client.delete("batch", &grouping).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PushClient {
+    client: Client,
+    url: String,
+    basic_auth: Option<BasicAuthentication>,
+}
+
+impl PushClient {
+    /// `builder` creates a [`PushClientBuilder`] for the Pushgateway at `url`.
+    ///
+    /// You can use just host:port or ip:port as url, in which case 'http://'
+    /// is added automatically. Do not include the '/metrics/jobs/...' part.
+    pub fn builder<S: Into<String>>(url: S) -> PushClientBuilder {
+        PushClientBuilder::new(url)
+    }
+
+    /// `push` pushes the metric families, replacing all previously pushed
+    /// metrics with the same job and other grouping labels. (It uses HTTP
+    /// method 'PUT' to push to the Pushgateway.)
+    pub async fn push<S: BuildHasher>(
+        &self,
+        job: &str,
+        grouping: &HashMap<String, String, S>,
+        mfs: Vec<proto::MetricFamily>,
+    ) -> Result<()> {
+        self.send(Method::PUT, job, grouping, Some(mfs)).await
+    }
+
+    /// `push_add` works like `push`, but only previously pushed metrics with
+    /// the same name (and the same job and other grouping labels) will be
+    /// replaced. (It uses HTTP method 'POST' to push to the Pushgateway.)
+    pub async fn push_add<S: BuildHasher>(
+        &self,
+        job: &str,
+        grouping: &HashMap<String, String, S>,
+        mfs: Vec<proto::MetricFamily>,
+    ) -> Result<()> {
+        self.send(Method::POST, job, grouping, Some(mfs)).await
+    }
+
+    /// `delete` deletes all metrics of the job and other grouping labels from
+    /// the Pushgateway. (It uses HTTP method 'DELETE'.)
+    pub async fn delete<S: BuildHasher>(
+        &self,
+        job: &str,
+        grouping: &HashMap<String, String, S>,
+    ) -> Result<()> {
+        self.send(Method::DELETE, job, grouping, None).await
+    }
+
+    async fn send<S: BuildHasher>(
+        &self,
+        method: Method,
+        job: &str,
+        grouping: &HashMap<String, String, S>,
+        mfs: Option<Vec<proto::MetricFamily>>,
+    ) -> Result<()> {
+        let push_url = build_push_url(&self.url, job, grouping)?;
+        let mut builder = self.client.request(method, push_url.as_str());
+
+        if let Some(mfs) = mfs {
+            builder = builder
+                .header(CONTENT_TYPE, PROTOBUF_FORMAT)
+                .body(encode_push_body(mfs, grouping)?);
+        }
+
+        if let Some(BasicAuthentication { username, password }) = &self.basic_auth {
+            builder = builder.basic_auth(username, Some(password));
+        }
+
+        let response = builder.send().await?;
+        check_push_status(response.status(), push_url)
+    }
+}
+
+/// A builder for [`PushClient`], bundling the timeouts, TLS settings and
+/// extra headers of the client.
+#[derive(Debug)]
+pub struct PushClientBuilder {
+    url: String,
+    timeout: Duration,
+    connect_timeout: Option<Duration>,
+    headers: Vec<(String, String)>,
+    basic_auth: Option<BasicAuthentication>,
+    root_certificates: Vec<Vec<u8>>,
+    accept_invalid_certs: bool,
+}
+
+impl PushClientBuilder {
+    /// `new` creates a builder for the Pushgateway at `url`, with a 10 second
+    /// request timeout.
+    pub fn new<S: Into<String>>(url: S) -> PushClientBuilder {
+        PushClientBuilder {
+            url: url.into(),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: None,
+            headers: Vec::new(),
+            basic_auth: None,
+            root_certificates: Vec::new(),
+            accept_invalid_certs: false,
+        }
+    }
+
+    /// `timeout` sets the timeout of a whole request, from connecting until
+    /// the response has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `connect_timeout` sets the timeout for establishing a connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// `header` adds a header sent with every request.
+    pub fn header<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// `basic_auth` sets the credentials for Basic access authentication.
+    pub fn basic_auth<S1: Into<String>, S2: Into<String>>(
+        mut self,
+        username: S1,
+        password: S2,
+    ) -> Self {
+        self.basic_auth = Some(BasicAuthentication {
+            username: username.into(),
+            password: password.into(),
+        });
+        self
+    }
+
+    /// `add_root_certificate` adds a PEM encoded certificate to the trusted
+    /// root certificates, e.g. to trust a Pushgateway with a self-signed
+    /// certificate.
+    pub fn add_root_certificate(mut self, pem: &[u8]) -> Self {
+        self.root_certificates.push(pem.to_vec());
+        self
+    }
+
+    /// `danger_accept_invalid_certs` disables the validation of the
+    /// Pushgateway's TLS certificate. Only use this for testing.
+    pub fn danger_accept_invalid_certs(mut self, accept: bool) -> Self {
+        self.accept_invalid_certs = accept;
+        self
+    }
+
+    /// `build` creates the [`PushClient`]. It fails if a header or a root
+    /// certificate is invalid.
+    pub fn build(self) -> Result<PushClient> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes());
+            let header_value = HeaderValue::from_str(&value);
+            match (header_name, header_value) {
+                (Ok(n), Ok(v)) => {
+                    headers.append(n, v);
+                }
+                _ => return Err(Error::InvalidHeader { name }),
+            }
+        }
+
+        let mut builder = Client::builder()
+            .timeout(self.timeout)
+            .default_headers(headers)
+            .danger_accept_invalid_certs(self.accept_invalid_certs);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        for pem in &self.root_certificates {
+            builder = builder.add_root_certificate(Certificate::from_pem(pem)?);
+        }
+
+        Ok(PushClient {
+            client: builder.build()?,
+            url: self.url,
+            basic_auth: self.basic_auth,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::push::tests::serve;
+
+    #[tokio::test]
+    async fn test_push_client() {
+        let (addr, handle) = serve(&["202 Accepted"; 3]);
+        let client = PushClient::builder(addr)
+            .header("X-Test", "yes")
+            .basic_auth("user", "pass")
+            .build()
+            .unwrap();
+
+        let counter = crate::Counter::new("test_counter", "test help").unwrap();
+        counter.inc();
+        let registry = crate::Registry::new();
+        registry.register(Box::new(counter)).unwrap();
+
+        let grouping = labels! {"instance".to_owned() => "a".to_owned(),};
+        client
+            .push("job", &grouping, registry.gather())
+            .await
+            .unwrap();
+        client
+            .push_add("job", &grouping, registry.gather())
+            .await
+            .unwrap();
+        client.delete("job", &grouping).await.unwrap();
+
+        let requests = handle.join().unwrap();
+        let methods = ["PUT", "POST", "DELETE"];
+        for (request, method) in requests.iter().zip(methods.iter()) {
+            let expect = format!("{} /metrics/job/job/instance/a HTTP/1.1", method);
+            assert!(request.starts_with(&expect), "{}", request);
+            let lower = request.to_lowercase();
+            assert!(lower.contains("x-test: yes"));
+            assert!(lower.contains("authorization: basic"));
+        }
+        assert!(requests[0].contains(PROTOBUF_FORMAT));
+        assert!(!requests[2].contains(PROTOBUF_FORMAT));
+    }
+
+    #[tokio::test]
+    async fn test_push_client_errors() {
+        let (addr, handle) = serve(&["500 Internal Server Error"]);
+        let client = PushClient::builder(addr).build().unwrap();
+        let grouping: HashMap<String, String> = HashMap::new();
+        match client.delete("job", &grouping).await.unwrap_err() {
+            Error::PushFailed { status, .. } => assert_eq!(status, 500),
+            other => panic!("{}", other),
+        }
+        handle.join().unwrap();
+
+        let res = PushClient::builder("127.0.0.1:9091")
+            .header("bad header", "value")
+            .build();
+        match res.unwrap_err() {
+            Error::InvalidHeader { name } => assert_eq!(name, "bad header"),
+            other => panic!("{}", other),
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use reqwest::Method;
+
+use crate::background::{BackgroundThread, Shutdown};
+use crate::errors::{Error, Result};
+use crate::proto;
+use crate::push::Pusher;
+
+/// A struct that bundles the options for creating a [`PushScheduler`].
+#[derive(Clone, Debug)]
+pub struct PushSchedulerOpts {
+    /// The interval between two pushes.
+    pub interval: Duration,
+    /// The delay before the first retry of a failed push. It is doubled after
+    /// each further failure.
+    pub initial_backoff: Duration,
+    /// The maximum delay between two retries.
+    pub max_backoff: Duration,
+    /// The fraction, between 0 and 1, by which each retry delay is randomly
+    /// shortened or extended.
+    pub jitter: f64,
+    /// The number of gathered snapshots kept while the Pushgateway is
+    /// unavailable. The oldest snapshot is dropped when the queue is full.
+    pub retry_queue_size: usize,
+    /// Whether to push with [`Pusher::add`] instead of [`Pusher::push`].
+    pub push_add: bool,
+}
+
+impl PushSchedulerOpts {
+    /// `new` creates the options pushing every `interval`, retrying after
+    /// 1 second up to every 30 seconds with a jitter of 0.2, and keeping up to
+    /// 10 snapshots.
+    pub fn new(interval: Duration) -> PushSchedulerOpts {
+        PushSchedulerOpts {
+            interval,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            jitter: 0.2,
+            retry_queue_size: 10,
+            push_add: false,
+        }
+    }
+
+    /// `backoff` sets the initial and the maximum retry delay.
+    pub fn backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+
+    /// `jitter` sets the jitter of the retry delay.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// `retry_queue_size` sets the number of snapshots kept for retries.
+    pub fn retry_queue_size(mut self, size: usize) -> Self {
+        self.retry_queue_size = size;
+        self
+    }
+
+    /// `push_add` sets whether to push with POST instead of PUT.
+    pub fn push_add(mut self, push_add: bool) -> Self {
+        self.push_add = push_add;
+        self
+    }
+}
+
+/// `PushScheduler` pushes the metrics of a [`Pusher`] on a fixed interval
+/// from a background thread.
+///
+/// The metrics are gathered at every interval and queued. A push failing with
+/// a transport error, a server error or `429 Too Many Requests` is retried
+/// with exponential backoff, the queued snapshots are pushed in order once the
+/// Pushgateway is reachable again. A snapshot rejected with another client
+/// error is dropped. When the scheduler is shut down or
+/// dropped, the metrics are gathered and pushed one last time.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use prometheus::{PushScheduler, PushSchedulerOpts, Pusher};
+///
+/// let pusher = Pusher::new("127.0.0.1:9091", "worker").gatherer(prometheus::default_registry());
+/// let scheduler = PushScheduler::start(pusher, PushSchedulerOpts::new(Duration::from_secs(15)));
+/// // Do the work.
+/// scheduler.shutdown().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PushScheduler {
+    thread: BackgroundThread,
+}
+
+impl PushScheduler {
+    /// `start` spawns the background thread pushing with `pusher`.
+    pub fn start(pusher: Pusher, opts: PushSchedulerOpts) -> PushScheduler {
+        let worker = Worker {
+            pusher,
+            queue: VecDeque::with_capacity(opts.retry_queue_size.max(1)),
+            opts,
+            failures: 0,
+            rng: seed(),
+        };
+        let thread =
+            BackgroundThread::spawn("prometheus-push", move |shutdown| worker.run(shutdown));
+
+        PushScheduler { thread }
+    }
+
+    /// `shutdown` stops the background thread after a final push, and returns
+    /// the result of that push.
+    pub fn shutdown(mut self) -> Result<()> {
+        self.thread.stop()
+    }
+}
+
+struct Worker {
+    pusher: Pusher,
+    opts: PushSchedulerOpts,
+    queue: VecDeque<Vec<proto::MetricFamily>>,
+    failures: u32,
+    rng: u64,
+}
+
+impl Worker {
+    fn run(mut self, shutdown: Shutdown) -> Result<()> {
+        let mut next_push = Instant::now() + self.opts.interval;
+        let mut next_retry = None;
+        loop {
+            let deadline = match next_retry {
+                Some(retry) if retry < next_push => retry,
+                _ => next_push,
+            };
+            if shutdown.wait_until(deadline) {
+                break;
+            }
+
+            let now = Instant::now();
+            if now >= next_push {
+                next_push = now + self.opts.interval;
+                self.enqueue();
+            }
+
+            next_retry = match self.flush() {
+                Ok(()) => {
+                    self.failures = 0;
+                    None
+                }
+                Err(_) => {
+                    self.failures = self.failures.saturating_add(1);
+                    let rand = self.next_rand();
+                    Some(Instant::now() + backoff_delay(&self.opts, self.failures, rand))
+                }
+            };
+        }
+
+        self.enqueue();
+        self.flush()
+    }
+
+    fn enqueue(&mut self) {
+        if self.queue.len() >= self.opts.retry_queue_size.max(1) {
+            self.queue.pop_front();
+        }
+        self.queue.push_back(self.pusher.gather());
+    }
+
+    /// `flush` pushes the queued snapshots in order, and stops at the first
+    /// push failing with a retryable error, see [`is_retryable`]. Snapshots
+    /// which can never be pushed, e.g. because of conflicting grouping labels
+    /// or a rejected payload, are dropped.
+    fn flush(&mut self) -> Result<()> {
+        let method = if self.opts.push_add {
+            Method::POST
+        } else {
+            Method::PUT
+        };
+        while let Some(mfs) = self.queue.front() {
+            match self.pusher.send(method.clone(), Some(mfs.clone())) {
+                Ok(()) => {}
+                Err(e) if is_retryable(&e) => return Err(e),
+                Err(e) => {
+                    self.queue.pop_front();
+                    return Err(e);
+                }
+            }
+            self.queue.pop_front();
+        }
+        Ok(())
+    }
+
+    // xorshift64*, returning a value in [0, 1).
+    fn next_rand(&mut self) -> f64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        (self.rng.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// `is_retryable` returns whether a failed push may succeed later: on
+/// transport errors, server errors and rate limiting, but not on other client
+/// errors such as a bad payload or missing authorization.
+fn is_retryable(e: &Error) -> bool {
+    match e {
+        Error::Http(_) => true,
+        Error::PushFailed { status, .. } => *status >= 500 || *status == 429,
+        _ => false,
+    }
+}
+
+fn seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    u64::from(nanos) << 1 | 1
+}
+
+/// `backoff_delay` returns the delay before the retry following the
+/// `failures`th consecutive failure, `rand` being a random value in [0, 1).
+fn backoff_delay(opts: &PushSchedulerOpts, failures: u32, rand: f64) -> Duration {
+    let exp = failures.saturating_sub(1).min(31);
+    let delay = opts
+        .initial_backoff
+        .saturating_mul(1 << exp)
+        .min(opts.max_backoff);
+    let jitter = opts.jitter.clamp(0.0, 1.0);
+    delay.mul_f64(1.0 + jitter * (2.0 * rand - 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::push::tests::serve;
+
+    #[test]
+    fn test_backoff_delay() {
+        let opts = PushSchedulerOpts::new(Duration::from_secs(10))
+            .backoff(Duration::from_millis(100), Duration::from_secs(1))
+            .jitter(0.0);
+        let table = vec![
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (4, 800),
+            (5, 1000),
+            (100, 1000),
+        ];
+        for (failures, millis) in table {
+            let delay = backoff_delay(&opts, failures, 0.7);
+            assert_eq!(delay, Duration::from_millis(millis));
+        }
+
+        let opts = opts.jitter(0.5);
+        assert_eq!(backoff_delay(&opts, 1, 0.0), Duration::from_millis(50));
+        assert_eq!(backoff_delay(&opts, 1, 0.5), Duration::from_millis(100));
+        assert!(backoff_delay(&opts, 1, 0.999) < Duration::from_millis(150));
+    }
+
+    #[test]
+    fn test_push_scheduler() {
+        let (addr, handle) = serve(&["500 Internal Server Error", "200 OK", "200 OK"]);
+        let counter = crate::IntCounter::new("test_counter", "test help").unwrap();
+        let pusher = Pusher::new(addr, "job")
+            .collector(Box::new(counter))
+            .unwrap();
+        let opts = PushSchedulerOpts::new(Duration::from_millis(10))
+            .backoff(Duration::from_millis(10), Duration::from_millis(10))
+            .push_add(true);
+
+        // The first push fails, the scheduler keeps pushing regardless.
+        let scheduler = PushScheduler::start(pusher, opts);
+        let requests = handle.join().unwrap();
+        drop(scheduler);
+
+        assert_eq!(requests.len(), 3);
+        for request in requests {
+            assert!(request.starts_with("POST /metrics/job/job HTTP/1.1"));
+        }
+    }
+
+    #[test]
+    fn test_push_scheduler_queue() {
+        let (addr, handle) = serve(&["200 OK"; 2]);
+        let pusher = Pusher::new(addr, "job");
+        let opts = PushSchedulerOpts::new(Duration::from_secs(3600)).retry_queue_size(2);
+        let mut worker = Worker {
+            pusher,
+            queue: VecDeque::new(),
+            opts,
+            failures: 0,
+            rng: seed(),
+        };
+        for _ in 0..3 {
+            worker.enqueue();
+        }
+        assert_eq!(worker.queue.len(), 2);
+        worker.flush().unwrap();
+        assert!(worker.queue.is_empty());
+        assert_eq!(handle.join().unwrap().len(), 2);
+
+        // A server error keeps the snapshot for a retry, a client error
+        // drops it.
+        let (addr, handle) = serve(&["503 Service Unavailable", "400 Bad Request", "200 OK"]);
+        worker.pusher = Pusher::new(addr, "job");
+        worker.enqueue();
+        worker.enqueue();
+        worker.flush().unwrap_err();
+        assert_eq!(worker.queue.len(), 2);
+        worker.flush().unwrap_err();
+        assert_eq!(worker.queue.len(), 1);
+        worker.flush().unwrap();
+        assert!(worker.queue.is_empty());
+        assert_eq!(handle.join().unwrap().len(), 3);
+    }
+}
@@ -4,26 +4,111 @@
 use std::collections::btree_map::Entry as BEntry;
 use std::collections::hash_map::Entry as HEntry;
 use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::Hasher;
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use fnv::FnvHasher;
 use parking_lot::RwLock;
 
+use crate::desc::{is_valid_label_name, is_valid_metric_name, Desc};
 use crate::errors::{Error, Result};
-use crate::metrics::Collector;
+use crate::histogram::BUCKET_LABEL;
+use crate::metrics::{Collector, SEPARATOR_BYTE};
 use crate::proto;
+use crate::timer;
 
 use cfg_if::cfg_if;
 use lazy_static::lazy_static;
 
+/// Used for the label that defines the quantile of a summary.
+const QUANTILE_LABEL: &str = "quantile";
+
+/// A struct that bundles the options for creating a [`Registry`]. All fields
+/// are optional and can safely be left at their zero value.
+#[derive(Clone, Debug, Default)]
+pub struct RegistryOpts {
+    /// prefix is prepended, joined with "_", to the names of all gathered
+    /// metric families. It must not be empty if set.
+    pub prefix: Option<String>,
+
+    /// labels are attached to every gathered metric.
+    pub labels: Option<HashMap<String, String>>,
+
+    /// unchecked allows registering collectors whose `desc` returns no
+    /// descriptors at all. Such collectors are meant for exporters whose
+    /// metric names are not known up front. Since their metrics cannot be
+    /// checked at registration time, they are checked for consistency on
+    /// every gather instead. Unchecked collectors cannot be unregistered.
+    pub unchecked: bool,
+
+    /// pedantic additionally checks on every gather that the metrics collected
+    /// from each collector match the descriptors it registered with. This is
+    /// mostly useful in tests of custom [`Collector`] implementations.
+    pub pedantic: bool,
+
+    /// gather_timestamps stamps every gathered metric without an explicit
+    /// timestamp with the time of the gather, e.g. for exporters whose
+    /// samples are relayed later rather than scraped.
+    pub gather_timestamps: bool,
+}
+
+impl RegistryOpts {
+    /// `new` creates the RegistryOpts with every option left unset.
+    pub fn new() -> RegistryOpts {
+        Default::default()
+    }
+
+    /// `prefix` sets the common namespace prefix.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// `labels` sets the common labels.
+    pub fn labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// `unchecked` enables or disables the registration of unchecked collectors.
+    pub fn unchecked(mut self, unchecked: bool) -> Self {
+        self.unchecked = unchecked;
+        self
+    }
+
+    /// `pedantic` enables or disables checking gathered metrics against the
+    /// registered descriptors.
+    pub fn pedantic(mut self, pedantic: bool) -> Self {
+        self.pedantic = pedantic;
+        self
+    }
+
+    /// `gather_timestamps` enables or disables stamping gathered metrics
+    /// with the time of the gather.
+    pub fn gather_timestamps(mut self, gather_timestamps: bool) -> Self {
+        self.gather_timestamps = gather_timestamps;
+        self
+    }
+}
+
 #[derive(Default)]
 struct RegistryCore {
     pub collectors_by_id: HashMap<u64, Box<dyn Collector>>,
+    /// Collectors without any descriptors, only accepted in unchecked mode.
+    pub unchecked_collectors: Vec<Box<dyn Collector>>,
     pub dim_hashes_by_name: HashMap<String, u64>,
     pub desc_ids: HashSet<u64>,
     /// Optional common labels for all registered collectors.
     pub labels: Option<HashMap<String, String>>,
     /// Optional common namespace for all registered collectors.
     pub prefix: Option<String>,
+    /// Whether collectors without descriptors are accepted.
+    pub unchecked: bool,
+    /// Whether gathered metrics are checked against registered descriptors.
+    pub pedantic: bool,
+    /// Whether gathered metrics without a timestamp get the gather time.
+    pub gather_timestamps: bool,
 }
 
 impl std::fmt::Debug for RegistryCore {
@@ -31,13 +116,18 @@ impl std::fmt::Debug for RegistryCore {
         write!(
             f,
             "RegistryCore ({} collectors)",
-            self.collectors_by_id.keys().len()
+            self.collectors_by_id.keys().len() + self.unchecked_collectors.len()
         )
     }
 }
 
 impl RegistryCore {
     fn register(&mut self, c: Box<dyn Collector>) -> Result<()> {
+        if self.unchecked && c.desc().is_empty() {
+            self.unchecked_collectors.push(c);
+            return Ok(());
+        }
+
         let mut desc_id_set = HashSet::new();
         let mut collector_id: u64 = 0;
 
@@ -50,13 +140,9 @@ impl RegistryCore {
 
             if let Some(hash) = self.dim_hashes_by_name.get(&desc.fq_name) {
                 if *hash != desc.dim_hash {
-                    return Err(Error::Msg(format!(
-                        "a previously registered descriptor with the \
-                         same fully-qualified name as {:?} has \
-                         different label names or a different help \
-                         string",
-                        desc
-                    )));
+                    return Err(Error::DescriptorMismatch {
+                        name: desc.fq_name.clone(),
+                    });
                 }
             }
 
@@ -72,11 +158,9 @@ impl RegistryCore {
                 // The set did have this value present, false is returned.
                 //
                 // TODO: Should we allow duplicate descs within the same collector?
-                return Err(Error::Msg(format!(
-                    "a duplicate descriptor within the same \
-                     collector the same fully-qualified name: {:?}",
-                    desc.fq_name
-                )));
+                return Err(Error::DuplicateDescriptor {
+                    name: desc.fq_name.clone(),
+                });
             }
         }
 
@@ -91,6 +175,10 @@ impl RegistryCore {
     }
 
     fn unregister(&mut self, c: Box<dyn Collector>) -> Result<()> {
+        if self.unchecked && c.desc().is_empty() {
+            return Err(Error::NotRegistered);
+        }
+
         let mut id_set = Vec::new();
         let mut collector_id: u64 = 0;
         for desc in c.desc() {
@@ -101,10 +189,7 @@ impl RegistryCore {
         }
 
         if self.collectors_by_id.remove(&collector_id).is_none() {
-            return Err(Error::Msg(format!(
-                "collector {:?} is not registered",
-                c.desc()
-            )));
+            return Err(Error::NotRegistered);
         }
 
         for id in id_set {
@@ -116,10 +201,17 @@ impl RegistryCore {
         Ok(())
     }
 
-    fn gather(&self) -> Vec<proto::MetricFamily> {
+    /// Gathers all registered collectors. Metric families failing the checks
+    /// of the unchecked or pedantic mode are left out of the result, the
+    /// first such failure is returned alongside.
+    fn gather(&self) -> (Vec<proto::MetricFamily>, Option<Error>) {
         let mut mf_by_name = BTreeMap::new();
+        let mut first_err = None;
+        let check = self.unchecked || self.pedantic;
 
-        for c in self.collectors_by_id.values() {
+        let checked = self.collectors_by_id.values().map(|c| (c, true));
+        let unchecked = self.unchecked_collectors.iter().map(|c| (c, false));
+        for (c, is_checked) in checked.chain(unchecked) {
             let mfs = c.collect();
             for mut mf in mfs {
                 // Prune empty MetricFamilies.
@@ -127,6 +219,20 @@ impl RegistryCore {
                     continue;
                 }
 
+                if check {
+                    let res = if is_checked && self.pedantic {
+                        check_metric_family(&mf).and_then(|_| check_against_descs(&mf, &c.desc()))
+                    } else if !is_checked {
+                        check_metric_family(&mf)
+                    } else {
+                        Ok(())
+                    };
+                    if let Err(e) = res {
+                        first_err.get_or_insert(e);
+                        continue;
+                    }
+                }
+
                 let name = mf.name().to_owned();
                 match mf_by_name.entry(name) {
                     BEntry::Vacant(entry) => {
@@ -134,10 +240,14 @@ impl RegistryCore {
                     }
                     BEntry::Occupied(mut entry) => {
                         let existent_mf = entry.get_mut();
-                        let existent_metrics = existent_mf.mut_metric();
+                        if check {
+                            if let Err(e) = check_same_family(existent_mf, &mf) {
+                                first_err.get_or_insert(e);
+                                continue;
+                            }
+                        }
 
-                        // TODO: check type.
-                        // TODO: check consistency.
+                        let existent_metrics = existent_mf.mut_metric();
                         for metric in mf.take_metric().into_iter() {
                             existent_metrics.push(metric);
                         }
@@ -146,6 +256,14 @@ impl RegistryCore {
             }
         }
 
+        if check {
+            for mf in mf_by_name.values_mut() {
+                if let Err(e) = dedup_metrics(mf) {
+                    first_err.get_or_insert(e);
+                }
+            }
+        }
+
         // TODO: metric_family injection hook.
 
         // Now that MetricFamilies are all set, sort their Metrics
@@ -181,8 +299,14 @@ impl RegistryCore {
             });
         }
 
+        let now = if self.gather_timestamps {
+            timer::unix_millis(SystemTime::now())
+        } else {
+            0
+        };
+
         // Write out MetricFamilies sorted by their name.
-        mf_by_name
+        let mfs = mf_by_name
             .into_values()
             .map(|mut m| {
                 // Add registry namespace prefix, if any.
@@ -209,10 +333,166 @@ impl RegistryCore {
                         metric.set_label(labels);
                     }
                 }
+
+                // Stamp metrics without an explicit timestamp, if enabled.
+                if now != 0 {
+                    for metric in m.mut_metric().iter_mut() {
+                        if metric.timestamp_ms() == 0 {
+                            metric.set_timestamp_ms(now);
+                        }
+                    }
+                }
                 m
             })
-            .collect()
+            .collect();
+
+        (mfs, first_err)
+    }
+}
+
+/// `check_metric_family` checks a collected metric family on its own: its name
+/// and label names must be valid, no metric may repeat a label name, and
+/// histograms and summaries must not use their reserved label names.
+fn check_metric_family(mf: &proto::MetricFamily) -> Result<()> {
+    if !is_valid_metric_name(mf.name()) {
+        return Err(Error::InvalidMetricName {
+            name: mf.name().to_owned(),
+        });
+    }
+
+    let reserved = match mf.get_field_type() {
+        proto::MetricType::HISTOGRAM => Some(BUCKET_LABEL),
+        proto::MetricType::SUMMARY => Some(QUANTILE_LABEL),
+        _ => None,
+    };
+
+    for m in mf.get_metric() {
+        let mut names = HashSet::with_capacity(m.get_label().len());
+        for lp in m.get_label() {
+            if !is_valid_label_name(lp.name()) {
+                return Err(Error::InvalidLabelName {
+                    name: lp.name().to_owned(),
+                });
+            }
+            if Some(lp.name()) == reserved {
+                return Err(Error::ReservedLabel {
+                    name: lp.name().to_owned(),
+                });
+            }
+            if !names.insert(lp.name()) {
+                return Err(Error::DuplicateLabel {
+                    name: lp.name().to_owned(),
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `check_against_descs` checks that every metric of a metric family collected
+/// by a collector matches one of the descriptors the collector was registered
+/// with, i.e. the name, help and label names agree and the const label values
+/// are the described ones.
+fn check_against_descs(mf: &proto::MetricFamily, descs: &[&Desc]) -> Result<()> {
+    let candidates: Vec<_> = descs.iter().filter(|d| d.fq_name == mf.name()).collect();
+    if candidates.is_empty() {
+        return Err(Error::UndescribedMetric {
+            name: mf.name().to_owned(),
+        });
+    }
+
+    if let Some(desc) = candidates.iter().find(|d| d.help != mf.help()) {
+        return Err(Error::HelpMismatch {
+            name: mf.name().to_owned(),
+            expect: desc.help.clone(),
+            got: mf.help().to_owned(),
+        });
+    }
+
+    for m in mf.get_metric() {
+        let labels = m.get_label();
+        let matches = candidates.iter().any(|d| {
+            labels.len() == d.const_label_pairs.len() + d.variable_labels.len()
+                && d.const_label_pairs.iter().all(|cp| {
+                    labels
+                        .iter()
+                        .any(|lp| lp.name() == cp.name() && lp.value() == cp.value())
+                })
+                && d.variable_labels
+                    .iter()
+                    .all(|name| labels.iter().any(|lp| lp.name() == name))
+        });
+        if !matches {
+            return Err(Error::InconsistentLabels {
+                name: mf.name().to_owned(),
+                labels: label_tuples(labels),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// `check_same_family` checks that two metric families of the same name,
+/// collected by different collectors, agree on their type and help string.
+fn check_same_family(existent: &proto::MetricFamily, mf: &proto::MetricFamily) -> Result<()> {
+    if existent.get_field_type() != mf.get_field_type() {
+        return Err(Error::TypeMismatch {
+            name: mf.name().to_owned(),
+            expect: existent.get_field_type(),
+            got: mf.get_field_type(),
+        });
+    }
+
+    if existent.help() != mf.help() {
+        return Err(Error::HelpMismatch {
+            name: mf.name().to_owned(),
+            expect: existent.help().to_owned(),
+            got: mf.help().to_owned(),
+        });
     }
+
+    Ok(())
+}
+
+/// `dedup_metrics` removes every metric that has the same label set as an
+/// earlier metric of the family, reporting the first one removed.
+fn dedup_metrics(mf: &mut proto::MetricFamily) -> Result<()> {
+    let name = mf.name().to_owned();
+    let mut seen = HashSet::with_capacity(mf.get_metric().len());
+    let mut dup = None;
+
+    mf.mut_metric().retain(|m| {
+        let mut pairs: Vec<_> = m.get_label().iter().collect();
+        pairs.sort();
+
+        let mut h = FnvHasher::default();
+        for lp in pairs {
+            h.write(lp.name().as_bytes());
+            h.write_u8(SEPARATOR_BYTE);
+            h.write(lp.value().as_bytes());
+            h.write_u8(SEPARATOR_BYTE);
+        }
+
+        let unique = seen.insert(h.finish());
+        if !unique && dup.is_none() {
+            dup = Some(label_tuples(m.get_label()));
+        }
+        unique
+    });
+
+    match dup {
+        Some(labels) => Err(Error::DuplicateMetric { name, labels }),
+        None => Ok(()),
+    }
+}
+
+fn label_tuples(labels: &[proto::LabelPair]) -> Vec<(String, String)> {
+    labels
+        .iter()
+        .map(|lp| (lp.name().to_owned(), lp.value().to_owned()))
+        .collect()
 }
 
 /// A struct for registering Prometheus collectors, collecting their metrics, and gathering
@@ -233,17 +513,29 @@ impl Registry {
         prefix: Option<String>,
         labels: Option<HashMap<String, String>>,
     ) -> Result<Registry> {
-        if let Some(ref namespace) = prefix {
+        Registry::with_opts(RegistryOpts {
+            prefix,
+            labels,
+            ..Default::default()
+        })
+    }
+
+    /// Create a new registry with the `opts` options.
+    pub fn with_opts(opts: RegistryOpts) -> Result<Registry> {
+        if let Some(ref namespace) = opts.prefix {
             if namespace.is_empty() {
-                return Err(Error::Msg("empty prefix namespace".to_string()));
+                return Err(Error::EmptyPrefix);
             }
         }
 
         let reg = Registry::default();
         {
             let mut core = reg.r.write();
-            core.prefix = prefix;
-            core.labels = labels;
+            core.prefix = opts.prefix;
+            core.labels = opts.labels;
+            core.unchecked = opts.unchecked;
+            core.pedantic = opts.pedantic;
+            core.gather_timestamps = opts.gather_timestamps;
         }
         Ok(reg)
     }
@@ -272,8 +564,23 @@ impl Registry {
     /// `gather` calls the Collect method of the registered Collectors and then
     /// gathers the collected metrics into a lexicographically sorted slice
     /// of MetricFamily protobufs.
+    ///
+    /// In unchecked or pedantic mode, collected metrics failing the checks of
+    /// that mode are silently left out. Use [`Registry::try_gather`] to learn
+    /// about them.
     pub fn gather(&self) -> Vec<proto::MetricFamily> {
-        self.r.read().gather()
+        self.r.read().gather().0
+    }
+
+    /// `try_gather` works like `gather`, but returns an error if any collected
+    /// metric fails the checks of the unchecked or pedantic mode. A registry in
+    /// neither mode performs no checks at gather time and never returns an
+    /// error.
+    pub fn try_gather(&self) -> Result<Vec<proto::MetricFamily>> {
+        match self.r.read().gather() {
+            (_, Some(e)) => Err(e),
+            (mfs, None) => Ok(mfs),
+        }
     }
 }
 
@@ -374,6 +681,36 @@ mod tests {
         counter_vec.with_label_values(&["1", "2"]).inc();
     }
 
+    #[test]
+    fn test_register_errors() {
+        let r = Registry::new();
+
+        let counter = Counter::new("test", "test help").unwrap();
+        r.register(Box::new(counter.clone())).unwrap();
+        assert!(matches!(
+            r.register(Box::new(counter.clone())),
+            Err(Error::AlreadyReg)
+        ));
+
+        let other_help =
+            Counter::with_opts(Opts::new("test", "other help").const_label("a", "1")).unwrap();
+        match r.register(Box::new(other_help)) {
+            Err(Error::DescriptorMismatch { name }) => assert_eq!(name, "test"),
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let unknown = Counter::new("unknown", "test help").unwrap();
+        assert!(matches!(
+            r.unregister(Box::new(unknown)),
+            Err(Error::NotRegistered)
+        ));
+
+        assert!(matches!(
+            Registry::new_custom(Some("".to_string()), None),
+            Err(Error::EmptyPrefix)
+        ));
+    }
+
     #[test]
     fn test_default_registry() {
         let counter = Counter::new("test", "test help").unwrap();
@@ -555,6 +892,169 @@ mod tests {
         r.register(Box::new(mc)).unwrap();
     }
 
+    struct UncheckedCollector {
+        mfs: Vec<proto::MetricFamily>,
+    }
+
+    impl UncheckedCollector {
+        fn new(name: &str, help: &str, metric_type: proto::MetricType, label: &str) -> Self {
+            let mut lp = proto::LabelPair::default();
+            lp.set_name(label.to_owned());
+            lp.set_value("v".to_owned());
+
+            let mut mf = proto::MetricFamily::default();
+            mf.set_name(name.to_owned());
+            mf.set_help(help.to_owned());
+            mf.set_field_type(metric_type);
+            mf.set_metric(vec![proto::Metric::from_label(vec![lp])]);
+            UncheckedCollector { mfs: vec![mf] }
+        }
+    }
+
+    impl Collector for UncheckedCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            vec![]
+        }
+
+        fn collect(&self) -> Vec<proto::MetricFamily> {
+            self.mfs.clone()
+        }
+    }
+
+    #[test]
+    fn test_unchecked_registry() {
+        let gauge = || UncheckedCollector::new("dyn", "dyn help", proto::MetricType::GAUGE, "a");
+
+        // A regular registry only takes a single collector without descriptors.
+        let r = Registry::new();
+        r.register(Box::new(gauge())).unwrap();
+        assert!(r.register(Box::new(gauge())).is_err());
+
+        let r = Registry::with_opts(RegistryOpts::new().unchecked(true)).unwrap();
+        r.register(Box::new(gauge())).unwrap();
+        r.register(Box::new(UncheckedCollector::new(
+            "dyn",
+            "dyn help",
+            proto::MetricType::GAUGE,
+            "b",
+        )))
+        .unwrap();
+        assert!(r.unregister(Box::new(gauge())).is_err());
+
+        let mfs = r.try_gather().unwrap();
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].get_metric().len(), 2);
+
+        // Same label values collected twice.
+        r.register(Box::new(gauge())).unwrap();
+        assert!(r.try_gather().is_err());
+        assert_eq!(r.gather()[0].get_metric().len(), 2);
+    }
+
+    #[test]
+    fn test_unchecked_registry_inconsistent_families() {
+        let table = vec![
+            UncheckedCollector::new("dyn", "dyn help", proto::MetricType::COUNTER, "b"),
+            UncheckedCollector::new("dyn", "other help", proto::MetricType::GAUGE, "b"),
+            UncheckedCollector::new("dyn-name", "dyn help", proto::MetricType::GAUGE, "b"),
+            UncheckedCollector::new("dyn", "dyn help", proto::MetricType::GAUGE, "b-c"),
+            UncheckedCollector::new("dyn", "dyn help", proto::MetricType::HISTOGRAM, "le"),
+        ];
+
+        for c in table {
+            let r = Registry::with_opts(RegistryOpts::new().unchecked(true)).unwrap();
+            r.register(Box::new(UncheckedCollector::new(
+                "dyn",
+                "dyn help",
+                proto::MetricType::GAUGE,
+                "a",
+            )))
+            .unwrap();
+            r.register(Box::new(c)).unwrap();
+
+            assert!(r.try_gather().is_err());
+            let mfs = r.gather();
+            assert_eq!(mfs.len(), 1);
+            assert_eq!(mfs[0].get_metric().len(), 1);
+        }
+    }
+
+    struct LyingCollector {
+        counter: Counter,
+        other: Counter,
+    }
+
+    impl Collector for LyingCollector {
+        fn desc(&self) -> Vec<&Desc> {
+            self.counter.desc()
+        }
+
+        fn collect(&self) -> Vec<proto::MetricFamily> {
+            let mut mfs = self.counter.collect();
+            mfs.extend(self.other.collect());
+            mfs
+        }
+    }
+
+    #[test]
+    fn test_pedantic_registry() {
+        let lying = || LyingCollector {
+            counter: Counter::new("described", "described help").unwrap(),
+            other: Counter::new("undescribed", "undescribed help").unwrap(),
+        };
+
+        let r = Registry::new();
+        r.register(Box::new(lying())).unwrap();
+        assert_eq!(r.try_gather().unwrap().len(), 2);
+
+        let r = Registry::with_opts(RegistryOpts::new().pedantic(true)).unwrap();
+        r.register(Box::new(lying())).unwrap();
+        assert!(r.try_gather().is_err());
+        let mfs = r.gather();
+        assert_eq!(mfs.len(), 1);
+        assert_eq!(mfs[0].name(), "described");
+
+        let r = Registry::with_opts(RegistryOpts::new().pedantic(true)).unwrap();
+        let counter_vec = CounterVec::new(
+            Opts::new("test_vec", "test vec help").const_label("c", "1"),
+            &["a", "b"],
+        )
+        .unwrap();
+        r.register(Box::new(counter_vec.clone())).unwrap();
+        counter_vec.with_label_values(&["1", "2"]).inc();
+        assert_eq!(r.try_gather().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_gather_timestamps() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        use crate::gauge::Gauge;
+
+        let counter = Counter::new("test_counter", "test help").unwrap();
+        let gauge = Gauge::new("test_gauge", "test help").unwrap();
+        gauge.set_with_timestamp(1.0, UNIX_EPOCH + Duration::from_millis(1_000));
+
+        let r = Registry::new();
+        r.register(Box::new(counter.clone())).unwrap();
+        assert_eq!(r.gather()[0].get_metric()[0].timestamp_ms(), 0);
+
+        let r = Registry::with_opts(RegistryOpts::new().gather_timestamps(true)).unwrap();
+        r.register(Box::new(counter)).unwrap();
+        r.register(Box::new(gauge.clone())).unwrap();
+        let before = timer::unix_millis(SystemTime::now());
+        let mfs = r.gather();
+        let after = timer::unix_millis(SystemTime::now());
+        let stamped = mfs[0].get_metric()[0].timestamp_ms();
+        assert!(before <= stamped && stamped <= after);
+        // Explicit timestamps are kept.
+        assert_eq!(mfs[1].get_metric()[0].timestamp_ms(), 1_000);
+
+        gauge.clear_timestamp();
+        let stamped = r.gather()[1].get_metric()[0].timestamp_ms();
+        assert!(stamped >= before);
+    }
+
     #[test]
     fn test_prune_empty_metric_family() {
         let counter_vec =
@@ -0,0 +1,492 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::io;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+
+use crate::errors::{Error, Result};
+use crate::histogram::BUCKET_LABEL;
+use crate::proto::{self, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+use crate::wire::{write_bytes, write_double, write_int64};
+
+/// The version of the remote write protocol implemented.
+pub const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+const REMOTE_WRITE_VERSION_HEADER: &str = "X-Prometheus-Remote-Write-Version";
+const REMOTE_WRITE_CONTENT_TYPE: &str = "application/x-protobuf";
+const METRIC_NAME_LABEL: &str = "__name__";
+const QUANTILE_LABEL: &str = "quantile";
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+const USER_AGENT: &str = concat!("rust-prometheus/", env!("CARGO_PKG_VERSION"));
+
+/// A remote write request, holding the time series to write.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WriteRequest {
+    /// The time series to write.
+    pub timeseries: Vec<TimeSeries>,
+}
+
+/// A time series of a [`WriteRequest`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TimeSeries {
+    /// The labels identifying the series, including `__name__`, sorted by
+    /// name.
+    pub labels: Vec<Label>,
+    /// The samples of the series, in increasing timestamp order.
+    pub samples: Vec<Sample>,
+}
+
+/// A label of a [`TimeSeries`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Label {
+    /// The label name.
+    pub name: String,
+    /// The label value.
+    pub value: String,
+}
+
+/// A sample of a [`TimeSeries`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Sample {
+    /// The sample value.
+    pub value: f64,
+    /// The timestamp of the sample in milliseconds since the Unix epoch.
+    pub timestamp: i64,
+}
+
+impl WriteRequest {
+    /// `from_metric_families` converts the metric families into time series
+    /// with one sample each. Histograms and summaries are expanded into their
+    /// `_bucket`, `_sum` and `_count` series and their quantiles, as in the
+    /// text format. Metrics without their own timestamp are stamped with
+    /// `timestamp_ms`.
+    pub fn from_metric_families(mfs: &[proto::MetricFamily], timestamp_ms: i64) -> WriteRequest {
+        let mut timeseries = Vec::new();
+        for mf in mfs {
+            let name = mf.name();
+            for m in mf.get_metric() {
+                let timestamp = match m.timestamp_ms() {
+                    0 => timestamp_ms,
+                    t => t,
+                };
+                let mut push = |suffix: &str, extra: Option<(&str, String)>, value: f64| {
+                    timeseries.push(new_series(
+                        &format!("{}{}", name, suffix),
+                        m,
+                        extra,
+                        value,
+                        timestamp,
+                    ));
+                };
+
+                match mf.get_field_type() {
+                    MetricType::COUNTER => push("", None, m.get_counter().get_value()),
+                    MetricType::GAUGE => push("", None, m.get_gauge().get_value()),
+                    MetricType::HISTOGRAM => {
+                        let h = m.get_histogram();
+                        let mut inf_seen = false;
+                        for b in h.get_bucket() {
+                            let upper_bound = b.upper_bound();
+                            inf_seen |= upper_bound == f64::INFINITY;
+                            let le = format_float(upper_bound);
+                            push(
+                                "_bucket",
+                                Some((BUCKET_LABEL, le)),
+                                b.cumulative_count() as f64,
+                            );
+                        }
+                        if !inf_seen {
+                            let le = format_float(f64::INFINITY);
+                            push(
+                                "_bucket",
+                                Some((BUCKET_LABEL, le)),
+                                h.get_sample_count() as f64,
+                            );
+                        }
+                        push("_sum", None, h.get_sample_sum());
+                        push("_count", None, h.get_sample_count() as f64);
+                    }
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+                        for q in s.get_quantile() {
+                            let quantile = format_float(q.quantile());
+                            push("", Some((QUANTILE_LABEL, quantile)), q.value());
+                        }
+                        push("_sum", None, s.sample_sum());
+                        push("_count", None, s.sample_count() as f64);
+                    }
+                    MetricType::UNTYPED => {
+                        #[allow(deprecated)]
+                        let value = m.get_untyped().get_value();
+                        push("", None, value);
+                    }
+                }
+            }
+        }
+        WriteRequest { timeseries }
+    }
+
+    /// `encode` encodes the request in the protobuf format.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for ts in &self.timeseries {
+            let mut ts_buf = Vec::new();
+            for l in &ts.labels {
+                let mut l_buf = Vec::new();
+                write_bytes(&mut l_buf, 1, l.name.as_bytes());
+                write_bytes(&mut l_buf, 2, l.value.as_bytes());
+                write_bytes(&mut ts_buf, 1, &l_buf);
+            }
+            for s in &ts.samples {
+                let mut s_buf = Vec::new();
+                write_double(&mut s_buf, 1, s.value);
+                write_int64(&mut s_buf, 2, s.timestamp);
+                write_bytes(&mut ts_buf, 2, &s_buf);
+            }
+            write_bytes(&mut buf, 1, &ts_buf);
+        }
+        buf
+    }
+
+    /// `encode_compressed` encodes the request in the protobuf format and
+    /// compresses it with the snappy block format, as expected by remote
+    /// write receivers.
+    pub fn encode_compressed(&self) -> Result<Vec<u8>> {
+        let buf = snap::raw::Encoder::new()
+            .compress_vec(&self.encode())
+            .map_err(io::Error::from)?;
+        Ok(buf)
+    }
+}
+
+fn new_series(
+    name: &str,
+    m: &proto::Metric,
+    extra: Option<(&str, String)>,
+    value: f64,
+    timestamp: i64,
+) -> TimeSeries {
+    let mut labels: Vec<Label> = m
+        .get_label()
+        .iter()
+        .map(|lp| Label {
+            name: lp.name().to_owned(),
+            value: lp.value().to_owned(),
+        })
+        .collect();
+    labels.push(Label {
+        name: METRIC_NAME_LABEL.to_owned(),
+        value: name.to_owned(),
+    });
+    if let Some((name, value)) = extra {
+        labels.push(Label {
+            name: name.to_owned(),
+            value,
+        });
+    }
+    labels.sort_by(|a, b| a.name.cmp(&b.name));
+
+    TimeSeries {
+        labels,
+        samples: vec![Sample { value, timestamp }],
+    }
+}
+
+fn format_float(f: f64) -> String {
+    if f == f64::INFINITY {
+        "+Inf".to_owned()
+    } else if f == f64::NEG_INFINITY {
+        "-Inf".to_owned()
+    } else {
+        f.to_string()
+    }
+}
+
+/// A client writing metrics to a Prometheus remote write endpoint, e.g. of
+/// Mimir, Thanos or Cortex.
+///
+/// ```no_run
+/// # fn run() -> prometheus::Result<()> {
+/// let client = prometheus::RemoteWriteClient::builder("http://127.0.0.1:9009/api/v1/push")
+///     .header("X-Scope-OrgID", "tenant")
+///     .build()?;
+/// client.write(&prometheus::gather())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct RemoteWriteClient {
+    client: Client,
+    url: String,
+    basic_auth: Option<(String, String)>,
+    bearer_token: Option<String>,
+}
+
+impl RemoteWriteClient {
+    /// `builder` creates a [`RemoteWriteClientBuilder`] for the endpoint at
+    /// `url`.
+    pub fn builder<S: Into<String>>(url: S) -> RemoteWriteClientBuilder {
+        RemoteWriteClientBuilder::new(url)
+    }
+
+    /// `write` converts the metric families, stamping metrics without a
+    /// timestamp with the current time, and sends them.
+    pub fn write(&self, mfs: &[proto::MetricFamily]) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        self.send(&WriteRequest::from_metric_families(mfs, now))
+    }
+
+    /// `send` sends the write request.
+    pub fn send(&self, request: &WriteRequest) -> Result<()> {
+        let mut builder = self
+            .client
+            .post(self.url.as_str())
+            .header(CONTENT_TYPE, REMOTE_WRITE_CONTENT_TYPE)
+            .header(CONTENT_ENCODING, "snappy")
+            .header(REMOTE_WRITE_VERSION_HEADER, REMOTE_WRITE_VERSION)
+            .body(request.encode_compressed()?);
+
+        if let Some((username, password)) = &self.basic_auth {
+            builder = builder.basic_auth(username, Some(password));
+        }
+        if let Some(token) = &self.bearer_token {
+            builder = builder.bearer_auth(token);
+        }
+
+        let response = builder.send()?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Error::PushFailed {
+                status: response.status().as_u16(),
+                url: self.url.clone(),
+            })
+        }
+    }
+}
+
+/// A builder for [`RemoteWriteClient`].
+#[derive(Debug)]
+pub struct RemoteWriteClientBuilder {
+    url: String,
+    timeout: Duration,
+    headers: Vec<(String, String)>,
+    basic_auth: Option<(String, String)>,
+    bearer_token: Option<String>,
+}
+
+impl RemoteWriteClientBuilder {
+    /// `new` creates a builder for the endpoint at `url`, with a 10 second
+    /// request timeout.
+    pub fn new<S: Into<String>>(url: S) -> RemoteWriteClientBuilder {
+        RemoteWriteClientBuilder {
+            url: url.into(),
+            timeout: DEFAULT_TIMEOUT,
+            headers: Vec::new(),
+            basic_auth: None,
+            bearer_token: None,
+        }
+    }
+
+    /// `timeout` sets the timeout of each request.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// `header` adds a header sent with every request.
+    pub fn header<S1: Into<String>, S2: Into<String>>(mut self, name: S1, value: S2) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// `basic_auth` sets the credentials for Basic access authentication.
+    pub fn basic_auth<S1: Into<String>, S2: Into<String>>(
+        mut self,
+        username: S1,
+        password: S2,
+    ) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// `bearer_token` sets the token sent in the `Authorization` header.
+    pub fn bearer_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.bearer_token = Some(token.into());
+        self
+    }
+
+    /// `build` creates the [`RemoteWriteClient`]. It fails if a header is
+    /// invalid.
+    pub fn build(self) -> Result<RemoteWriteClient> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers {
+            let header_name = HeaderName::from_bytes(name.as_bytes());
+            let header_value = HeaderValue::from_str(&value);
+            match (header_name, header_value) {
+                (Ok(n), Ok(v)) => {
+                    headers.append(n, v);
+                }
+                _ => return Err(Error::InvalidHeader { name }),
+            }
+        }
+
+        // The remote write spec requires a user agent, a `User-Agent` header
+        // set on the builder replaces the default one.
+        let client = Client::builder()
+            .timeout(self.timeout)
+            .user_agent(USER_AGENT)
+            .default_headers(headers)
+            .build()?;
+
+        Ok(RemoteWriteClient {
+            client,
+            url: self.url,
+            basic_auth: self.basic_auth,
+            bearer_token: self.bearer_token,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Counter, Histogram, HistogramOpts, Opts, Registry};
+
+    fn label_set(ts: &TimeSeries) -> Vec<(&str, &str)> {
+        ts.labels
+            .iter()
+            .map(|l| (l.name.as_str(), l.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn test_from_metric_families() {
+        let registry = Registry::new();
+        let counter =
+            Counter::with_opts(Opts::new("test_counter", "help").const_label("a", "1")).unwrap();
+        counter.inc_by(2.0);
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("test_histogram", "help").buckets(vec![1.0]))
+                .unwrap();
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        registry.register(Box::new(counter)).unwrap();
+        registry.register(Box::new(histogram)).unwrap();
+
+        let req = WriteRequest::from_metric_families(&registry.gather(), 1000);
+        assert_eq!(req.timeseries.len(), 5);
+        assert_eq!(
+            label_set(&req.timeseries[0]),
+            vec![("__name__", "test_counter"), ("a", "1")]
+        );
+        assert_eq!(
+            req.timeseries[0].samples,
+            vec![Sample {
+                value: 2.0,
+                timestamp: 1000
+            }]
+        );
+
+        let expect = vec![
+            ("test_histogram_bucket", Some("1"), 1.0),
+            ("test_histogram_bucket", Some("+Inf"), 2.0),
+            ("test_histogram_sum", None, 3.5),
+            ("test_histogram_count", None, 2.0),
+        ];
+        for (ts, (name, le, value)) in req.timeseries[1..].iter().zip(expect) {
+            let mut labels = vec![("__name__", name)];
+            if let Some(le) = le {
+                labels.push(("le", le));
+            }
+            assert_eq!(label_set(ts), labels);
+            assert_eq!(ts.samples[0].value, value);
+        }
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_from_untyped_metric_family() {
+        use crate::proto::{Metric, MetricFamily, Untyped};
+
+        let mut untyped = Untyped::default();
+        untyped.set_value(2.5);
+        let mut m = Metric::default();
+        m.set_untyped(untyped);
+        let mut mf = MetricFamily::default();
+        mf.set_name("test_untyped".to_owned());
+        mf.set_field_type(MetricType::UNTYPED);
+        mf.set_metric(vec![m]);
+
+        let req = WriteRequest::from_metric_families(&[mf], 1000);
+        assert_eq!(req.timeseries.len(), 1);
+        assert_eq!(
+            label_set(&req.timeseries[0]),
+            vec![("__name__", "test_untyped")]
+        );
+        assert_eq!(req.timeseries[0].samples[0].value, 2.5);
+    }
+
+    #[test]
+    fn test_user_agent() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api/v1/push", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut head = String::new();
+            while reader.read_line(&mut head).unwrap() > 2 {}
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .unwrap();
+            head.to_lowercase()
+        });
+
+        let client = RemoteWriteClient::builder(url).build().unwrap();
+        client.send(&WriteRequest::default()).unwrap();
+        let head = handle.join().unwrap();
+        let expect = format!(
+            "user-agent: rust-prometheus/{}\r\n",
+            env!("CARGO_PKG_VERSION")
+        );
+        assert!(head.contains(&expect), "{}", head);
+    }
+
+    #[test]
+    fn test_encode() {
+        let req = WriteRequest {
+            timeseries: vec![TimeSeries {
+                labels: vec![Label {
+                    name: "a".to_owned(),
+                    value: "b".to_owned(),
+                }],
+                samples: vec![Sample {
+                    value: 1.0,
+                    timestamp: 300,
+                }],
+            }],
+        };
+        let expect = vec![
+            0x0a, 0x16, // timeseries, 22 bytes
+            0x0a, 0x06, 0x0a, 0x01, b'a', 0x12, 0x01, b'b', // label
+            0x12, 0x0c, 0x09, 0, 0, 0, 0, 0, 0, 0xf0, 0x3f, 0x10, 0xac, 0x02, // sample
+        ];
+        assert_eq!(req.encode(), expect);
+
+        let compressed = req.encode_compressed().unwrap();
+        let decompressed = snap::raw::Decoder::new()
+            .decompress_vec(&compressed)
+            .unwrap();
+        assert_eq!(decompressed, expect);
+    }
+}
@@ -0,0 +1,324 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! Deltas and rates between two gathered snapshots, computed in process like
+//! PromQL's `increase()` and `rate()` over a window of two samples.
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::histogram::bucket_quantile;
+use crate::proto::{self, MetricFamily, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+use crate::registry::Registry;
+
+/// `Snapshot` is the output of a gather together with the time it was taken.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    /// The time of the gather in milliseconds since the Unix epoch.
+    pub timestamp_ms: i64,
+    /// The gathered metric families.
+    pub families: Vec<MetricFamily>,
+}
+
+impl Snapshot {
+    /// `new` creates a snapshot of metric families gathered at
+    /// `timestamp_ms`.
+    pub fn new(families: Vec<MetricFamily>, timestamp_ms: i64) -> Snapshot {
+        Snapshot {
+            timestamp_ms,
+            families,
+        }
+    }
+
+    /// `gather` gathers `registry` and stamps the snapshot with the current
+    /// time.
+    pub fn gather(registry: &Registry) -> Snapshot {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        Snapshot::new(registry.gather(), timestamp_ms)
+    }
+
+    /// `diff` computes the delta of every series in this snapshot since the
+    /// `earlier` one, sorted like the gathered families.
+    ///
+    /// A counter, histogram or summary that went down was reset in between,
+    /// and its increase is its current value, as in PromQL. Such series that
+    /// are missing from `earlier` are counted from zero, while gauges missing
+    /// from `earlier` are skipped.
+    pub fn diff(&self, earlier: &Snapshot) -> Vec<SeriesDelta> {
+        let mut previous = HashMap::new();
+        for mf in &earlier.families {
+            for m in mf.get_metric() {
+                previous.insert((mf.name(), labels(m)), (mf.get_field_type(), m));
+            }
+        }
+
+        let interval = (self.timestamp_ms - earlier.timestamp_ms) as f64 / 1000.0;
+        let mut deltas = vec![];
+        for mf in &self.families {
+            let metric_type = mf.get_field_type();
+            for m in mf.get_metric() {
+                let labels = labels(m);
+                let prev = match previous.get(&(mf.name(), labels.clone())) {
+                    Some(&(t, prev)) if t == metric_type => Some(prev),
+                    _ => None,
+                };
+                if let Some((delta, reset)) = delta(metric_type, m, prev) {
+                    deltas.push(SeriesDelta {
+                        name: mf.name().to_owned(),
+                        labels,
+                        delta,
+                        reset,
+                        interval,
+                    });
+                }
+            }
+        }
+        deltas
+    }
+}
+
+/// The change of a single series between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesDelta {
+    /// The name of the metric family.
+    pub name: String,
+    /// The label pairs of the series, sorted by name.
+    pub labels: Vec<(String, String)>,
+    /// The change of the value.
+    pub delta: Delta,
+    /// Whether a counter, histogram or summary was reset in between.
+    pub reset: bool,
+    /// The time between the snapshots in seconds.
+    pub interval: f64,
+}
+
+impl SeriesDelta {
+    /// `rate` returns the per-second increase of a counter, or the
+    /// per-second number of observations of a histogram or summary. Gauges
+    /// have no rate.
+    pub fn rate(&self) -> Option<f64> {
+        let increase = match self.delta {
+            Delta::Counter(increase) => increase,
+            Delta::Gauge(_) => return None,
+            Delta::Histogram(ref h) => h.count as f64,
+            Delta::Summary { count, .. } => count as f64,
+        };
+        Some(increase / self.interval)
+    }
+
+    /// `label` returns the value of the label `name`, if any.
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// The change of the value of a series.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Delta {
+    /// The increase of a counter.
+    Counter(f64),
+    /// The difference of the values of a gauge.
+    Gauge(f64),
+    /// The observations of a histogram between the snapshots.
+    Histogram(HistogramDelta),
+    /// The observations of a summary between the snapshots. Quantiles can't
+    /// be subtracted, so only the count and the sum are available.
+    Summary {
+        /// The number of observations.
+        count: u64,
+        /// The sum of the observations.
+        sum: f64,
+    },
+}
+
+/// The observations of a histogram between two snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistogramDelta {
+    /// The number of observations.
+    pub count: u64,
+    /// The sum of the observations.
+    pub sum: f64,
+    /// The cumulative number of observations per bucket as pairs of upper
+    /// bound and count, without the +Inf bucket.
+    pub buckets: Vec<(f64, u64)>,
+}
+
+impl HistogramDelta {
+    /// `quantile` estimates the `q`-quantile of the observations, see
+    /// [`histogram_quantile`](crate::histogram_quantile).
+    pub fn quantile(&self, q: f64) -> f64 {
+        bucket_quantile(q, &self.buckets, self.count)
+    }
+
+    /// `mean` returns the mean of the observations, NaN without any.
+    pub fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+}
+
+fn labels(m: &proto::Metric) -> Vec<(String, String)> {
+    let mut labels: Vec<_> = m
+        .get_label()
+        .iter()
+        .map(|lp| (lp.name().to_owned(), lp.value().to_owned()))
+        .collect();
+    labels.sort();
+    labels
+}
+
+fn delta(
+    metric_type: MetricType,
+    m: &proto::Metric,
+    prev: Option<&proto::Metric>,
+) -> Option<(Delta, bool)> {
+    match metric_type {
+        MetricType::COUNTER => {
+            let value = m.get_counter().get_value();
+            let prev = prev.map_or(0.0, |p| p.get_counter().get_value());
+            let reset = value < prev;
+            let increase = if reset { value } else { value - prev };
+            Some((Delta::Counter(increase), reset))
+        }
+        MetricType::GAUGE => {
+            let prev = prev?.get_gauge().get_value();
+            Some((Delta::Gauge(m.get_gauge().get_value() - prev), false))
+        }
+        MetricType::HISTOGRAM => {
+            let h = m.get_histogram();
+            let mut count = h.get_sample_count();
+            let mut sum = h.get_sample_sum();
+            let mut buckets: Vec<(f64, u64)> = h
+                .get_bucket()
+                .iter()
+                .filter(|b| b.upper_bound() != f64::INFINITY)
+                .map(|b| (b.upper_bound(), b.cumulative_count()))
+                .collect();
+
+            let mut reset = false;
+            if let Some(p) = prev {
+                let p = p.get_histogram();
+                let prev_buckets: HashMap<u64, u64> = p
+                    .get_bucket()
+                    .iter()
+                    .map(|b| (b.upper_bound().to_bits(), b.cumulative_count()))
+                    .collect();
+                reset = count < p.get_sample_count()
+                    || buckets.iter().any(|(upper_bound, c)| {
+                        prev_buckets
+                            .get(&upper_bound.to_bits())
+                            .is_some_and(|prev| c < prev)
+                    });
+                if !reset {
+                    count -= p.get_sample_count();
+                    sum -= p.get_sample_sum();
+                    for (upper_bound, c) in buckets.iter_mut() {
+                        *c -= prev_buckets.get(&upper_bound.to_bits()).unwrap_or(&0);
+                    }
+                }
+            }
+            let delta = HistogramDelta {
+                count,
+                sum,
+                buckets,
+            };
+            Some((Delta::Histogram(delta), reset))
+        }
+        MetricType::SUMMARY => {
+            let s = m.get_summary();
+            let (mut count, mut sum) = (s.sample_count(), s.sample_sum());
+            let mut reset = false;
+            if let Some(p) = prev {
+                let p = p.get_summary();
+                reset = count < p.sample_count();
+                if !reset {
+                    count -= p.sample_count();
+                    sum -= p.sample_sum();
+                }
+            }
+            Some((Delta::Summary { count, sum }, reset))
+        }
+        MetricType::UNTYPED => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CounterVec, Gauge, Histogram, HistogramOpts, Opts};
+
+    #[test]
+    fn test_diff() {
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code"]).unwrap();
+        let gauge = Gauge::new("queue", "help").unwrap();
+        let histogram = Histogram::with_opts(
+            HistogramOpts::new("latency", "help").buckets(vec![1.0, 2.0, 4.0]),
+        )
+        .unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+
+        counter.with_label_values(&["200"]).inc_by(10.0);
+        gauge.set(5.0);
+        histogram.observe(0.5);
+        let earlier = Snapshot::new(registry.gather(), 1_000);
+
+        counter.with_label_values(&["200"]).inc_by(20.0);
+        counter.with_label_values(&["500"]).inc();
+        gauge.set(3.0);
+        for v in &[1.5, 1.5, 3.0, 3.0] {
+            histogram.observe(*v);
+        }
+        let later = Snapshot::new(registry.gather(), 11_000);
+
+        let deltas = later.diff(&earlier);
+        assert_eq!(deltas.len(), 4);
+
+        let h = &deltas[0];
+        assert_eq!(h.name, "latency");
+        assert_eq!(h.rate(), Some(0.4));
+        match h.delta {
+            Delta::Histogram(ref h) => {
+                assert_eq!(h.buckets, vec![(1.0, 0), (2.0, 2), (4.0, 4)]);
+                assert_eq!(h.mean(), 2.25);
+                assert_eq!(h.quantile(0.5), 2.0);
+                assert_eq!(h.quantile(0.75), 3.0);
+            }
+            _ => panic!("expected a histogram delta"),
+        }
+
+        assert_eq!(deltas[1].delta, Delta::Gauge(-2.0));
+        assert_eq!(deltas[1].rate(), None);
+        assert_eq!(deltas[2].label("code"), Some("200"));
+        assert_eq!(deltas[2].delta, Delta::Counter(20.0));
+        assert_eq!(deltas[2].rate(), Some(2.0));
+        assert_eq!(deltas[3].label("code"), Some("500"));
+        assert_eq!(deltas[3].delta, Delta::Counter(1.0));
+    }
+
+    #[test]
+    fn test_diff_reset() {
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+
+        counter.with_label_values(&["200"]).inc_by(10.0);
+        let earlier = Snapshot::new(registry.gather(), 0);
+        counter.reset();
+        counter.with_label_values(&["200"]).inc_by(4.0);
+        let later = Snapshot::new(registry.gather(), 2_000);
+
+        let deltas = later.diff(&earlier);
+        assert_eq!(deltas[0].delta, Delta::Counter(4.0));
+        assert!(deltas[0].reset);
+        assert_eq!(deltas[0].rate(), Some(2.0));
+    }
+}
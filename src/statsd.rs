@@ -0,0 +1,498 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::path::Path;
+use std::time::Duration;
+
+use crate::errors::Result;
+use crate::periodic::PeriodicTask;
+use crate::proto::{self, MetricType};
+#[cfg(feature = "protobuf")]
+use crate::proto_ext::MessageFieldExt;
+use crate::registry::Registry;
+
+const QUANTILE_LABEL: &str = "quantile";
+
+/// The line format of a [`StatsdExporter`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsdFormat {
+    /// StatsD with InfluxDB style tags, e.g. `name,key=value:1|c`, as
+    /// understood by Telegraf and the statsd_exporter. Histograms are sent
+    /// with the `h` type.
+    Statsd,
+    /// DogStatsD, e.g. `name:1|c|#key:value`. Histograms are sent as
+    /// distributions with the `d` type.
+    DogStatsd,
+}
+
+/// A struct that bundles the options of a [`StatsdExporter`].
+#[derive(Clone, Debug)]
+pub struct StatsdOpts {
+    /// The line format.
+    pub format: StatsdFormat,
+    /// A prefix joined to every metric name with a '.'.
+    pub prefix: String,
+    /// Tags added to every line.
+    pub tags: Vec<(String, String)>,
+    /// The maximum size of a datagram. Lines are batched into datagrams up to
+    /// this size.
+    pub max_packet_size: usize,
+}
+
+impl StatsdOpts {
+    /// `new` creates the options for `format`, without prefix or tags, and
+    /// with a maximum packet size of 1432 bytes, which fits into an Ethernet
+    /// frame.
+    pub fn new(format: StatsdFormat) -> StatsdOpts {
+        StatsdOpts {
+            format,
+            prefix: String::new(),
+            tags: Vec::new(),
+            max_packet_size: 1432,
+        }
+    }
+
+    /// `prefix` sets the prefix.
+    pub fn prefix<S: Into<String>>(mut self, prefix: S) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// `tag` adds a tag to every line.
+    pub fn tag<S1: Into<String>, S2: Into<String>>(mut self, key: S1, value: S2) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// `max_packet_size` sets the maximum size of a datagram.
+    pub fn max_packet_size(mut self, size: usize) -> Self {
+        self.max_packet_size = size;
+        self
+    }
+}
+
+#[derive(Debug)]
+enum Sink {
+    Udp(UdpSocket),
+    #[cfg(unix)]
+    Unix(UnixDatagram),
+}
+
+/// An exporter sending gathered metrics as StatsD or DogStatsD lines over UDP
+/// or a Unix datagram socket.
+///
+/// Labels become tags. Counters are sent as deltas since the last flush, and
+/// gauges and untyped metrics as their current value. Histograms are sent as distributions: the
+/// observations since the last flush are reported at the upper bound of their
+/// bucket, the `+Inf` bucket at the highest finite bound, using the sample
+/// rate to send all observations of a bucket at once. Summaries are sent as
+/// quantile gauges with counter deltas for `_sum` and `_count`. Samples with
+/// a non-finite value are skipped.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use prometheus::{StatsdExporter, StatsdFormat, StatsdOpts};
+///
+/// let opts = StatsdOpts::new(StatsdFormat::DogStatsd).tag("env", "prod");
+/// let exporter = StatsdExporter::udp("127.0.0.1:8125", opts).unwrap();
+/// let task = exporter.start(prometheus::default_registry(), Duration::from_secs(10));
+/// // Do the work.
+/// task.shutdown().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct StatsdExporter {
+    sink: Sink,
+    opts: StatsdOpts,
+    counters: HashMap<String, f64>,
+    histograms: HashMap<String, Vec<u64>>,
+}
+
+impl StatsdExporter {
+    /// `udp` creates an exporter sending to the StatsD server at `addr`.
+    pub fn udp<A: ToSocketAddrs>(addr: A, opts: StatsdOpts) -> Result<StatsdExporter> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no address to send to"))?;
+        let local: SocketAddr = match addr {
+            SocketAddr::V4(_) => ([0, 0, 0, 0], 0).into(),
+            SocketAddr::V6(_) => ([0u16; 8], 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        Ok(StatsdExporter::new(Sink::Udp(socket), opts))
+    }
+
+    /// `unix` creates an exporter sending to the Unix datagram socket at
+    /// `path`, e.g. `/var/run/datadog/dsd.socket`.
+    #[cfg(unix)]
+    pub fn unix<P: AsRef<Path>>(path: P, opts: StatsdOpts) -> Result<StatsdExporter> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(StatsdExporter::new(Sink::Unix(socket), opts))
+    }
+
+    fn new(sink: Sink, opts: StatsdOpts) -> StatsdExporter {
+        StatsdExporter {
+            sink,
+            opts,
+            counters: HashMap::new(),
+            histograms: HashMap::new(),
+        }
+    }
+
+    /// `flush` renders the metric families and sends the lines, batched into
+    /// datagrams.
+    pub fn flush(&mut self, mfs: &[proto::MetricFamily]) -> Result<()> {
+        let max = self.opts.max_packet_size;
+        let mut packet = String::new();
+        for line in self.render(mfs) {
+            if !packet.is_empty() && packet.len() + 1 + line.len() > max {
+                self.send(&packet)?;
+                packet.clear();
+            }
+            if !packet.is_empty() {
+                packet.push('\n');
+            }
+            packet.push_str(&line);
+        }
+        if !packet.is_empty() {
+            self.send(&packet)?;
+        }
+        Ok(())
+    }
+
+    /// `start` flushes the metrics gathered from `registry` every `interval`
+    /// from a background thread.
+    pub fn start(mut self, registry: &Registry, interval: Duration) -> PeriodicTask {
+        let registry = registry.clone();
+        PeriodicTask::spawn("prometheus-statsd", interval, move || {
+            self.flush(&registry.gather())
+        })
+    }
+
+    fn send(&self, packet: &str) -> Result<()> {
+        match &self.sink {
+            Sink::Udp(socket) => socket.send(packet.as_bytes())?,
+            #[cfg(unix)]
+            Sink::Unix(socket) => socket.send(packet.as_bytes())?,
+        };
+        Ok(())
+    }
+
+    /// `render` renders the metric families as lines, and remembers the
+    /// counter and histogram values to compute the next deltas.
+    pub fn render(&mut self, mfs: &[proto::MetricFamily]) -> Vec<String> {
+        let mut lines = Vec::new();
+        for mf in mfs {
+            let name = mf.name();
+            for m in mf.get_metric() {
+                match mf.get_field_type() {
+                    MetricType::COUNTER => {
+                        let delta = self.counter_delta(name, m, m.get_counter().get_value());
+                        lines.extend(self.line(name, m, None, delta, "c", 1));
+                    }
+                    MetricType::GAUGE => {
+                        self.push_gauge(&mut lines, name, m, None, m.get_gauge().get_value());
+                    }
+                    MetricType::HISTOGRAM => self.push_histogram(&mut lines, name, m),
+                    MetricType::SUMMARY => {
+                        let s = m.get_summary();
+                        for q in s.get_quantile() {
+                            let quantile = (QUANTILE_LABEL, q.quantile().to_string());
+                            self.push_gauge(&mut lines, name, m, Some(quantile), q.value());
+                        }
+                        let sum_name = format!("{}_sum", name);
+                        let delta = self.counter_delta(&sum_name, m, s.sample_sum());
+                        lines.extend(self.line(&sum_name, m, None, delta, "c", 1));
+                        let count_name = format!("{}_count", name);
+                        let delta = self.counter_delta(&count_name, m, s.sample_count() as f64);
+                        lines.extend(self.line(&count_name, m, None, delta, "c", 1));
+                    }
+                    MetricType::UNTYPED => {
+                        #[allow(deprecated)]
+                        let value = m.get_untyped().get_value();
+                        self.push_gauge(&mut lines, name, m, None, value);
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    fn counter_delta(&mut self, name: &str, m: &proto::Metric, value: f64) -> f64 {
+        let last = self.counters.insert(series_key(name, m), value);
+        match last {
+            // A counter lower than before has been reset.
+            Some(last) if last <= value => value - last,
+            _ => value,
+        }
+    }
+
+    fn push_gauge(
+        &self,
+        lines: &mut Vec<String>,
+        name: &str,
+        m: &proto::Metric,
+        extra: Option<(&str, String)>,
+        value: f64,
+    ) {
+        if !value.is_finite() {
+            return;
+        }
+        // A signed gauge value changes the gauge by the value instead of
+        // setting it, so negative values are set by resetting to 0 first.
+        if value < 0.0 {
+            lines.extend(self.line(name, m, extra.clone(), 0.0, "g", 1));
+        }
+        lines.extend(self.line(name, m, extra, value, "g", 1));
+    }
+
+    fn push_histogram(&mut self, lines: &mut Vec<String>, name: &str, m: &proto::Metric) {
+        let h = m.get_histogram();
+        let mut bounds = Vec::new();
+        let mut counts = Vec::new();
+        for b in h.get_bucket() {
+            if b.upper_bound() == f64::INFINITY {
+                break;
+            }
+            bounds.push(b.upper_bound());
+            counts.push(b.cumulative_count());
+        }
+        counts.push(h.get_sample_count());
+
+        let last = self
+            .histograms
+            .insert(series_key(name, m), counts.clone())
+            .filter(|last| {
+                last.len() == counts.len() && last[last.len() - 1] <= h.get_sample_count()
+            })
+            .unwrap_or_else(|| vec![0; counts.len()]);
+
+        let metric_type = match self.opts.format {
+            StatsdFormat::Statsd => "h",
+            StatsdFormat::DogStatsd => "d",
+        };
+        let mut prev_new = 0;
+        for (i, (count, last)) in counts.iter().zip(&last).enumerate() {
+            let cumulative_new = count.saturating_sub(*last);
+            let n = cumulative_new.saturating_sub(prev_new);
+            prev_new = cumulative_new;
+            if n == 0 {
+                continue;
+            }
+            let value = bounds
+                .get(i)
+                .or_else(|| bounds.last())
+                .copied()
+                .unwrap_or(0.0);
+            lines.extend(self.line(name, m, None, value, metric_type, n));
+        }
+    }
+
+    fn line(
+        &self,
+        name: &str,
+        m: &proto::Metric,
+        extra: Option<(&str, String)>,
+        value: f64,
+        metric_type: &str,
+        samples: u64,
+    ) -> Option<String> {
+        // Neither StatsD nor DogStatsD can parse NaN or infinities.
+        if !value.is_finite() {
+            return None;
+        }
+        let mut tags: Vec<(String, String)> = m
+            .get_label()
+            .iter()
+            .map(|lp| (sanitize(lp.name()), sanitize(lp.value())))
+            .collect();
+        if let Some((k, v)) = extra {
+            tags.push((k.to_owned(), sanitize(&v)));
+        }
+        for (k, v) in &self.opts.tags {
+            tags.push((sanitize(k), sanitize(v)));
+        }
+
+        let mut line = String::new();
+        if !self.opts.prefix.is_empty() {
+            line.push_str(&sanitize(&self.opts.prefix));
+            line.push('.');
+        }
+        line.push_str(&sanitize(name));
+        if self.opts.format == StatsdFormat::Statsd {
+            for (k, v) in &tags {
+                line.push_str(&format!(",{}={}", k, v));
+            }
+        }
+        line.push_str(&format!(":{}|{}", value, metric_type));
+        if samples > 1 {
+            line.push_str(&format!("|@{}", 1.0 / samples as f64));
+        }
+        if self.opts.format == StatsdFormat::DogStatsd && !tags.is_empty() {
+            let tags: Vec<String> = tags.iter().map(|(k, v)| format!("{}:{}", k, v)).collect();
+            line.push_str("|#");
+            line.push_str(&tags.join(","));
+        }
+        Some(line)
+    }
+}
+
+fn series_key(name: &str, m: &proto::Metric) -> String {
+    let mut key = name.to_owned();
+    for lp in m.get_label() {
+        key.push('\u{ff}');
+        key.push_str(lp.name());
+        key.push('\u{ff}');
+        key.push_str(lp.value());
+    }
+    key
+}
+
+/// `sanitize` replaces the characters separating the parts of a line.
+fn sanitize(s: &str) -> String {
+    s.replace([':', '|', '@', ',', '#', '=', '\n'], "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CounterVec, Gauge, Histogram, HistogramOpts, Opts};
+
+    fn exporter(format: StatsdFormat) -> (StatsdExporter, UdpSocket) {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+        let opts = StatsdOpts::new(format).prefix("app").tag("env", "test");
+        let exporter = StatsdExporter::udp(server.local_addr().unwrap(), opts).unwrap();
+        (exporter, server)
+    }
+
+    #[test]
+    fn test_counter_deltas() {
+        let (mut exporter, _) = exporter(StatsdFormat::DogStatsd);
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+
+        counter.with_label_values(&["200"]).inc_by(2.0);
+        assert_eq!(
+            exporter.render(&registry.gather()),
+            vec!["app.requests:2|c|#code:200,env:test"]
+        );
+        counter.with_label_values(&["200"]).inc_by(3.0);
+        assert_eq!(
+            exporter.render(&registry.gather()),
+            vec!["app.requests:3|c|#code:200,env:test"]
+        );
+    }
+
+    #[test]
+    fn test_gauge_and_histogram() {
+        let (mut exporter, _) = exporter(StatsdFormat::Statsd);
+        let registry = Registry::new();
+        let gauge = Gauge::new("temperature", "help").unwrap();
+        let histogram =
+            Histogram::with_opts(HistogramOpts::new("latency", "help").buckets(vec![1.0, 2.0]))
+                .unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+
+        gauge.set(-3.0);
+        for v in [0.5, 0.7, 1.5, 5.0] {
+            histogram.observe(v);
+        }
+        assert_eq!(
+            exporter.render(&registry.gather()),
+            vec![
+                "app.latency,env=test:1|h|@0.5",
+                "app.latency,env=test:2|h",
+                "app.latency,env=test:2|h",
+                "app.temperature,env=test:0|g",
+                "app.temperature,env=test:-3|g",
+            ]
+        );
+
+        gauge.set(1.0);
+        histogram.observe(1.5);
+        assert_eq!(
+            exporter.render(&registry.gather()),
+            vec!["app.latency,env=test:2|h", "app.temperature,env=test:1|g",]
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn test_untyped() {
+        use crate::proto::{Metric, MetricFamily, Untyped};
+
+        let (mut exporter, _) = exporter(StatsdFormat::Statsd);
+        let mut untyped = Untyped::default();
+        untyped.set_value(2.5);
+        let mut m = Metric::default();
+        m.set_untyped(untyped);
+        let mut mf = MetricFamily::default();
+        mf.set_name("queue".to_owned());
+        mf.set_field_type(MetricType::UNTYPED);
+        mf.set_metric(vec![m]);
+
+        assert_eq!(exporter.render(&[mf]), vec!["app.queue,env=test:2.5|g"]);
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let (mut exporter, _) = exporter(StatsdFormat::Statsd);
+        let registry = Registry::new();
+        let gauge = Gauge::new("temperature", "help").unwrap();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code"]).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+
+        counter.with_label_values(&["200"]).inc_by(f64::INFINITY);
+        for v in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            gauge.set(v);
+            assert_eq!(exporter.render(&registry.gather()), Vec::<String>::new());
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let (mut exporter, server) = exporter(StatsdFormat::DogStatsd);
+        exporter.opts.max_packet_size = 40;
+        let registry = Registry::new();
+        let counter = CounterVec::new(Opts::new("requests", "help"), &["code"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        counter.with_label_values(&["200"]).inc();
+        counter.with_label_values(&["500"]).inc();
+
+        exporter.flush(&registry.gather()).unwrap();
+        let mut buf = [0; 1024];
+        for code in ["200", "500"] {
+            let n = server.recv(&mut buf).unwrap();
+            let expect = format!("app.requests:1|c|#code:{},env:test", code);
+            assert_eq!(std::str::from_utf8(&buf[..n]).unwrap(), expect);
+        }
+    }
+
+    #[test]
+    fn test_start() {
+        let (exporter, server) = exporter(StatsdFormat::DogStatsd);
+        let registry = Registry::new();
+        let gauge = Gauge::new("temperature", "help").unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+        gauge.set(2.0);
+
+        let task = exporter.start(&registry, Duration::from_secs(3600));
+        task.shutdown().unwrap();
+        let mut buf = [0; 1024];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"app.temperature:2|g|#env:test");
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use lazy_static::lazy_static;
 
@@ -16,6 +16,13 @@ pub fn duration_to_millis(dur: Duration) -> u64 {
     dur.as_secs() * 1000 + dur.subsec_millis() as u64
 }
 
+/// Convert a time to milliseconds since the Unix epoch, 0 for earlier times.
+pub fn unix_millis(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
 /// Returns milliseconds since ANCHOR.
 ///
 /// ANCHOR is some fixed point in history.
@@ -43,7 +50,9 @@ lazy_static! {
     static ref UPDATER_IS_RUNNING: AtomicBool = AtomicBool::new(false);
 }
 
-const CHECK_UPDATE_INTERVAL: Duration = Duration::from_millis(200);
+/// The interval at which the background updater refreshes the time returned
+/// by [`recent_millis`].
+pub const CHECK_UPDATE_INTERVAL: Duration = Duration::from_millis(200);
 
 /// Ensures background updater is running, which will call `now_millis` periodically.
 pub fn ensure_updater() {
@@ -63,7 +72,6 @@ pub fn ensure_updater() {
 
 #[cfg(test)]
 mod tests {
-    use std::thread;
     use std::time::Duration;
 
     #[test]
@@ -74,14 +82,4 @@ mod tests {
             assert_eq!(super::duration_to_millis(dur), exp);
         }
     }
-
-    #[test]
-    fn test_time_update() {
-        assert_eq!(super::recent_millis(), 0);
-        let now = super::now_millis();
-        assert_eq!(super::recent_millis(), now);
-        super::ensure_updater();
-        thread::sleep(super::CHECK_UPDATE_INTERVAL * 2);
-        assert!(super::recent_millis() > now);
-    }
 }
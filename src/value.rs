@@ -1,7 +1,11 @@
 // Copyright 2014 The Prometheus Authors
 // Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
 
-use crate::atomic64::{Atomic, Number};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::atomic64::{Atomic, AtomicMinMax, Number};
 use crate::desc::{Desc, Describer};
 use crate::errors::{Error, Result};
 use crate::proto::{Counter, Gauge, LabelPair, Metric, MetricFamily, MetricType};
@@ -34,6 +38,11 @@ pub struct Value<P: Atomic> {
     pub val: P,
     pub val_type: ValueType,
     pub label_pairs: Vec<LabelPair>,
+    /// The time the value was created or last reset, exposed for counters.
+    pub created: Mutex<SystemTime>,
+    /// The explicit timestamp of the value in milliseconds since the Unix
+    /// epoch, 0 if unset.
+    pub timestamp_ms: AtomicI64,
 }
 
 impl<P: Atomic> Value<P> {
@@ -51,6 +60,8 @@ impl<P: Atomic> Value<P> {
             val: P::new(val),
             val_type,
             label_pairs,
+            created: Mutex::new(SystemTime::now()),
+            timestamp_ms: AtomicI64::new(0),
         })
     }
 
@@ -64,6 +75,18 @@ impl<P: Atomic> Value<P> {
         self.val.set(val);
     }
 
+    /// Set the explicit timestamp of the value, 0 to unset it.
+    pub fn set_timestamp_ms(&self, timestamp_ms: i64) {
+        self.timestamp_ms.store(timestamp_ms, Ordering::Relaxed);
+    }
+
+    /// Set the value back to zero and restart it from now.
+    pub fn reset(&self) {
+        let mut created = self.created.lock().expect("Lock poisoned");
+        self.val.set(P::T::from_i64(0));
+        *created = SystemTime::now();
+    }
+
     #[inline]
     pub fn inc_by(&self, val: P::T) {
         self.val.inc_by(val);
@@ -84,14 +107,36 @@ impl<P: Atomic> Value<P> {
         self.val.dec_by(val)
     }
 
+    #[inline]
+    pub fn set_max(&self, val: P::T)
+    where
+        P: AtomicMinMax,
+    {
+        self.val.set_max(val)
+    }
+
+    #[inline]
+    pub fn set_min(&self, val: P::T)
+    where
+        P: AtomicMinMax,
+    {
+        self.val.set_min(val)
+    }
+
     pub fn metric(&self) -> Metric {
         let mut m = Metric::from_label(self.label_pairs.clone());
+        let timestamp_ms = self.timestamp_ms.load(Ordering::Relaxed);
+        if timestamp_ms != 0 {
+            m.set_timestamp_ms(timestamp_ms);
+        }
 
         let val = self.get();
         match self.val_type {
             ValueType::Counter => {
                 let mut counter = Counter::default();
                 counter.set_value(val.into_f64());
+                counter
+                    .set_created_timestamp((*self.created.lock().expect("Lock poisoned")).into());
                 m.set_counter(counter);
             }
             ValueType::Gauge => {
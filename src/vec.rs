@@ -32,7 +32,7 @@ pub(crate) struct MetricVecCore<T: MetricVecBuilder> {
     pub desc: Desc,
     pub metric_type: MetricType,
     pub new_metric: T,
-    pub opts: T::P,
+    pub opts: RwLock<T::P>,
 }
 
 impl<T: MetricVecBuilder> MetricVecCore<T> {
@@ -86,7 +86,9 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
 
         let mut children = self.children.write();
         if children.remove(&h).is_none() {
-            return Err(Error::Msg(format!("missing label values {:?}", vals)));
+            return Err(Error::MissingLabelValues {
+                values: vals.iter().map(|v| v.as_ref().to_owned()).collect(),
+            });
         }
 
         Ok(())
@@ -100,7 +102,10 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
 
         let mut children = self.children.write();
         if children.remove(&h).is_none() {
-            return Err(Error::Msg(format!("missing labels {:?}", labels)));
+            let values = self.get_label_values(labels)?;
+            return Err(Error::MissingLabelValues {
+                values: values.into_iter().map(str::to_owned).collect(),
+            });
         }
 
         Ok(())
@@ -146,10 +151,7 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
             match labels.get(&name.as_ref()) {
                 Some(val) => h.write(val.as_ref().as_bytes()),
                 None => {
-                    return Err(Error::Msg(format!(
-                        "label name {} missing in label map",
-                        name
-                    )));
+                    return Err(Error::MissingLabel { name: name.clone() });
                 }
             }
         }
@@ -169,10 +171,7 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
             match labels.get(&name.as_ref()) {
                 Some(val) => values.push(val.as_ref()),
                 None => {
-                    return Err(Error::Msg(format!(
-                        "label name {} missing in label map",
-                        name
-                    )));
+                    return Err(Error::MissingLabel { name: name.clone() });
                 }
             }
         }
@@ -189,7 +188,7 @@ impl<T: MetricVecBuilder> MetricVecCore<T> {
             return Ok(metric);
         }
 
-        let metric = self.new_metric.build(&self.opts, label_values)?;
+        let metric = self.new_metric.build(&self.opts.read(), label_values)?;
         children.insert(hash, metric.clone());
         Ok(metric)
     }
@@ -221,7 +220,7 @@ impl<T: MetricVecBuilder> MetricVec<T> {
             desc,
             metric_type,
             new_metric,
-            opts,
+            opts: RwLock::new(opts),
         };
 
         Ok(MetricVec { v: Arc::new(v) })
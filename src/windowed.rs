@@ -0,0 +1,413 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+use std::sync::atomic::{AtomicU64 as StdAtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::atomic64::{Atomic, AtomicF64, AtomicU64};
+use crate::counter::Counter;
+use crate::desc::Desc;
+use crate::errors::{Error, Result};
+use crate::histogram::{BucketLayout, Histogram, HistogramOpts};
+use crate::metrics::{Collector, Metric, Opts};
+use crate::proto;
+use crate::snapshot::HistogramDelta;
+use crate::timer;
+
+/// One time slice of a [`Ring`], holding the observations of the slice with
+/// index `epoch` since the timer anchor.
+#[derive(Debug)]
+struct Slice {
+    epoch: StdAtomicU64,
+    count: AtomicU64,
+    sum: AtomicF64,
+    buckets: Vec<AtomicU64>,
+}
+
+/// The sums of the slices within the window.
+struct Totals {
+    count: u64,
+    sum: f64,
+    buckets: Vec<u64>,
+    seconds: f64,
+}
+
+/// A ring of time slices covering the last `window`. Slices are reused once
+/// they fall out of the window.
+///
+/// Time is read with [`timer::recent_millis`] by default, so the window moves
+/// in steps of the timer updater.
+#[derive(Debug)]
+struct Ring {
+    slice_millis: u64,
+    created_millis: u64,
+    slices: Vec<Slice>,
+    reset_lock: Mutex<()>,
+    now: fn() -> u64,
+}
+
+impl Ring {
+    /// `new` creates a ring reading the coarse clock of [`timer`], and starts
+    /// its updater.
+    fn new(window: Duration, slices: usize, num_buckets: usize) -> Result<Ring> {
+        timer::ensure_updater();
+        // Refresh the coarse clock, so that the ring starts at the current
+        // time.
+        timer::now_millis();
+        Ring::with_clock(window, slices, num_buckets, timer::recent_millis)
+    }
+
+    /// `with_clock` creates a ring reading the time in milliseconds with
+    /// `now`.
+    fn with_clock(
+        window: Duration,
+        slices: usize,
+        num_buckets: usize,
+        now: fn() -> u64,
+    ) -> Result<Ring> {
+        let slice_millis = timer::duration_to_millis(window) / slices.max(1) as u64;
+        if slices == 0 || slice_millis == 0 {
+            return Err(Error::InvalidWindow { window, slices });
+        }
+
+        let created_millis = now();
+        let epoch = created_millis / slice_millis;
+        let slices = (0..slices)
+            .map(|_| Slice {
+                epoch: StdAtomicU64::new(epoch),
+                count: AtomicU64::new(0),
+                sum: AtomicF64::new(0.0),
+                buckets: (0..num_buckets).map(|_| AtomicU64::new(0)).collect(),
+            })
+            .collect();
+
+        Ok(Ring {
+            slice_millis,
+            created_millis,
+            slices,
+            reset_lock: Mutex::new(()),
+            now,
+        })
+    }
+
+    /// Returns the slice of the current time, resetting it first if it still
+    /// holds an older slice.
+    fn current(&self) -> &Slice {
+        let epoch = (self.now)() / self.slice_millis;
+        let slice = &self.slices[(epoch % self.slices.len() as u64) as usize];
+        if slice.epoch.load(Ordering::Acquire) < epoch {
+            let _guard = self.reset_lock.lock().expect("Lock poisoned");
+            if slice.epoch.load(Ordering::Acquire) < epoch {
+                slice.count.set(0);
+                slice.sum.set(0.0);
+                for b in &slice.buckets {
+                    b.set(0);
+                }
+                slice.epoch.store(epoch, Ordering::Release);
+            }
+        }
+        slice
+    }
+
+    fn observe(&self, v: f64, bucket: Option<usize>) {
+        let slice = self.current();
+        if let Some(i) = bucket {
+            slice.buckets[i].inc_by(1);
+        }
+        slice.sum.inc_by(v);
+        slice.count.inc_by(1);
+    }
+
+    fn totals(&self) -> Totals {
+        let now = (self.now)();
+        let epoch = now / self.slice_millis;
+        let len = self.slices.len() as u64;
+
+        let mut totals = Totals {
+            count: 0,
+            sum: 0.0,
+            buckets: vec![0; self.slices[0].buckets.len()],
+            seconds: 0.0,
+        };
+        for slice in &self.slices {
+            let slice_epoch = slice.epoch.load(Ordering::Acquire);
+            if slice_epoch > epoch || epoch - slice_epoch >= len {
+                continue;
+            }
+            totals.count += slice.count.get();
+            totals.sum += slice.sum.get();
+            for (total, b) in totals.buckets.iter_mut().zip(&slice.buckets) {
+                *total += b.get();
+            }
+        }
+
+        // The window ends now, within the current slice, and starts no
+        // earlier than the creation of the ring.
+        let start = ((epoch + 1).saturating_sub(len) * self.slice_millis).max(self.created_millis);
+        totals.seconds = now.saturating_sub(start) as f64 / 1000.0;
+        totals
+    }
+}
+
+/// A [`Histogram`] that additionally keeps the observations of the last
+/// `window` in a ring of time slices.
+///
+/// It is collected as the usual cumulative histogram, while
+/// [`window`](WindowedHistogram::window) gives the count, sum and quantiles
+/// of only the recent observations, e.g. for local latency percentiles. The
+/// window keeps the buckets the histogram had at creation.
+#[derive(Clone, Debug)]
+pub struct WindowedHistogram {
+    histogram: Histogram,
+    layout: Arc<BucketLayout>,
+    ring: Arc<Ring>,
+}
+
+impl WindowedHistogram {
+    /// `with_opts` creates a [`WindowedHistogram`] covering the last
+    /// `window`, split into `slices` time slices. More slices make the window
+    /// move more smoothly at the cost of memory.
+    pub fn with_opts(
+        opts: HistogramOpts,
+        window: Duration,
+        slices: usize,
+    ) -> Result<WindowedHistogram> {
+        WindowedHistogram::with_ring(opts, |num_buckets| Ring::new(window, slices, num_buckets))
+    }
+
+    /// `with_ring` creates the histogram with the ring returned by `ring` for
+    /// the number of buckets.
+    fn with_ring<F>(opts: HistogramOpts, ring: F) -> Result<WindowedHistogram>
+    where
+        F: FnOnce(usize) -> Result<Ring>,
+    {
+        let histogram = Histogram::with_opts(opts)?;
+        let layout = histogram.layout();
+        let ring = ring(layout.upper_bounds.len())?;
+        Ok(WindowedHistogram {
+            histogram,
+            layout,
+            ring: Arc::new(ring),
+        })
+    }
+
+    /// Add a single observation.
+    pub fn observe(&self, v: f64) {
+        self.histogram.observe(v);
+        let bucket = self.layout.find(v);
+        self.ring.observe(v, bucket);
+    }
+
+    /// Return the cumulative [`Histogram`].
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// Return the observations within the window. Use
+    /// [`HistogramDelta::quantile`] for their quantiles.
+    pub fn window(&self) -> HistogramDelta {
+        let totals = self.ring.totals();
+        let mut cumulative_count = 0;
+        let buckets = self
+            .layout
+            .upper_bounds
+            .iter()
+            .zip(totals.buckets)
+            .map(|(&upper_bound, count)| {
+                cumulative_count += count;
+                (upper_bound, cumulative_count)
+            })
+            .collect();
+        HistogramDelta {
+            count: totals.count,
+            sum: totals.sum,
+            buckets,
+        }
+    }
+
+    /// Return the per-second number of observations within the window.
+    ///
+    /// The window spans no time right after the histogram was created, until the
+    /// coarse clock of [`timer`](crate::timer) advances, and the rate is 0.
+    pub fn rate(&self) -> f64 {
+        let totals = self.ring.totals();
+        if totals.seconds == 0.0 {
+            return 0.0;
+        }
+        totals.count as f64 / totals.seconds
+    }
+}
+
+impl Metric for WindowedHistogram {
+    fn metric(&self) -> proto::Metric {
+        self.histogram.metric()
+    }
+}
+
+impl Collector for WindowedHistogram {
+    fn desc(&self) -> Vec<&Desc> {
+        self.histogram.desc()
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        self.histogram.collect()
+    }
+}
+
+/// A [`Counter`] that additionally keeps the increments of the last `window`
+/// in a ring of time slices, to compute its recent rate.
+#[derive(Clone, Debug)]
+pub struct WindowedCounter {
+    counter: Counter,
+    ring: Arc<Ring>,
+}
+
+impl WindowedCounter {
+    /// `with_opts` creates a [`WindowedCounter`] covering the last `window`,
+    /// split into `slices` time slices.
+    pub fn with_opts(opts: Opts, window: Duration, slices: usize) -> Result<WindowedCounter> {
+        WindowedCounter::with_ring(opts, Ring::new(window, slices, 0)?)
+    }
+
+    fn with_ring(opts: Opts, ring: Ring) -> Result<WindowedCounter> {
+        Ok(WindowedCounter {
+            counter: Counter::with_opts(opts)?,
+            ring: Arc::new(ring),
+        })
+    }
+
+    /// Increase the counter by 1.
+    pub fn inc(&self) {
+        self.inc_by(1.0);
+    }
+
+    /// Increase the counter by `v`.
+    ///
+    /// # Panics
+    ///
+    /// Panics in debug build if the value is < 0.
+    pub fn inc_by(&self, v: f64) {
+        self.counter.inc_by(v);
+        self.ring.observe(v, None);
+    }
+
+    /// Return the cumulative [`Counter`].
+    pub fn counter(&self) -> &Counter {
+        &self.counter
+    }
+
+    /// Return the increase of the counter within the window.
+    pub fn increase(&self) -> f64 {
+        self.ring.totals().sum
+    }
+
+    /// Return the per-second increase of the counter within the window.
+    ///
+    /// The window spans no time right after the counter was created, until the
+    /// coarse clock of [`timer`](crate::timer) advances, and the rate is 0.
+    pub fn rate(&self) -> f64 {
+        let totals = self.ring.totals();
+        if totals.seconds == 0.0 {
+            return 0.0;
+        }
+        totals.sum / totals.seconds
+    }
+}
+
+impl Metric for WindowedCounter {
+    fn metric(&self) -> proto::Metric {
+        self.counter.metric()
+    }
+}
+
+impl Collector for WindowedCounter {
+    fn desc(&self) -> Vec<&Desc> {
+        self.counter.desc()
+    }
+
+    fn collect(&self) -> Vec<proto::MetricFamily> {
+        self.counter.collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    thread_local! {
+        static NOW: Cell<u64> = const { Cell::new(10_000) };
+    }
+
+    fn mock_now() -> u64 {
+        NOW.with(Cell::get)
+    }
+
+    fn advance(millis: u64) {
+        NOW.with(|now| now.set(now.get() + millis));
+    }
+
+    #[test]
+    fn test_windowed_histogram() {
+        let opts = HistogramOpts::new("test_histogram", "test help").buckets(vec![1.0, 2.0, 4.0]);
+        let histogram = WindowedHistogram::with_ring(opts, |num_buckets| {
+            Ring::with_clock(Duration::from_millis(400), 2, num_buckets, mock_now)
+        })
+        .unwrap();
+        for v in &[0.5, 1.5, 1.5, 3.0] {
+            histogram.observe(*v);
+        }
+
+        let window = histogram.window();
+        assert_eq!(window.count, 4);
+        assert_eq!(window.sum, 6.5);
+        assert_eq!(window.buckets, vec![(1.0, 1), (2.0, 3), (4.0, 4)]);
+        assert_eq!(window.quantile(0.5), 1.5);
+
+        // The previous slice is still within the window.
+        advance(200);
+        histogram.observe(3.0);
+        assert_eq!(histogram.window().count, 5);
+
+        // Old slices fall out of the window, the cumulative histogram keeps
+        // everything.
+        advance(1000);
+        histogram.observe(3.0);
+        assert_eq!(histogram.window().count, 1);
+        assert_eq!(histogram.histogram().get_sample_count(), 6);
+        let mfs = histogram.collect();
+        assert_eq!(mfs[0].get_metric()[0].get_histogram().get_sample_count(), 6);
+
+        let err = WindowedHistogram::with_opts(
+            HistogramOpts::new("test_histogram", "test help"),
+            Duration::from_millis(1),
+            2,
+        )
+        .unwrap_err();
+        assert!(
+            matches!(err, Error::InvalidWindow { slices: 2, .. }),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_windowed_counter() {
+        let ring = Ring::with_clock(Duration::from_millis(400), 2, 0, mock_now).unwrap();
+        let counter =
+            WindowedCounter::with_ring(Opts::new("test_counter", "test help"), ring).unwrap();
+        counter.inc();
+        counter.inc_by(2.0);
+        assert_eq!(counter.increase(), 3.0);
+        // A window which spans no time yet has no rate.
+        assert_eq!(counter.rate(), 0.0);
+
+        // The window spans the current slice of 200ms and the previous one.
+        advance(1000);
+        counter.inc();
+        assert_eq!(counter.increase(), 1.0);
+        assert_eq!(counter.counter().get(), 4.0);
+        assert_eq!(counter.rate(), 5.0);
+    }
+}
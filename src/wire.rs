@@ -0,0 +1,55 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! A minimal encoder of the protobuf wire format, for the messages of the
+//! remote write and OTLP exporters which are not part of the generated model.
+
+const WIRE_VARINT: u32 = 0;
+const WIRE_FIXED64: u32 = 1;
+const WIRE_LEN: u32 = 2;
+
+fn write_key(buf: &mut Vec<u8>, field: u32, wire_type: u32) {
+    write_varint(buf, u64::from(field << 3 | wire_type));
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+/// `write_int64` writes an `int64` field.
+#[cfg(feature = "remote-write")]
+pub(crate) fn write_int64(buf: &mut Vec<u8>, field: u32, v: i64) {
+    write_key(buf, field, WIRE_VARINT);
+    write_varint(buf, v as u64);
+}
+
+/// `write_uint64` writes a `uint64`, `bool` or enum field.
+#[cfg(feature = "otlp")]
+pub(crate) fn write_uint64(buf: &mut Vec<u8>, field: u32, v: u64) {
+    write_key(buf, field, WIRE_VARINT);
+    write_varint(buf, v);
+}
+
+/// `write_fixed64` writes a `fixed64` or, reinterpreted, `sfixed64` field.
+#[cfg(feature = "otlp")]
+pub(crate) fn write_fixed64(buf: &mut Vec<u8>, field: u32, v: u64) {
+    write_key(buf, field, WIRE_FIXED64);
+    buf.extend_from_slice(&v.to_le_bytes());
+}
+
+/// `write_double` writes a `double` field.
+pub(crate) fn write_double(buf: &mut Vec<u8>, field: u32, v: f64) {
+    write_key(buf, field, WIRE_FIXED64);
+    buf.extend_from_slice(&v.to_bits().to_le_bytes());
+}
+
+/// `write_bytes` writes a length-delimited field, i.e. a string, bytes, an
+/// embedded message or a packed repeated field.
+pub(crate) fn write_bytes(buf: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    write_key(buf, field, WIRE_LEN);
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
@@ -0,0 +1,19 @@
+// Copyright 2019 TiKV Project Authors. Licensed under Apache-2.0.
+
+//! The coarse clock is process-wide, and unit tests of e.g. auto-flush
+//! metrics start its updater, so its initial state is checked in a process of
+//! its own.
+
+use std::thread;
+
+use prometheus::timer;
+
+#[test]
+fn test_time_update() {
+    assert_eq!(timer::recent_millis(), 0);
+    let now = timer::now_millis();
+    assert_eq!(timer::recent_millis(), now);
+    timer::ensure_updater();
+    thread::sleep(timer::CHECK_UPDATE_INTERVAL * 2);
+    assert!(timer::recent_millis() > now);
+}